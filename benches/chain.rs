@@ -0,0 +1,53 @@
+//! Parses and evaluates long chains like `1+1+1+...`, the time per term should stay the same
+//! when the chain grows. The longest chains have an AST deeper than the default tree depth, so
+//! the bench only raises that limit. Run it with `cargo bench --bench chain`
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use arithmetic_paser::parsemath::{ast, iterative, limits::Limits, parser::Parser};
+
+const ITERATIONS: u32 = 20;
+
+fn report(name: &str, terms: usize, elapsed: Duration) {
+    let per_term = elapsed.as_nanos() as f64 / f64::from(ITERATIONS) / terms as f64;
+    println!("{:<16} {:>6} terms {:>10.1} ns/term", name, terms, per_term);
+}
+
+fn main() {
+    let limits = Limits {
+        max_tree_depth: usize::MAX,
+        ..Limits::default()
+    };
+    for terms in [1_000, 2_000, 4_000, 8_000].iter().copied() {
+        let expr = vec!["1"; terms].join("+");
+
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            black_box(Parser::with_limits(black_box(&expr), limits).unwrap().parse().unwrap());
+        }
+        report("parse", terms, start.elapsed());
+
+        let ast = Parser::with_limits(&expr, limits).unwrap().parse().unwrap();
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            black_box(ast::eval(black_box(&ast)).unwrap());
+        }
+        report("eval", terms, start.elapsed());
+
+        // The iterative versions keep their pending work in the heap
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            black_box(iterative::parse(black_box(&expr)).unwrap());
+        }
+        report("iterative parse", terms, start.elapsed());
+
+        let variables = HashMap::new();
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            black_box(iterative::eval_with(black_box(&ast), &variables).unwrap());
+        }
+        report("iterative eval", terms, start.elapsed());
+    }
+}
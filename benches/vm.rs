@@ -0,0 +1,53 @@
+//! Compares the tree walking `ast::eval_with` with the bytecode virtual machine and the closure
+//! compiler evaluating the same formula many times, run it with `cargo bench --bench vm`
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use arithmetic_paser::parsemath::{
+    ast, closure,
+    parser::Parser,
+    vm::{Bytecode, Vm},
+};
+
+const ITERATIONS: u32 = 200_000;
+
+fn report(name: &str, elapsed: Duration) {
+    let per_iteration = elapsed.as_nanos() as f64 / f64::from(ITERATIONS);
+    println!("{:<24} {:>10.1} ns/iter", name, per_iteration);
+}
+
+fn main() {
+    let formula = "p*r/(1-(1+r)^-n) + sqrt(x^2+y^2)*sin(x) - max(x, y)/2";
+    let names = ["p", "r", "n", "x", "y"];
+    let values = [1000.0, 0.05, 12.0, 3.0, 4.0];
+    let ast = Parser::new(formula).unwrap().parse().unwrap();
+    let variables: HashMap<String, f64> = names
+        .iter()
+        .map(|name| name.to_string())
+        .zip(values.iter().copied())
+        .collect();
+    println!("Evaluating {} {} times", formula, ITERATIONS);
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(ast::eval_with(black_box(&ast), &variables).unwrap());
+    }
+    report("ast::eval_with", start.elapsed());
+
+    let code = Bytecode::compile(&ast, &names).unwrap();
+    let mut vm = Vm::new();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(vm.run(black_box(&code), black_box(&values)).unwrap());
+    }
+    report("vm::Vm::run", start.elapsed());
+
+    let function = closure::compile(&ast, &names).unwrap();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(function(black_box(&values)).unwrap());
+    }
+    report("closure::compile", start.elapsed());
+}
@@ -0,0 +1,5 @@
+//! Arithmetic parser library, holds the **Tokenizer**, the **Parser** and the **AST** used by the
+//! calculator binary so they can be reused from other crates
+#![allow(clippy::upper_case_acronyms)]
+
+pub mod parsemath;
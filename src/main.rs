@@ -1,15 +1,39 @@
+#![allow(clippy::upper_case_acronyms)]
+
 use std::io;
-mod parsemath;
-use parsemath::ast;
-use parsemath::parser::{ParseError, Parser};
+use arithmetic_paser::parsemath::ast;
+use arithmetic_paser::parsemath::limits::Limits;
+use arithmetic_paser::parsemath::parser::{ParseError, Parser};
+use arithmetic_paser::parsemath::rpn::RpnCalculator;
+use arithmetic_paser::parsemath::value::Value;
+use arithmetic_paser::parsemath::visualize;
 
-fn evaluate(expr: String) -> Result<f64, ParseError> {
-    let expr = expr.split_whitespace().collect::<String>();
-    let mut math_parser = Parser::new(&expr)?;
+fn evaluate(expr: String, business: bool) -> Result<Value, ParseError> {
+    // We keep the spaces, they separate a conversion like 1 km in m from a unit named kminm
+    let mut math_parser = if business {
+        Parser::with_business_mode(expr.trim(), Limits::default())?
+    } else {
+        Parser::new(expr.trim())?
+    };
     let ast = math_parser.parse()?;
-    println!("The generated AST is {:?}", ast);
+    println!("The generated AST is:\n{}", visualize::to_ascii_tree(&ast, true));
+    println!("The normalized expression is {}", ast);
+
+    Ok(ast::eval(&ast)?)
+}
 
-    Ok(ast::eval(ast)?)
+// The calculator reads infix expressions or RPN lines, the mode is changed typing its name
+#[derive(Debug, PartialEq)]
+enum Mode {
+    INFIX,
+    RPN,
+    BUSINESS,
+}
+
+fn evaluate_rpn(calculator: &mut RpnCalculator, line: &str) -> Result<(), ParseError> {
+    calculator.execute(line)?;
+    println!("{}", calculator);
+    Ok(())
 }
 
 fn main() {
@@ -17,13 +41,51 @@ fn main() {
     println!("You can calculate value for expression such as 2*3+(4-5)+2^3/4.");
     println!("Allowed numbers are: Positive, Negative and Decimals");
     println!("Supported operands: Add, Subtract, Multiply, Divide, Powerof(^).");
+    println!("Supported functions: sqrt, abs, exp, ln, log, sin, cos, tan, min, max.");
+    println!("Sums like sum(i, 1, 10, i^2), prod, fold and map with lambdas like x -> x^2.");
+    println!("Lists like [1, 2, 3] * 2, v[0] and 1..12 with sum, mean, len and dot.");
+    println!("Matrices like [[1, 2], [3, 4]] with *, ^, transpose, det, inverse and solve.");
+    println!("Units like 5 kg * 9.81 m/s^2 in N and 90 km/h in m/s.");
+    println!("Dates like 2026-10-16 + 30 days and (2026-10-18 - 2026-10-16) in hours.");
+    println!("Constants pi (or π), e, tau and phi, like 2*pi*r.");
+    println!("Percentages like 50% of 80, type business to read 200 + 15% as 230.");
+    println!("Type rpn to enter postfix expressions like 3 4 + 2 * and infix to go back,");
+    println!("the RPN mode also has the swap, dup, drop, clear and neg commands.");
     println!("Enter your arithmetic expression below:");
+    let mut mode = Mode::INFIX;
+    let mut calculator = RpnCalculator::new();
     loop {
         let mut input = String::new();
         match io::stdin().read_line(&mut input) {
+            // The end of the input closes the calculator
+            Ok(0) => break,
             Ok(_) => {
-                match evaluate(input) {
-                    Ok(val) => println!("The computed number is: {}", val),
+                match input.trim() {
+                    "rpn" => {
+                        mode = Mode::RPN;
+                        println!("RPN mode\n{}", calculator);
+                        continue;
+                    }
+                    "infix" => {
+                        mode = Mode::INFIX;
+                        println!("Infix mode");
+                        continue;
+                    }
+                    "business" => {
+                        mode = Mode::BUSINESS;
+                        println!("Business mode");
+                        continue;
+                    }
+                    _ => (),
+                }
+                if mode == Mode::RPN {
+                    if let Err(e) = evaluate_rpn(&mut calculator, &input) {
+                        println!("{}\n{}", e, calculator);
+                    }
+                    continue;
+                }
+                match evaluate(input, mode == Mode::BUSINESS) {
+                    Ok(val) => println!("The computed value is: {}", val),
                     Err(_) => {
                         println!("Error evaluating expression. Please enter a valid expression\n")
                     }
@@ -39,8 +101,34 @@ mod test {
     use super::*;
     #[test]
     fn test_main_evaluate() {
-        let result = evaluate(String::from("5+5+10")).unwrap();
-        let expected = 20.0;
+        let result = evaluate(String::from("5+5+10"), false).unwrap();
+        let expected = Value::NUMBER(20.0);
         assert_eq!(result, expected)
     }
+
+    #[test]
+    fn test_main_evaluate_units() {
+        let result = evaluate(String::from("1 km + 500 m in m\n"), false).unwrap();
+        assert_eq!(result.to_string(), "1500 m");
+    }
+
+    #[test]
+    fn test_main_evaluate_dates() {
+        let result = evaluate(String::from("2026-10-16 + 30 days"), false).unwrap();
+        assert_eq!(result.to_string(), "2026-11-15");
+    }
+
+    #[test]
+    fn test_main_evaluate_business() {
+        assert_eq!(evaluate(String::from("200 + 15%"), true).unwrap(), Value::NUMBER(230.0));
+        assert_eq!(evaluate(String::from("200 + 15%"), false).unwrap(), Value::NUMBER(200.15));
+    }
+
+    #[test]
+    fn test_main_evaluate_rpn() {
+        let mut calculator = RpnCalculator::new();
+        evaluate_rpn(&mut calculator, "3 4 + 2 *").unwrap();
+        assert_eq!(calculator.top(), Some(14.0));
+        assert!(evaluate_rpn(&mut calculator, "+").is_err());
+    }
 }
\ No newline at end of file
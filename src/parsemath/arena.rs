@@ -0,0 +1,393 @@
+//! This module holds the **arena AST**, an alternative to the boxed `Node` where all the nodes
+//! of one or many trees are stored in a single `Vec` and the children are referenced by their
+//! `NodeId`. An id is a small copyable index, so adding a node doesn't allocate a box and the
+//! same subtree can be the child of many nodes.
+//!
+//! An arena created with `Arena::with_hash_consing` stores every distinct subtree once, adding
+//! a node equal to one already in the arena returns the id of the existing node
+
+use std::collections::HashMap;
+
+use super::{ast::Node, datetime::DateTime, parser::NodeBuilder, token::Token};
+
+/// The NodeId struct is the index of a node inside an arena, it's only meaningful for the arena
+/// that returned it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(u32);
+
+impl NodeId {
+    /// Returns the position of the node in the arena
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// The arena node enum holds the same operations than `Node` but the children are ids
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArenaNode {
+    ADD(NodeId, NodeId),
+    SUBTRACT(NodeId, NodeId),
+    MULTIPLY(NodeId, NodeId),
+    DIVIDE(NodeId, NodeId),
+    CARRET(NodeId, NodeId),
+    NEGATIVE(NodeId),
+    FUNCTION(String, Vec<NodeId>),
+    VARIABLE(String),
+    NUMBER(f64),
+    ERROR,
+    LAMBDA(Vec<String>, NodeId),
+    LIST(Vec<NodeId>),
+    INDEX(NodeId, NodeId),
+    RANGE(NodeId, NodeId),
+    QUANTITY(f64, String),
+    CONVERT(NodeId, String),
+    PERCENT(NodeId),
+    DATE(DateTime),
+}
+
+impl ArenaNode {
+    /// Returns the ids of the direct children of the node from left to right
+    pub fn children(&self) -> Vec<NodeId> {
+        use self::ArenaNode::*;
+        match self {
+            ADD(left, right)
+            | SUBTRACT(left, right)
+            | MULTIPLY(left, right)
+            | DIVIDE(left, right)
+            | CARRET(left, right)
+            | INDEX(left, right)
+            | RANGE(left, right) => vec![*left, *right],
+            NEGATIVE(expr) | LAMBDA(_, expr) | CONVERT(expr, _) | PERCENT(expr) => vec![*expr],
+            FUNCTION(_, args) | LIST(args) => args.clone(),
+            VARIABLE(_) | NUMBER(_) | QUANTITY(..) | DATE(_) | ERROR => Vec::new(),
+        }
+    }
+}
+
+// The key of a node for the hash-consing, the numbers are compared by their bits so every
+// number, NaN included, is equal to itself
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Key {
+    BINARY(u8, NodeId, NodeId),
+    NEGATIVE(NodeId),
+    PERCENT(NodeId),
+    FUNCTION(String, Vec<NodeId>),
+    VARIABLE(String),
+    NUMBER(u64),
+    ERROR,
+    LAMBDA(Vec<String>, NodeId),
+    LIST(Vec<NodeId>),
+    QUANTITY(u64, String),
+    CONVERT(NodeId, String),
+    DATE(u64),
+}
+
+impl Key {
+    fn new(node: &ArenaNode) -> Key {
+        use self::ArenaNode::*;
+        match node {
+            ADD(left, right) => Key::BINARY(0, *left, *right),
+            SUBTRACT(left, right) => Key::BINARY(1, *left, *right),
+            MULTIPLY(left, right) => Key::BINARY(2, *left, *right),
+            DIVIDE(left, right) => Key::BINARY(3, *left, *right),
+            CARRET(left, right) => Key::BINARY(4, *left, *right),
+            INDEX(list, index) => Key::BINARY(5, *list, *index),
+            RANGE(from, to) => Key::BINARY(6, *from, *to),
+            NEGATIVE(expr) => Key::NEGATIVE(*expr),
+            PERCENT(expr) => Key::PERCENT(*expr),
+            FUNCTION(name, args) => Key::FUNCTION(name.clone(), args.clone()),
+            VARIABLE(name) => Key::VARIABLE(name.clone()),
+            NUMBER(value) => Key::NUMBER(value.to_bits()),
+            ERROR => Key::ERROR,
+            LAMBDA(parameters, body) => Key::LAMBDA(parameters.clone(), *body),
+            LIST(items) => Key::LIST(items.clone()),
+            QUANTITY(value, unit) => Key::QUANTITY(value.to_bits(), unit.clone()),
+            CONVERT(expr, unit) => Key::CONVERT(*expr, unit.clone()),
+            DATE(date) => Key::DATE(date.seconds().to_bits()),
+        }
+    }
+}
+
+/// The Arena struct owns the nodes, the children of a node are always added before the node
+/// # Example
+/// ```
+/// let mut arena = Arena::with_hash_consing();
+/// let root = Parser::new("(x+1)*(x+1)").unwrap().parse_into(&mut arena).unwrap();
+/// // Both factors are the same node, the arena holds x, 1, x+1 and the product
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Arena {
+    nodes: Vec<ArenaNode>,
+    // The ids of the nodes by key, only when the arena does hash-consing
+    interned: Option<HashMap<Key, NodeId>>,
+}
+
+impl Arena {
+    /// Returns an empty arena where every added node gets a new id
+    pub fn new() -> Self {
+        Arena {
+            nodes: Vec::new(),
+            interned: None,
+        }
+    }
+
+    /// Returns an empty arena that shares the identical subtrees
+    pub fn with_hash_consing() -> Self {
+        Arena {
+            nodes: Vec::new(),
+            interned: Some(HashMap::new()),
+        }
+    }
+
+    /// Adds a node to the arena
+    /// # Arguments
+    /// * `node: ArenaNode` - the node, its children must be ids of this arena
+    /// # Returns
+    /// * `NodeId` - the id of the new node, or of an equal node when the arena does hash-consing
+    pub fn push(&mut self, node: ArenaNode) -> NodeId {
+        debug_assert!(node.children().iter().all(|child| child.index() < self.nodes.len()));
+        let id = NodeId(self.nodes.len() as u32);
+        if let Some(interned) = &mut self.interned {
+            let existing = *interned.entry(Key::new(&node)).or_insert(id);
+            if existing != id {
+                return existing;
+            }
+        }
+        self.nodes.push(node);
+        id
+    }
+
+    /// Returns the node with the given id
+    pub fn get(&self, id: NodeId) -> &ArenaNode {
+        &self.nodes[id.index()]
+    }
+
+    /// Returns the number of nodes in the arena
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns true if the arena has no nodes
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the ids of all the nodes in the order they were added, the children of a node
+    /// always come before the node
+    pub fn ids(&self) -> impl Iterator<Item = NodeId> {
+        (0..self.nodes.len() as u32).map(NodeId)
+    }
+
+    /// Adds a boxed AST to the arena
+    /// # Arguments
+    /// * `node: &Node` - the root of the boxed AST
+    /// # Returns
+    /// * `NodeId` - the id of the root in the arena
+    /// # Example
+    /// ```
+    /// let ast = Parser::new("1+2").unwrap().parse().unwrap();
+    /// let mut arena = Arena::new();
+    /// let root = arena.from_node(&ast);
+    /// // The arena will hold NUMBER(1.0), NUMBER(2.0) and ADD(NodeId(0), NodeId(1))
+    /// ```
+    pub fn from_node(&mut self, node: &Node) -> NodeId {
+        let children: Vec<NodeId> = node.children().into_iter().map(|child| self.from_node(child)).collect();
+        let node = match node {
+            Node::FUNCTION(name, _) => ArenaNode::FUNCTION(name.clone(), children),
+            Node::VARIABLE(name) => ArenaNode::VARIABLE(name.clone()),
+            Node::NUMBER(value) => ArenaNode::NUMBER(*value),
+            Node::ERROR => ArenaNode::ERROR,
+            Node::LAMBDA(parameters, _) => ArenaNode::LAMBDA(parameters.clone(), children[0]),
+            Node::LIST(_) => ArenaNode::LIST(children),
+            Node::INDEX(..) => ArenaNode::INDEX(children[0], children[1]),
+            Node::RANGE(..) => ArenaNode::RANGE(children[0], children[1]),
+            Node::QUANTITY(value, unit) => ArenaNode::QUANTITY(*value, unit.clone()),
+            Node::CONVERT(_, unit) => ArenaNode::CONVERT(children[0], unit.clone()),
+            Node::PERCENT(_) => ArenaNode::PERCENT(children[0]),
+            Node::DATE(date) => ArenaNode::DATE(*date),
+            Node::NEGATIVE(_) => ArenaNode::NEGATIVE(children[0]),
+            Node::ADD(..) => ArenaNode::ADD(children[0], children[1]),
+            Node::SUBTRACT(..) => ArenaNode::SUBTRACT(children[0], children[1]),
+            Node::MULTIPLY(..) => ArenaNode::MULTIPLY(children[0], children[1]),
+            Node::DIVIDE(..) => ArenaNode::DIVIDE(children[0], children[1]),
+            Node::CARRET(..) => ArenaNode::CARRET(children[0], children[1]),
+        };
+        self.push(node)
+    }
+
+    /// Converts a tree of the arena to a boxed AST, a shared subtree is copied in every place
+    /// where it's used
+    /// # Arguments
+    /// * `id: NodeId` - the id of the root
+    /// # Returns
+    /// * `Node` - the boxed AST
+    pub fn to_node(&self, id: NodeId) -> Node {
+        let boxed = |id: &NodeId| Box::new(self.to_node(*id));
+        match self.get(id) {
+            ArenaNode::ADD(left, right) => Node::ADD(boxed(left), boxed(right)),
+            ArenaNode::SUBTRACT(left, right) => Node::SUBTRACT(boxed(left), boxed(right)),
+            ArenaNode::MULTIPLY(left, right) => Node::MULTIPLY(boxed(left), boxed(right)),
+            ArenaNode::DIVIDE(left, right) => Node::DIVIDE(boxed(left), boxed(right)),
+            ArenaNode::CARRET(left, right) => Node::CARRET(boxed(left), boxed(right)),
+            ArenaNode::NEGATIVE(expr) => Node::NEGATIVE(boxed(expr)),
+            ArenaNode::FUNCTION(name, args) => {
+                Node::FUNCTION(name.clone(), args.iter().map(|arg| self.to_node(*arg)).collect())
+            }
+            ArenaNode::VARIABLE(name) => Node::VARIABLE(name.clone()),
+            ArenaNode::NUMBER(value) => Node::NUMBER(*value),
+            ArenaNode::ERROR => Node::ERROR,
+            ArenaNode::LAMBDA(parameters, body) => Node::LAMBDA(parameters.clone(), boxed(body)),
+            ArenaNode::LIST(items) => Node::LIST(items.iter().map(|item| self.to_node(*item)).collect()),
+            ArenaNode::INDEX(list, index) => Node::INDEX(boxed(list), boxed(index)),
+            ArenaNode::RANGE(from, to) => Node::RANGE(boxed(from), boxed(to)),
+            ArenaNode::QUANTITY(value, unit) => Node::QUANTITY(*value, unit.clone()),
+            ArenaNode::CONVERT(expr, unit) => Node::CONVERT(boxed(expr), unit.clone()),
+            ArenaNode::PERCENT(expr) => Node::PERCENT(boxed(expr)),
+            ArenaNode::DATE(date) => Node::DATE(*date),
+        }
+    }
+}
+
+// The parser adds the nodes directly to the arena
+impl NodeBuilder for Arena {
+    type Output = NodeId;
+
+    fn number(&mut self, value: f64) -> NodeId {
+        self.push(ArenaNode::NUMBER(value))
+    }
+
+    fn variable(&mut self, name: String) -> NodeId {
+        self.push(ArenaNode::VARIABLE(name))
+    }
+
+    fn negative(&mut self, expr: NodeId) -> NodeId {
+        self.push(ArenaNode::NEGATIVE(expr))
+    }
+
+    fn function(&mut self, name: String, args: Vec<NodeId>) -> NodeId {
+        self.push(ArenaNode::FUNCTION(name, args))
+    }
+
+    fn binary(&mut self, operator: &Token, left: NodeId, right: NodeId) -> NodeId {
+        let constructor = match operator {
+            Token::ADD => ArenaNode::ADD,
+            Token::SUBTRACT => ArenaNode::SUBTRACT,
+            Token::MULTIPLY => ArenaNode::MULTIPLY,
+            Token::DIVIDE => ArenaNode::DIVIDE,
+            Token::RANGE => ArenaNode::RANGE,
+            _ => ArenaNode::CARRET,
+        };
+        self.push(constructor(left, right))
+    }
+
+    fn error(&mut self) -> NodeId {
+        self.push(ArenaNode::ERROR)
+    }
+
+    fn lambda(&mut self, parameters: Vec<String>, body: NodeId) -> NodeId {
+        self.push(ArenaNode::LAMBDA(parameters, body))
+    }
+
+    fn list(&mut self, items: Vec<NodeId>) -> NodeId {
+        self.push(ArenaNode::LIST(items))
+    }
+
+    fn index(&mut self, list: NodeId, index: NodeId) -> NodeId {
+        self.push(ArenaNode::INDEX(list, index))
+    }
+
+    fn quantity(&mut self, value: f64, unit: String) -> NodeId {
+        self.push(ArenaNode::QUANTITY(value, unit))
+    }
+
+    fn date(&mut self, date: DateTime) -> NodeId {
+        self.push(ArenaNode::DATE(date))
+    }
+
+    fn convert(&mut self, expr: NodeId, unit: String) -> NodeId {
+        self.push(ArenaNode::CONVERT(expr, unit))
+    }
+
+    fn percent(&mut self, expr: NodeId) -> NodeId {
+        self.push(ArenaNode::PERCENT(expr))
+    }
+
+    fn percent_operand(&self, node: &NodeId) -> Option<NodeId> {
+        match self.get(*node) {
+            ArenaNode::PERCENT(expr) => Some(*expr),
+            _ => None,
+        }
+    }
+
+    fn variable_name(&self, node: &NodeId) -> Option<String> {
+        match self.get(*node) {
+            ArenaNode::VARIABLE(name) => Some(name.clone()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsemath::parser::Parser;
+
+    fn parse(expr: &str) -> Node {
+        let mut parser = Parser::new(expr).unwrap();
+        parser.parse().unwrap()
+    }
+
+    #[test]
+    fn test_arena_parse_into() {
+        let mut arena = Arena::new();
+        let root = Parser::new("1+2*x").unwrap().parse_into(&mut arena).unwrap();
+        assert_eq!(arena.len(), 5);
+        match arena.get(root) {
+            ArenaNode::ADD(left, right) => {
+                assert_eq!(arena.get(*left), &ArenaNode::NUMBER(1.0));
+                assert_eq!(arena.get(*right).children().len(), 2);
+            }
+            node => panic!("Expected an addition, got {:?}", node),
+        }
+    }
+
+    #[test]
+    fn test_arena_round_trip() {
+        for expr in ["1+2*3-4", "(1+x)^-(3/y)", "2^3^4", "--1", "max(x,sqrt(4))*abs(-y)", "[1,x][0]+(1..3)", "3 km/h*x in m/s", "50% of 80-x%"].iter() {
+            let ast = parse(expr);
+            let mut arena = Arena::new();
+            let root = arena.from_node(&ast);
+            assert_eq!(arena.to_node(root), ast);
+            // Parsing into the arena gives the same nodes than converting the boxed AST
+            let mut parsed = Arena::new();
+            let parsed_root = Parser::new(expr).unwrap().parse_into(&mut parsed).unwrap();
+            assert_eq!(parsed.nodes, arena.nodes);
+            assert_eq!(parsed_root, root);
+        }
+    }
+
+    #[test]
+    fn test_arena_hash_consing() {
+        let ast = parse("(x+1)*(x+1)+sqrt(x+1)");
+        let mut arena = Arena::new();
+        arena.from_node(&ast);
+        assert_eq!(arena.len(), 12);
+
+        let mut shared = Arena::with_hash_consing();
+        let root = shared.from_node(&ast);
+        // x, 1, x+1, the product, the call and the sum
+        assert_eq!(shared.len(), 6);
+        match shared.get(root) {
+            ArenaNode::ADD(product, call) => {
+                assert_eq!(shared.get(*product), &ArenaNode::MULTIPLY(NodeId(2), NodeId(2)));
+                assert_eq!(shared.get(*call), &ArenaNode::FUNCTION("sqrt".into(), vec![NodeId(2)]));
+            }
+            node => panic!("Expected an addition, got {:?}", node),
+        }
+        assert_eq!(shared.to_node(root), ast);
+        // A second tree reuses the nodes already in the arena
+        let other = Parser::new("x+1").unwrap().parse_into(&mut shared).unwrap();
+        assert_eq!(other, NodeId(2));
+        assert_eq!(shared.len(), 6);
+    }
+}
@@ -1,9 +1,32 @@
+use std::collections::HashMap;
 use std::error;
+use std::fmt;
+use std::sync::Arc;
+use super::{
+    constants::Constants,
+    datetime::{self, DateTime},
+    functions::{self, Aggregate, HigherOrder, MatrixFunction, CONDITIONAL_NAME},
+    limits::{Budget, LimitError, Limits},
+    matrix::Matrix,
+    program::UserFunction,
+    token::OperPrec,
+    units,
+    value::{self, Lambda, Value},
+};
 
 /// The node enum hold all the operation variant to make the sintax tree
 /// # Arguments
 /// * `left: Box<Node>` - the left side of an operation
 /// * `right: Box<Node>` - the right side of an operation
+/// * `name: String, args: Vec<Node>` - the name and the arguments of a function call
+/// * `name: String` - the name of a variable, its value is given when the AST is evaluated
+/// * `parameters: Vec<String>, body: Box<Node>` - the parameters and the body of a lambda
+/// * `items: Vec<Node>` - the elements of a list
+/// * `list: Box<Node>, index: Box<Node>` - the list and the position of an element, from zero
+/// * `from: Box<Node>, to: Box<Node>` - the bounds of a range, both included
+/// * `value: f64, unit: String` - the number and the unit of a quantity
+/// * `expr: Box<Node>, unit: String` - the value and the unit of a conversion
+/// * `date: DateTime` - a date and a time of the day
 /// # Example 
 /// ```
 /// // To represent an adition we can use the addition variant with two numeric values
@@ -18,97 +41,1044 @@ pub enum Node {
     DIVIDE(Box<Node>, Box<Node>),
     CARRET(Box<Node>, Box<Node>),
     NEGATIVE(Box<Node>),
-    NUMBER(f64) // All the numbers are treated like f64
+    FUNCTION(String, Vec<Node>), // A call to a built-in function like sqrt(2)
+    VARIABLE(String), // A name without arguments like x
+    NUMBER(f64), // All the numbers are treated like f64
+    LAMBDA(Vec<String>, Box<Node>), // An anonymous function like x -> x^2
+    LIST(Vec<Node>), // A list like [1, 2, 3]
+    INDEX(Box<Node>, Box<Node>), // An element of a list like v[0]
+    RANGE(Box<Node>, Box<Node>), // The list of the integers between two bounds like 1..10
+    QUANTITY(f64, String), // A number with a unit like 3 km or 9.81 m/s^2
+    CONVERT(Box<Node>, String), // A value written in another unit like x in km
+    PERCENT(Box<Node>), // A percentage like 15%, the hundredth part of its operand
+    DATE(DateTime), // A date like 2026-10-16, a duration added to it gives another date
+    ERROR, // A part of the input that couldn't be parsed, only built by the recovery mode
+}
+
+// Drops the children with a stack instead of recursion, so dropping a very deep AST doesn't
+// overflow the stack. The numbers and variables have no children and are dropped as usual
+impl Drop for Node {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        take_children(self, &mut pending);
+        while let Some(mut node) = pending.pop() {
+            // The node is dropped without children at the end of the iteration
+            take_children(&mut node, &mut pending);
+        }
+    }
+}
+
+// Moves the children that have children of their own out of the node
+fn take_children(node: &mut Node, pending: &mut Vec<Node>) {
+    use self::Node::*;
+    let mut take = |child: &mut Node| {
+        if !matches!(child, NUMBER(_) | VARIABLE(_) | ERROR) {
+            pending.push(std::mem::replace(child, NUMBER(0.0)));
+        }
+    };
+    match node {
+        ADD(left, right)
+        | SUBTRACT(left, right)
+        | MULTIPLY(left, right)
+        | DIVIDE(left, right)
+        | CARRET(left, right)
+        | INDEX(left, right)
+        | RANGE(left, right) => {
+            take(left);
+            take(right);
+        }
+        NEGATIVE(expr) | LAMBDA(_, expr) | CONVERT(expr, _) | PERCENT(expr) => take(expr),
+        FUNCTION(_, args) | LIST(args) => args.iter_mut().for_each(take),
+        VARIABLE(_) | NUMBER(_) | QUANTITY(..) | DATE(_) | ERROR => (),
+    }
+}
+
+/// Eval error enum contains the errors that can happen when an AST is evaluated
+/// # Arguments
+/// * `name: String` - the name of the function, variable or constant that produced the error
+/// * `expected: usize, found: usize` - the number of arguments, values or rows expected and received
+/// * `limit: LimitError` - the resource limit exceeded by the evaluation
+/// * `expected: String, found: String` - the types expected and received, like `a number`
+/// * `index: f64, length: usize` - an index that isn't the position of an element of a list
+/// * `left: usize, right: usize` - the lengths of two lists that should be the same
+/// * `operation: String, shape: (usize, usize)` - a matrix operation and the rows and columns
+///   of its operands, the shapes don't fit the operation
+/// * `left: String, right: String` - the dimensions of two quantities that should be the same,
+///   written with the SI base units like `m/s`
+/// * `unit: String` - a unit that isn't in the table of units
+/// * `name: String` - a name that can't be registered as a constant, like a function
+/// * `name: String` - a function that the numeric evaluators don't compute, like `sum` whose body
+///   binds its own variable
+#[derive(Clone, Debug, PartialEq)]
+pub enum EvalError {
+    UnknownFunction(String),
+    WrongArgumentCount(String, usize, usize),
+    UnknownVariable(String),
+    WrongVariableCount(usize, usize),
+    WrongColumnLength(String, usize, usize),
+    LimitExceeded(LimitError),
+    SyntaxError,
+    ConstantAssignment(String),
+    TypeMismatch(String, String),
+    IndexOutOfRange(f64, usize),
+    LengthMismatch(usize, usize),
+    ShapeMismatch(String, (usize, usize), (usize, usize)),
+    NotSquare(String, (usize, usize)),
+    SingularMatrix,
+    DimensionMismatch(String, String),
+    UnknownUnit(String),
+    InvalidConstant(String),
+    UnsupportedFunction(String),
+}
+
+// The trait for display with format! or println!
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self {
+            self::EvalError::UnknownFunction(name) => write!(f, "Unknown function {}", name),
+            self::EvalError::WrongArgumentCount(name, expected, found) => write!(
+                f,
+                "The function {} takes {} arguments but {} were given",
+                name, expected, found
+            ),
+            self::EvalError::UnknownVariable(name) => write!(f, "Unknown variable {}", name),
+            self::EvalError::WrongVariableCount(expected, found) => write!(
+                f,
+                "Expected values for {} variables but {} were given",
+                expected, found
+            ),
+            self::EvalError::WrongColumnLength(name, expected, found) => write!(
+                f,
+                "The column {} has {} rows but {} were expected",
+                name, found, expected
+            ),
+            self::EvalError::LimitExceeded(limit) => write!(f, "{}", limit),
+            self::EvalError::SyntaxError => write!(f, "The expression has syntax errors"),
+            self::EvalError::ConstantAssignment(name) => write!(f, "The constant {} can't be assigned", name),
+            self::EvalError::TypeMismatch(expected, found) => write!(f, "Expected {} but found {}", expected, found),
+            self::EvalError::IndexOutOfRange(index, length) => write!(
+                f,
+                "There is no element {} in a list of {} elements",
+                index, length
+            ),
+            self::EvalError::LengthMismatch(left, right) => write!(
+                f,
+                "The lists have {} and {} elements but must have the same length",
+                left, right
+            ),
+            self::EvalError::ShapeMismatch(operation, left, right) => write!(
+                f,
+                "Cannot {} a {}x{} matrix and a {}x{} matrix",
+                operation, left.0, left.1, right.0, right.1
+            ),
+            self::EvalError::NotSquare(operation, shape) => write!(
+                f,
+                "The {} needs a square matrix but found a {}x{} matrix",
+                operation, shape.0, shape.1
+            ),
+            self::EvalError::SingularMatrix => write!(f, "The matrix is singular and has no inverse"),
+            self::EvalError::DimensionMismatch(left, right) => write!(
+                f,
+                "The dimensions {} and {} are incompatible",
+                left, right
+            ),
+            self::EvalError::UnknownUnit(unit) => write!(f, "Unknown unit {}", unit),
+            self::EvalError::InvalidConstant(name) => write!(f, "The name {} can't be a constant", name),
+            self::EvalError::UnsupportedFunction(name) => {
+                write!(f, "The function {} isn't supported by this evaluator", name)
+            }
+        }
+    }
+}
+
+impl error::Error for EvalError {}
+
+impl Node {
+    /// Returns the precedence of the operation held by the node, this is the same precedence
+    /// that the parser gives to the token that produced the node
+    /// # Returns
+    /// `OperPrec` - An `OperPrec` enum variant, numbers, negatives, lists, indexes and percentages bind tighter
+    ///   than any operator, a lambda takes everything at its right and the bounds of a range are
+    ///   whole expressions, so they are looser than any operator. A quantity is the product of
+    ///   its number and its unit and a conversion applies to the whole expression at its left
+    /// # Example
+    /// ```
+    /// let node = Node::ADD(Box::new(Node::NUMBER(1.0)), Box::new(Node::NUMBER(2.0)));
+    /// let oper_prec = node.get_oper_prec();
+    /// // This will be OperPrec::ADDSUB
+    /// ```
+    pub fn get_oper_prec(&self) -> OperPrec {
+        use self::Node::*;
+        match *self {
+            ADD(..) | SUBTRACT(..) => OperPrec::ADDSUB,
+            MULTIPLY(..) | DIVIDE(..) | QUANTITY(..) => OperPrec::MULDIV,
+            CARRET(..) => OperPrec::POWER,
+            NEGATIVE(..) | FUNCTION(..) | VARIABLE(..) | NUMBER(..) | DATE(..) | ERROR => OperPrec::NEGATIVE,
+            LIST(..) | INDEX(..) | PERCENT(..) => OperPrec::NEGATIVE,
+            LAMBDA(..) | RANGE(..) | CONVERT(..) => OperPrec::DEFAULTZERO,
+        }
+    }
+
+    /// Returns the direct children of the node from left to right
+    /// # Returns
+    /// `Vec<&Node>` - the operands of an operation, the arguments of a call, the body of a lambda,
+    ///   the items of a list or nothing for a number
+    /// # Example
+    /// ```
+    /// let node = Node::NEGATIVE(Box::new(Node::NUMBER(1.0)));
+    /// let children = node.children();
+    /// // This will be vec![&Node::NUMBER(1.0)]
+    /// ```
+    pub fn children(&self) -> Vec<&Node> {
+        use self::Node::*;
+        match self {
+            ADD(left, right)
+            | SUBTRACT(left, right)
+            | MULTIPLY(left, right)
+            | DIVIDE(left, right)
+            | CARRET(left, right)
+            | INDEX(left, right)
+            | RANGE(left, right) => vec![left, right],
+            NEGATIVE(expr) | LAMBDA(_, expr) | CONVERT(expr, _) | PERCENT(expr) => vec![expr],
+            FUNCTION(_, args) | LIST(args) => args.iter().collect(),
+            VARIABLE(_) | NUMBER(_) | QUANTITY(..) | DATE(_) | ERROR => Vec::new(),
+        }
+    }
 }
 
 /// The eval function takes an operation node and resolve the operation if we take an addition node
 /// for example: `Node::ADD(left, right)` we can evaluate the addition with this function 
 /// # Arguments
-/// * `expr: Node` - Is a node representing an operation node, number node or negative node
+/// * `expr: &Node` - Is a node representing an operation node, number node or negative node, the
+///   node is borrowed so the same AST can be evaluated many times
 /// 
 /// # Returns
-/// * `Result<f64, Box<dyn error::Error>>` - the eval function returns a `Ok(number)` or `Err(err)` 
+/// * `Result<Value, Box<dyn error::Error>>` - the eval function returns a `Ok(value)` or `Err(err)`,
+///   the value is a number, a lambda or a list
 ///
 /// 
 /// # Example
 /// ```
 /// // We create an addition node
 /// let addition = Node::ADD(Box::new(Node::Number(5.0)), Box::new(Node::Number(5.0)))
-/// let evaluated = eval(addition_node); // This should return a result with Ok(10.0)
-pub fn eval(expr: Node) -> Result<f64, Box<dyn error::Error>> {
+/// let evaluated = eval(&addition_node); // This should return a result with Ok(Value::NUMBER(10.0))
+pub fn eval(expr: &Node) -> Result<Value, Box<dyn error::Error>> {
+    eval_with(expr, &HashMap::new())
+}
+
+/// The eval_with function evaluates an AST that uses variables, the value of each variable is
+/// taken from a map. The evaluation isn't limited, the ASTs built by hand from untrusted data
+/// should be evaluated with `eval_limited`
+/// # Arguments
+/// * `expr: &Node` - the node to evaluate
+/// * `variables: &HashMap<String, f64>` - the values of the variables by name
+///
+/// # Returns
+/// * `Result<Value, Box<dyn error::Error>>` - the value or an error if a variable has no value
+///
+/// # Example
+/// ```
+/// let mut variables = HashMap::new();
+/// variables.insert("x".to_string(), 2.0);
+/// let product = Node::MULTIPLY(Box::new(Node::VARIABLE("x".into())), Box::new(Node::NUMBER(5.0)));
+/// let evaluated = eval_with(&product, &variables); // This should return a result with Ok(Value::NUMBER(10.0))
+/// ```
+pub fn eval_with(expr: &Node, variables: &HashMap<String, f64>) -> Result<Value, Box<dyn error::Error>> {
+    Ok(eval_limited(expr, variables, Limits::unlimited())?)
+}
+
+/// The eval_limited function evaluates an AST from an untrusted source, the evaluation stops
+/// with an error when the AST is too deep or takes too many steps
+/// # Arguments
+/// * `expr: &Node` - the node to evaluate
+/// * `variables: &HashMap<String, f64>` - the values of the variables by name
+/// * `limits: Limits` - the maximum depth and number of steps, every evaluated node is a step
+///
+/// # Returns
+/// * `Result<Value, EvalError>` - the value or an error, `EvalError::LimitExceeded` if a limit
+///   was exceeded
+///
+/// # Example
+/// ```
+/// let ast = Parser::new("1+2").unwrap().parse().unwrap();
+/// let limits = Limits { max_eval_steps: 2, ..Limits::default() };
+/// let evaluated = eval_limited(&ast, &HashMap::new(), limits);
+/// // This will be Err(EvalError::LimitExceeded(LimitError::TooManySteps(2)))
+/// ```
+pub fn eval_limited(expr: &Node, variables: &HashMap<String, f64>, limits: Limits) -> Result<Value, EvalError> {
+    eval_with_constants(expr, variables, &Constants::new(), limits)
+}
+
+/// The eval_with_constants function evaluates an AST that uses the constants registered by the
+/// library user, the other evaluations only know the built-in constants like `pi`
+/// # Arguments
+/// * `expr: &Node` - the node to evaluate
+/// * `variables: &HashMap<String, f64>` - the values of the variables by name, a variable hides
+///   the constant with the same name
+/// * `constants: &Constants` - the built-in and the registered constants
+/// * `limits: Limits` - the maximum depth and number of steps
+///
+/// # Returns
+/// * `Result<Value, EvalError>` - the value or an error
+///
+/// # Example
+/// ```
+/// let mut constants = Constants::new();
+/// constants.register("g", 9.81).unwrap();
+/// let ast = Parser::new("g/2").unwrap().parse().unwrap();
+/// let evaluated = eval_with_constants(&ast, &HashMap::new(), &constants, Limits::default());
+/// // This will be Ok(Value::NUMBER(4.905))
+/// ```
+pub fn eval_with_constants(
+    expr: &Node,
+    variables: &HashMap<String, f64>,
+    constants: &Constants,
+    limits: Limits,
+) -> Result<Value, EvalError> {
+    let functions = HashMap::new();
+    let scope = Scope {
+        parameters: None,
+        globals: Globals::NUMBERS(variables),
+        constants,
+        functions: &functions,
+    };
+    eval_scoped(expr, &scope, limits)
+}
+
+// The variables given by the caller of the evaluation or the values of a program
+#[derive(Clone, Copy)]
+pub(crate) enum Globals<'a> {
+    NUMBERS(&'a HashMap<String, f64>),
+    VALUES(&'a HashMap<String, Value>),
+}
+
+// The names that an evaluation can read, the parameters of the function being evaluated hide
+// the globals with the same name
+#[derive(Clone, Copy)]
+pub(crate) struct Scope<'a> {
+    pub(crate) parameters: Option<&'a HashMap<String, Value>>,
+    pub(crate) globals: Globals<'a>,
+    pub(crate) constants: &'a Constants,
+    pub(crate) functions: &'a HashMap<String, UserFunction>,
+}
+
+impl<'a> Scope<'a> {
+    // Returns the value of a parameter, a global or a constant
+    fn lookup(&self, name: &str) -> Option<Value> {
+        if let Some(value) = self.parameters.and_then(|parameters| parameters.get(name)) {
+            return Some(value.clone());
+        }
+        let global = match self.globals {
+            Globals::NUMBERS(variables) => variables.get(name).map(|value| Value::NUMBER(*value)),
+            Globals::VALUES(values) => values.get(name).cloned(),
+        };
+        global.or_else(|| self.constants.get(name).map(Value::NUMBER))
+    }
+
+    // Returns the value of a name, a user function used as a value is a lambda
+    fn get(&self, name: &str) -> Option<Value> {
+        self.lookup(name).or_else(|| {
+            let function = self.functions.get(name)?;
+            Some(Value::LAMBDA(Arc::new(Lambda {
+                parameters: function.parameters.clone(),
+                body: function.body.clone(),
+                captured: HashMap::new(),
+            })))
+        })
+    }
+}
+
+// Evaluates an AST that can call the user functions of the scope
+pub(crate) fn eval_scoped(expr: &Node, scope: &Scope, limits: Limits) -> Result<Value, EvalError> {
+    eval_node(expr, scope, &mut Budget::new(limits), 1)
+}
+
+fn eval_node(expr: &Node, scope: &Scope, budget: &mut Budget, depth: usize) -> Result<Value, EvalError> {
     use self::Node::*;
+    // We count the node before going down, so a deep tree stops before the stack overflows
+    budget.step(depth).map_err(EvalError::LimitExceeded)?;
     match expr {
         // If we have a number we return the value
-        NUMBER(value) => Ok(value),
-        // If we have an operation node we extract the values and evaluate them
-        ADD(expr1, expr2) => Ok(eval(*expr1)? + eval(*expr2)?), 
-        SUBTRACT(expr1, expr2) => Ok(eval(*expr1)? - eval(*expr2)?),
-        MULTIPLY(expr1, expr2) => Ok(eval(*expr1)? * eval(*expr2)?),
-        DIVIDE(expr1, expr2) => Ok(eval(*expr1)? / eval(*expr2)?),
-        CARRET(expr1, expr2) => Ok(eval(*expr1)?.powf(eval(*expr2)?)),
+        NUMBER(value) => Ok(Value::NUMBER(*value)),
         // If we have a negative number, we evaluate to extract the value
-        // and we apply the "-" operation
-        NEGATIVE(expr1) => Ok(-(eval(*expr1)?)),
+        // and we apply the "-" operation to the number or to every element of the list
+        NEGATIVE(expr1) => {
+            let value = eval_node(expr1, scope, budget, depth + 1)?;
+            negate(value)
+        }
+        // If we have an operation node we extract the values and evaluate them, there is a
+        // single call for all the operators to keep the frame small
+        ADD(expr1, expr2)
+        | SUBTRACT(expr1, expr2)
+        | MULTIPLY(expr1, expr2)
+        | DIVIDE(expr1, expr2)
+        | CARRET(expr1, expr2) => {
+            let left = eval_node(expr1, scope, budget, depth + 1)?;
+            let right = eval_node(expr2, scope, budget, depth + 1)?;
+            apply(expr, left, right)
+        }
+        _ => eval_other(expr, scope, budget, depth),
+    }
+}
+
+// Evaluates the nodes that aren't operations. They are apart from eval_node, so a deep tree of
+// operations only needs the small frame of eval_node on every level
+fn eval_other(expr: &Node, scope: &Scope, budget: &mut Budget, depth: usize) -> Result<Value, EvalError> {
+    use self::Node::*;
+    match expr {
+        // If we have a variable we look for its value
+        VARIABLE(name) => scope.get(name).ok_or_else(|| EvalError::UnknownVariable(name.clone())),
+        // A placeholder of the recovery mode has no value
+        ERROR => Err(EvalError::SyntaxError),
+        LAMBDA(parameters, body) => Ok(make_lambda(parameters, body, scope)),
+        // If we have a function we evaluate the arguments and call the function
+        FUNCTION(name, args) => eval_call(name, args, scope, budget, depth + 1),
+        LIST(items) => eval_list(items, scope, budget, depth + 1),
+        INDEX(list, index) => {
+            let list = eval_node(list, scope, budget, depth + 1)?;
+            let index = eval_number(index, scope, budget, depth + 1)?;
+            element(list, index)
+        }
+        QUANTITY(value, unit) => units::quantity(*value, unit),
+        DATE(date) => Ok(Value::DATE(*date)),
+        CONVERT(expr, unit) => {
+            let value = eval_node(expr, scope, budget, depth + 1)?;
+            units::convert(value, unit)
+        }
+        PERCENT(expr) => {
+            let value = eval_node(expr, scope, budget, depth + 1)?;
+            percent(value)
+        }
+        RANGE(from, to) => {
+            let mut numbers = Vec::new();
+            for i in eval_range(from, to, scope, budget, depth + 1)? {
+                // Every element is a step, so a huge range stops at the limit
+                budget.step(depth).map_err(EvalError::LimitExceeded)?;
+                numbers.push(i);
+            }
+            Ok(Value::LIST(numbers))
+        }
+        _ => unreachable!(),
+    }
+}
+
+// Evaluates the items of a list, the items are numbers or the lists with the rows of a matrix
+fn eval_list(items: &[Node], scope: &Scope, budget: &mut Budget, depth: usize) -> Result<Value, EvalError> {
+    collect_list(items.iter().map(|item| eval_node(item, scope, budget, depth)), items.len())
+}
+
+// Builds a list or a matrix from the values of the items, the first error stops it
+fn collect_list(
+    items: impl Iterator<Item = Result<Value, EvalError>>,
+    length: usize,
+) -> Result<Value, EvalError> {
+    let mut numbers = Vec::with_capacity(length);
+    let mut rows = Vec::new();
+    for item in items {
+        match item? {
+            Value::NUMBER(number) if rows.is_empty() => numbers.push(number),
+            Value::LIST(row) if numbers.is_empty() => rows.push(row),
+            // We name the type of the first item as the expected one
+            other => {
+                let expected = if rows.is_empty() { value::NUMBER_TYPE } else { value::LIST_TYPE };
+                return Err(EvalError::TypeMismatch(expected.to_string(), other.type_name().to_string()));
+            }
+        }
+    }
+    if rows.is_empty() {
+        return Ok(Value::LIST(numbers));
+    }
+    Matrix::from_rows(rows).map(Value::MATRIX)
+}
+
+// Applies an operator node to the values of its operands. A matrix changes the meaning of `*`,
+// the matrix product, and of `^`, an integer power, the other cases work element by element
+fn apply(expr: &Node, left: Value, right: Value) -> Result<Value, EvalError> {
+    match (expr, &left, &right) {
+        (Node::MULTIPLY(..), Value::MATRIX(_), _) | (Node::MULTIPLY(..), _, Value::MATRIX(_)) => {
+            value::multiply(left, right)
+        }
+        (Node::CARRET(..), Value::MATRIX(base), _) => value::power(base, right),
+        (_, Value::DATE(_), _) | (_, _, Value::DATE(_)) => datetime::apply(expr, left, right),
+        (_, Value::QUANTITY(_), _) | (_, _, Value::QUANTITY(_)) => units::apply(expr, left, right),
+        _ => value::broadcast(left, right, operation(expr)),
+    }
+}
+
+// Returns the element of a list at an index, the element of a matrix is a row so m[i][j] is the
+// element in the row i
+fn element(list: Value, index: f64) -> Result<Value, EvalError> {
+    let length = match &list {
+        Value::MATRIX(matrix) => matrix.shape().0,
+        list => list.as_list()?.len(),
+    };
+    // The index must be the position of an element, a whole number from zero
+    if index < 0.0 || index.fract() != 0.0 || index >= length as f64 {
+        return Err(EvalError::IndexOutOfRange(index, length));
+    }
+    match list {
+        Value::MATRIX(matrix) => Ok(Value::LIST(matrix.row(index as usize).to_vec())),
+        list => Ok(Value::NUMBER(list.as_list()?[index as usize])),
+    }
+}
+
+// A percentage of a number or of every element of a list
+fn percent(value: Value) -> Result<Value, EvalError> {
+    value::broadcast(value, Value::NUMBER(100.0), |value, hundred| value / hundred)
+}
+
+// The minus sign of a number, a quantity or every element of a list or a matrix
+fn negate(value: Value) -> Result<Value, EvalError> {
+    match value {
+        Value::QUANTITY(quantity) => Ok(Value::QUANTITY(quantity.negate())),
+        value => value::broadcast(value, Value::NUMBER(0.0), |value, _| -value),
+    }
+}
+
+// Returns the operation on two numbers of an operator node
+fn operation(expr: &Node) -> fn(f64, f64) -> f64 {
+    match expr {
+        Node::ADD(..) => |left, right| left + right,
+        Node::SUBTRACT(..) => |left, right| left - right,
+        Node::MULTIPLY(..) => |left, right| left * right,
+        Node::DIVIDE(..) => |left, right| left / right,
+        _ => f64::powf,
+    }
+}
+
+// Evaluates a node that must be a number, like a condition or the bound of a range
+fn eval_number(expr: &Node, scope: &Scope, budget: &mut Budget, depth: usize) -> Result<f64, EvalError> {
+    eval_node(expr, scope, budget, depth)?.as_number()
+}
+
+// A lambda keeps the parameters that its body can read, the globals are read when it's called
+fn make_lambda(parameters: &[String], body: &Node, scope: &Scope) -> Value {
+    Value::LAMBDA(Arc::new(Lambda {
+        parameters: parameters.to_vec(),
+        body: body.clone(),
+        captured: scope.parameters.cloned().unwrap_or_default(),
+    }))
+}
+
+fn eval_call(name: &str, args: &[Node], scope: &Scope, budget: &mut Budget, depth: usize) -> Result<Value, EvalError> {
+    // Only the branch chosen by the condition is evaluated, so a recursive function can stop
+    if name == CONDITIONAL_NAME {
+        if args.len() != 3 {
+            return Err(EvalError::WrongArgumentCount(name.to_string(), 3, args.len()));
+        }
+        let condition = eval_number(&args[0], scope, budget, depth)?;
+        let branch = if condition != 0.0 { &args[1] } else { &args[2] };
+        return eval_node(branch, scope, budget, depth);
+    }
+    if let Some(function) = HigherOrder::from_name(name) {
+        if args.len() == function.arity() {
+            return eval_higher_order(function, args, scope, budget, depth);
+        }
+        // The sum of a list has the name of the higher-order sum
+        if Aggregate::from_name(name).is_none() {
+            return Err(EvalError::WrongArgumentCount(name.to_string(), function.arity(), args.len()));
+        }
+    }
+    let mut values = Vec::with_capacity(args.len());
+    for arg in args {
+        values.push(eval_node(arg, scope, budget, depth)?);
+    }
+    call_values(name, values, scope, budget, depth)
+}
+
+// Calls a function with the values of its arguments, the functions that don't evaluate all
+// their arguments are handled by eval_call before
+fn call_values(name: &str, values: Vec<Value>, scope: &Scope, budget: &mut Budget, depth: usize) -> Result<Value, EvalError> {
+    // A lambda in a parameter or a variable hides the functions with the same name
+    if let Some(Value::LAMBDA(lambda)) = scope.lookup(name) {
+        return call_lambda(name, &lambda, values, scope, budget, depth);
+    }
+    if let Some(function) = scope.functions.get(name) {
+        let bound = bind(name, &function.parameters, None, values)?;
+        let inner = Scope {
+            parameters: Some(&bound),
+            ..*scope
+        };
+        return eval_body(&function.body, &inner, budget, depth);
+    }
+    if let Some(function) = MatrixFunction::from_name(name) {
+        if values.len() != function.arity() {
+            return Err(EvalError::WrongArgumentCount(name.to_string(), function.arity(), values.len()));
+        }
+        return function.apply(&values);
+    }
+    if let Some(aggregate) = Aggregate::from_name(name) {
+        if values.len() != aggregate.arity() {
+            return Err(EvalError::WrongArgumentCount(name.to_string(), aggregate.arity(), values.len()));
+        }
+        let lists = values.iter().map(Value::as_list).collect::<Result<Vec<&[f64]>, EvalError>>()?;
+        return aggregate.apply(&lists).map(Value::NUMBER);
+    }
+    if functions::get_arity(name).is_none() {
+        return Err(EvalError::UnknownFunction(name.to_string()));
+    }
+    let numbers = values.iter().map(Value::as_number).collect::<Result<Vec<f64>, EvalError>>()?;
+    functions::call(name, &numbers).map(Value::NUMBER)
+}
+
+fn call_lambda(
+    name: &str,
+    lambda: &Lambda,
+    values: Vec<Value>,
+    scope: &Scope,
+    budget: &mut Budget,
+    depth: usize,
+) -> Result<Value, EvalError> {
+    let bound = bind(name, &lambda.parameters, Some(&lambda.captured), values)?;
+    let inner = Scope {
+        parameters: Some(&bound),
+        ..*scope
+    };
+    eval_body(&lambda.body, &inner, budget, depth)
+}
+
+// Evaluates the body of a user function or a lambda, the calls are counted apart from the depth
+// because every call takes more stack than a node
+fn eval_body(body: &Node, scope: &Scope, budget: &mut Budget, depth: usize) -> Result<Value, EvalError> {
+    budget.enter_call().map_err(EvalError::LimitExceeded)?;
+    let value = eval_node(body, scope, budget, depth);
+    budget.leave_call();
+    value
+}
+
+// Returns the parameters of a call to a user function or a lambda. The body sees them, the
+// parameters captured by the lambda and the globals, not the parameters of the caller. The depth
+// of the call goes on, so a recursion stops at the call or the depth limit
+fn bind(
+    name: &str,
+    parameters: &[String],
+    captured: Option<&HashMap<String, Value>>,
+    values: Vec<Value>,
+) -> Result<HashMap<String, Value>, EvalError> {
+    if parameters.len() != values.len() {
+        return Err(EvalError::WrongArgumentCount(name.to_string(), parameters.len(), values.len()));
+    }
+    let mut bound = captured.cloned().unwrap_or_default();
+    bound.extend(parameters.iter().cloned().zip(values));
+    Ok(bound)
+}
+
+// Evaluates sum, prod, fold and map, the caller already checked the number of arguments
+fn eval_higher_order(
+    function: HigherOrder,
+    args: &[Node],
+    scope: &Scope,
+    budget: &mut Budget,
+    depth: usize,
+) -> Result<Value, EvalError> {
+    match function {
+        HigherOrder::SUM | HigherOrder::PROD => {
+            // The first argument is the name of the index, it isn't evaluated
+            let index = match &args[0] {
+                Node::VARIABLE(index) => index,
+                other => return Err(EvalError::TypeMismatch("a name".to_string(), other.to_string())),
+            };
+            let range = eval_range(&args[1], &args[2], scope, budget, depth)?;
+            // The expression sees the index like a parameter, next to the parameters in scope
+            let mut bound = scope.parameters.cloned().unwrap_or_default();
+            let mut total = if function == HigherOrder::SUM { 0.0 } else { 1.0 };
+            for i in range {
+                bound.insert(index.clone(), Value::NUMBER(i));
+                let inner = Scope {
+                    parameters: Some(&bound),
+                    ..*scope
+                };
+                let value = eval_number(&args[3], &inner, budget, depth)?;
+                if function == HigherOrder::SUM {
+                    total += value;
+                } else {
+                    total *= value;
+                }
+            }
+            Ok(Value::NUMBER(total))
+        }
+        HigherOrder::FOLD => {
+            let lambda = eval_node(&args[0], scope, budget, depth)?;
+            let lambda = lambda.as_lambda()?;
+            let mut accumulator = eval_node(&args[1], scope, budget, depth)?;
+            let name = lambda.to_string();
+            for i in eval_range(&args[2], &args[3], scope, budget, depth)? {
+                let values = vec![accumulator, Value::NUMBER(i)];
+                accumulator = call_lambda(&name, lambda, values, scope, budget, depth)?;
+            }
+            Ok(accumulator)
+        }
+        HigherOrder::MAP => {
+            let lambda = eval_node(&args[0], scope, budget, depth)?;
+            let lambda = lambda.as_lambda()?;
+            let value = eval_node(&args[1], scope, budget, depth)?;
+            let name = lambda.to_string();
+            // A list is mapped element by element, the results must be numbers
+            let list = match value {
+                Value::LIST(list) => list,
+                value => return call_lambda(&name, lambda, vec![value], scope, budget, depth),
+            };
+            let mut numbers = Vec::with_capacity(list.len());
+            for element in list {
+                let result = call_lambda(&name, lambda, vec![Value::NUMBER(element)], scope, budget, depth)?;
+                numbers.push(result.as_number()?);
+            }
+            Ok(Value::LIST(numbers))
+        }
+    }
+}
+
+// Returns the integers from the first bound to the last one, both included. The count is
+// computed first, so a bound too large to add one doesn't loop forever
+fn eval_range(
+    from: &Node,
+    to: &Node,
+    scope: &Scope,
+    budget: &mut Budget,
+    depth: usize,
+) -> Result<impl Iterator<Item = f64>, EvalError> {
+    let from = eval_number(from, scope, budget, depth)?;
+    let to = eval_number(to, scope, budget, depth)?;
+    Ok(range(from, to))
+}
+
+fn range(from: f64, to: f64) -> impl Iterator<Item = f64> {
+    let count = if to >= from { (to - from).floor() as u64 + 1 } else { 0 };
+    (0..count).map(move |i| from + i as f64)
+}
+
+// Returns the value of every subtree in a single pass, in preorder like `children`. The value of
+// a node is computed from the values of its children, only sum, prod, fold and map evaluate their
+// arguments again because they take an expression or a lambda
+pub(crate) fn eval_subtrees(expr: &Node) -> Vec<Result<Value, EvalError>> {
+    let (variables, constants, functions) = (HashMap::new(), Constants::new(), HashMap::new());
+    let scope = Scope {
+        parameters: None,
+        globals: Globals::NUMBERS(&variables),
+        constants: &constants,
+        functions: &functions,
+    };
+    let mut values = Vec::new();
+    eval_subtree(expr, &scope, &mut Budget::new(Limits::unlimited()), &mut values);
+    values
+}
+
+// Pushes the value of the node and then the values of its children, the slot of the node is
+// filled when the children are done. Returns the slot of the node
+fn eval_subtree(expr: &Node, scope: &Scope, budget: &mut Budget, values: &mut Vec<Result<Value, EvalError>>) -> usize {
+    let slot = values.len();
+    values.push(Err(EvalError::SyntaxError));
+    let mut children = Vec::new();
+    for child in expr.children() {
+        let child = eval_subtree(child, scope, budget, values);
+        children.push(values[child].clone());
+    }
+    values[slot] = combine(expr, children, scope, budget);
+    slot
+}
+
+// Computes the value of a node from the values of its children, like eval_node would
+fn combine(expr: &Node, children: Vec<Result<Value, EvalError>>, scope: &Scope, budget: &mut Budget) -> Result<Value, EvalError> {
+    use self::Node::*;
+    let length = children.len();
+    let mut children = children.into_iter();
+    let mut next = || children.next().unwrap();
+    match expr {
+        NEGATIVE(_) => negate(next()?),
+        ADD(..) | SUBTRACT(..) | MULTIPLY(..) | DIVIDE(..) | CARRET(..) => {
+            let left = next()?;
+            let right = next()?;
+            apply(expr, left, right)
+        }
+        PERCENT(_) => percent(next()?),
+        CONVERT(_, unit) => units::convert(next()?, unit),
+        INDEX(..) => {
+            let list = next()?;
+            let index = next()?.as_number()?;
+            element(list, index)
+        }
+        LIST(_) => collect_list(children, length),
+        RANGE(..) => {
+            let from = next()?.as_number()?;
+            let to = next()?.as_number()?;
+            Ok(Value::LIST(range(from, to).collect()))
+        }
+        FUNCTION(name, args) if name == CONDITIONAL_NAME && args.len() == 3 => {
+            let condition = next()?.as_number()?;
+            let (then, otherwise) = (next(), next());
+            if condition != 0.0 { then } else { otherwise }
+        }
+        FUNCTION(name, _) if name != CONDITIONAL_NAME && HigherOrder::from_name(name).is_none() => {
+            let values = children.collect::<Result<Vec<Value>, EvalError>>()?;
+            call_values(name, values, scope, budget, 1)
+        }
+        // The leaves, the lambdas and the functions with a wrong number of arguments
+        _ => eval_node(expr, scope, budget, 1),
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use Node::*;
     fn get_operation(token_symbol: &str) -> Node {
-        use Node::*;
         if token_symbol == "+" {
-            return ADD(Box::new(NUMBER(5.0)), Box::new(NUMBER(5.0)));
+            ADD(Box::new(NUMBER(5.0)), Box::new(NUMBER(5.0)))
         } else if token_symbol == "-" {
-            return SUBTRACT(Box::new(NUMBER(5.0)), Box::new(NUMBER(5.0)));
+            SUBTRACT(Box::new(NUMBER(5.0)), Box::new(NUMBER(5.0)))
         } else if token_symbol == "*" {
-            return MULTIPLY(Box::new(NUMBER(5.0)), Box::new(NUMBER(5.0)));
+            MULTIPLY(Box::new(NUMBER(5.0)), Box::new(NUMBER(5.0)))
         } else if token_symbol == "/" {
-            return DIVIDE(Box::new(NUMBER(5.0)), Box::new(NUMBER(5.0)));
+            DIVIDE(Box::new(NUMBER(5.0)), Box::new(NUMBER(5.0)))
         } else if token_symbol == "^" {
-            return CARRET(Box::new(NUMBER(5.0)), Box::new(NUMBER(5.0)));
+            CARRET(Box::new(NUMBER(5.0)), Box::new(NUMBER(5.0)))
         } else {
-            return NEGATIVE(Box::new(NUMBER(5.0)))
+            NEGATIVE(Box::new(NUMBER(5.0)))
         }
     }
     #[test]
     fn test_ast_node_addition() {
         let node = get_operation("+");
-        let evaluated = eval(node).unwrap();
-        assert_eq!(evaluated,  10.0)
+        let evaluated = eval(&node).unwrap();
+        assert_eq!(evaluated, Value::NUMBER(10.0))
     }
 
     #[test]
     fn test_ast_node_subtraction() {
         let node = get_operation("-");
-        let evaluated = eval(node).unwrap();
-        assert_eq!(evaluated, 0.0)
+        let evaluated = eval(&node).unwrap();
+        assert_eq!(evaluated, Value::NUMBER(0.0))
     }
     #[test]
     fn test_ast_node_multiplitation() {
         let node = get_operation("*");
-        let evaluated = eval(node).unwrap();
-        assert_eq!(evaluated, 25.0)
+        let evaluated = eval(&node).unwrap();
+        assert_eq!(evaluated, Value::NUMBER(25.0))
     }
 
     #[test]
     fn test_ast_node_division() {
         let node = get_operation("/");
-        let evaluated = eval(node).unwrap();
-        assert_eq!(evaluated, 1.0)
+        let evaluated = eval(&node).unwrap();
+        assert_eq!(evaluated, Value::NUMBER(1.0))
     }
 
     #[test]
     fn test_ast_node_power() {
         let node = get_operation("^");
-        let evaluated = eval(node).unwrap();
-        assert_eq!(evaluated, 3125.0);
+        let evaluated = eval(&node).unwrap();
+        assert_eq!(evaluated, Value::NUMBER(3125.0));
     }
 
     #[test]
     fn test_ast_node_negative() {
         let node = get_operation("0");
-        let evaluated = eval(node).unwrap();
-        assert_eq!(evaluated, -5.0);
+        let evaluated = eval(&node).unwrap();
+        assert_eq!(evaluated, Value::NUMBER(-5.0));
+    }
+
+    #[test]
+    fn test_ast_node_function() {
+        let node = FUNCTION("max".into(), vec![NUMBER(2.0), get_operation("*")]);
+        let evaluated = eval(&node).unwrap();
+        assert_eq!(evaluated, Value::NUMBER(25.0));
+    }
+
+    #[test]
+    fn test_ast_node_variable() {
+        let mut variables = HashMap::new();
+        variables.insert("x".to_string(), 3.0);
+        let node = MULTIPLY(Box::new(VARIABLE("x".into())), Box::new(NUMBER(5.0)));
+        assert_eq!(eval_with(&node, &variables).unwrap(), Value::NUMBER(15.0));
+        let error = eval(&node).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown variable x");
+    }
+
+    #[test]
+    fn test_ast_node_constants() {
+        use std::f64::consts::{E, PI};
+        assert_eq!(eval_expr("2*pi").unwrap(), Value::NUMBER(2.0 * PI));
+        assert_eq!(eval_expr("π - pi + tau/2 - ln(e)").unwrap(), Value::NUMBER(PI - 1.0));
+        assert_eq!(eval_expr("phi^2 - phi").unwrap(), Value::NUMBER(1.0));
+        // The variables and the parameters hide the constants
+        let mut variables = HashMap::new();
+        variables.insert("e".to_string(), 2.0);
+        assert_eq!(eval_with(&VARIABLE("e".into()), &variables).unwrap(), Value::NUMBER(2.0));
+        assert_eq!(eval_expr("map(pi -> pi*2, [1])").unwrap(), Value::LIST(vec![2.0]));
+        let mut constants = Constants::new();
+        constants.register("c", 299_792_458.0).unwrap();
+        constants.register("e", 3.0).unwrap();
+        let ast = crate::parsemath::parser::Parser::new("c / 1000 + e").unwrap().parse().unwrap();
+        let value = eval_with_constants(&ast, &HashMap::new(), &constants, Limits::default());
+        assert_eq!(value, Ok(Value::NUMBER(299_795.458)));
+        // The other evaluations only know the built-in constants
+        let error = eval(&VARIABLE("c".into())).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown variable c");
+        assert_eq!(eval(&VARIABLE("e".into())).unwrap(), Value::NUMBER(E));
+    }
+
+    #[test]
+    fn test_ast_node_conditional() {
+        // The branch that isn't taken would fail
+        let node = FUNCTION(
+            "if".into(),
+            vec![NUMBER(0.0), VARIABLE("x".into()), NUMBER(2.0)],
+        );
+        assert_eq!(eval(&node).unwrap(), Value::NUMBER(2.0));
+        let node = FUNCTION("if".into(), vec![NUMBER(1.0)]);
+        assert_eq!(eval(&node).unwrap_err().to_string(), "The function if takes 3 arguments but 1 were given");
+    }
+
+    fn eval_expr(expr: &str) -> Result<Value, Box<dyn error::Error>> {
+        let ast = crate::parsemath::parser::Parser::new(expr).unwrap().parse().unwrap();
+        eval(&ast)
+    }
+
+    #[test]
+    fn test_ast_node_higher_order() {
+        assert_eq!(eval_expr("sum(i, 1, 4, i^2)").unwrap(), Value::NUMBER(30.0));
+        assert_eq!(eval_expr("prod(k, 1, 5, k)").unwrap(), Value::NUMBER(120.0));
+        // An empty range gives the neutral value
+        assert_eq!(eval_expr("sum(i, 3, 1, i)").unwrap(), Value::NUMBER(0.0));
+        assert_eq!(eval_expr("fold((acc, i) -> acc*2 + i, 0, 1, 3)").unwrap(), Value::NUMBER(11.0));
+        assert_eq!(eval_expr("map(x -> x^2 + 1, 3)").unwrap(), Value::NUMBER(10.0));
+        // The nested sum sees the index of the outer one
+        assert_eq!(eval_expr("sum(i, 1, 3, sum(j, 1, i, j))").unwrap(), Value::NUMBER(10.0));
+    }
+
+    #[test]
+    fn test_ast_node_lambda() {
+        let value = eval_expr("x -> x^2").unwrap();
+        assert_eq!(value.to_string(), "x->x^2");
+        // A lambda created inside another one keeps the parameter of the outer lambda
+        assert_eq!(eval_expr("map(y -> map(x -> x*y, 3), 2)").unwrap(), Value::NUMBER(6.0));
+        let error = eval_expr("1 + (x -> x)").unwrap_err();
+        assert_eq!(error.to_string(), "Expected a number but found a function");
+        let error = eval_expr("map(2, 3)").unwrap_err();
+        assert_eq!(error.to_string(), "Expected a function but found a number");
+        let error = eval_expr("map((a, b) -> a, 3)").unwrap_err();
+        assert_eq!(error.to_string(), "The function (a,b)->a takes 2 arguments but 1 were given");
+        let error = eval_expr("sum(2, 1, 3, 1)").unwrap_err();
+        assert_eq!(error.to_string(), "Expected a name but found 2");
+    }
+
+    #[test]
+    fn test_ast_node_list() {
+        let list = |numbers: &[f64]| Value::LIST(numbers.to_vec());
+        assert_eq!(eval_expr("[1, 2, 3] * 2 + 1").unwrap(), list(&[3.0, 5.0, 7.0]));
+        assert_eq!(eval_expr("[1, 2] ^ [3, 2] - -[1, 1]").unwrap(), list(&[2.0, 5.0]));
+        assert_eq!(eval_expr("1..4").unwrap(), list(&[1.0, 2.0, 3.0, 4.0]));
+        assert_eq!(eval_expr("3..1").unwrap(), list(&[]));
+        assert_eq!(eval_expr("[10, 20, 30][1] + (1..5)[4]").unwrap(), Value::NUMBER(25.0));
+        assert_eq!(eval_expr("map(x -> x^2, 1..3)").unwrap(), list(&[1.0, 4.0, 9.0]));
+        let error = eval_expr("[1, 2] + [1, 2, 3]").unwrap_err();
+        assert_eq!(error.to_string(), "The lists have 2 and 3 elements but must have the same length");
+        let error = eval_expr("[1, 2][2]").unwrap_err();
+        assert_eq!(error.to_string(), "There is no element 2 in a list of 2 elements");
+        assert!(eval_expr("[1, 2][0.5]").is_err());
+        // A list of lists is a matrix, so the items must be all numbers or all lists
+        let error = eval_expr("[1, [2]]").unwrap_err();
+        assert_eq!(error.to_string(), "Expected a number but found a list");
+        let error = eval_expr("[[1], 2]").unwrap_err();
+        assert_eq!(error.to_string(), "Expected a list but found a number");
+        let error = eval_expr("sqrt([4])").unwrap_err();
+        assert_eq!(error.to_string(), "Expected a number but found a list");
+    }
+
+    #[test]
+    fn test_ast_node_matrix() {
+        let matrix = |rows: &[&[f64]]| Value::MATRIX(Matrix::from_rows(rows.iter().map(|row| row.to_vec()).collect()).unwrap());
+        let m = "[[1, 2], [3, 4]]";
+        assert_eq!(eval_expr(m).unwrap(), matrix(&[&[1.0, 2.0], &[3.0, 4.0]]));
+        assert_eq!(eval_expr(&format!("{} * {}", m, m)).unwrap(), matrix(&[&[7.0, 10.0], &[15.0, 22.0]]));
+        assert_eq!(eval_expr(&format!("{}^2 - {} * 2", m, m)).unwrap(), matrix(&[&[5.0, 6.0], &[9.0, 14.0]]));
+        assert_eq!(eval_expr(&format!("{} * [1, 1]", m)).unwrap(), Value::LIST(vec![3.0, 7.0]));
+        assert_eq!(eval_expr(&format!("{}[1][0]", m)).unwrap(), Value::NUMBER(3.0));
+        assert_eq!(eval_expr(&format!("det({})", m)).unwrap(), Value::NUMBER(-2.0));
+        assert_eq!(eval_expr(&format!("transpose({})[0]", m)).unwrap(), Value::LIST(vec![1.0, 3.0]));
+        assert_eq!(eval_expr(&format!("solve({}, [5, 11])", m)).unwrap(), Value::LIST(vec![1.0, 2.0]));
+        assert_eq!(eval_expr("[1..3, 4..6]").unwrap(), matrix(&[&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]]));
+        let error = eval_expr(&format!("{} * [[1, 2, 3]]", m)).unwrap_err();
+        assert_eq!(error.to_string(), "Cannot multiply a 2x2 matrix and a 1x3 matrix");
+        let error = eval_expr(&format!("{} + [1, 2]", m)).unwrap_err();
+        assert_eq!(error.to_string(), "Cannot combine a 2x2 matrix and a 2x1 matrix");
+        let error = eval_expr("inverse([[1, 2], [2, 4]])").unwrap_err();
+        assert_eq!(error.to_string(), "The matrix is singular and has no inverse");
+        let error = eval_expr("[[1, 2], [3]]").unwrap_err();
+        assert_eq!(error.to_string(), "The lists have 2 and 1 elements but must have the same length");
+    }
+
+    #[test]
+    fn test_ast_node_units() {
+        assert_eq!(eval_expr("3 m / 2 s").unwrap().to_string(), "1.5 m/s");
+        assert_eq!(eval_expr("2 kg * 5 m/s^2 in N").unwrap().to_string(), "10 N");
+        assert_eq!(eval_expr("1 km + 500 m").unwrap().to_string(), "1.5 km");
+        assert_eq!(eval_expr("90 km/h in m/s").unwrap().to_string(), "25 m/s");
+        // The units cancel out to a number
+        assert_eq!(eval_expr("(2 m) / (50 cm)").unwrap(), Value::NUMBER(4.0));
+        let error = eval_expr("1 m + 1 s").unwrap_err();
+        assert_eq!(error.to_string(), "The dimensions m and s are incompatible");
+        let error = eval_expr("1 m in s").unwrap_err();
+        assert_eq!(error.to_string(), "The dimensions m and s are incompatible");
+        // The parser only reads known units, a built AST can hold any name
+        let error = eval(&CONVERT(Box::new(NUMBER(1.0)), "parsec".into())).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown unit parsec");
+    }
+
+    #[test]
+    fn test_ast_node_dates() {
+        assert_eq!(eval_expr("2026-10-16 + 30 days").unwrap().to_string(), "2026-11-15");
+        assert_eq!(eval_expr("(2026-10-18 - 2026-10-16) in hours").unwrap().to_string(), "48 hours");
+        assert_eq!(eval_expr("2026-10-16T09:00 - 2026-10-15T17:30").unwrap().to_string(), "930 min");
+        assert_eq!(eval_expr("2024-02-28T12:00 + 1 day + 2 h").unwrap().to_string(), "2024-02-29T14:00");
+        assert_eq!(eval_expr("2026-10-16 - 2 weeks").unwrap().to_string(), "2026-10-02");
+        let error = eval_expr("2026-10-16 + 2026-10-17").unwrap_err();
+        assert_eq!(error.to_string(), "Expected a duration but found a date");
+        let error = eval_expr("2026-10-16 * 2").unwrap_err();
+        assert_eq!(error.to_string(), "Expected a number but found a date");
+        let error = eval_expr("-2026-10-16").unwrap_err();
+        assert_eq!(error.to_string(), "Expected a number but found a date");
+    }
+
+    #[test]
+    fn test_ast_node_percent() {
+        assert_eq!(eval_expr("200 + 15%").unwrap(), Value::NUMBER(200.15));
+        assert_eq!(eval_expr("50% of 80").unwrap(), Value::NUMBER(40.0));
+        assert_eq!(eval_expr("[50, 20]%").unwrap(), Value::LIST(vec![0.5, 0.2]));
+        let business = |expr: &str| {
+            let ast = crate::parsemath::parser::Parser::with_business_mode(expr, Limits::default()).unwrap().parse().unwrap();
+            eval(&ast).unwrap()
+        };
+        assert_eq!(business("200 + 15%"), Value::NUMBER(230.0));
+        assert_eq!(business("80 - 25% + 50% of 10"), Value::NUMBER(65.0));
+    }
+
+    #[test]
+    fn test_ast_node_aggregate() {
+        assert_eq!(eval_expr("sum([1, 2, 3] * 2)").unwrap(), Value::NUMBER(12.0));
+        assert_eq!(eval_expr("mean(1..4)").unwrap(), Value::NUMBER(2.5));
+        assert_eq!(eval_expr("len([])").unwrap(), Value::NUMBER(0.0));
+        assert_eq!(eval_expr("dot([1, 2], [3, 4])").unwrap(), Value::NUMBER(11.0));
+        // The sum with four arguments is still the higher-order sum
+        assert_eq!(eval_expr("sum(i, 1, 3, i)").unwrap(), Value::NUMBER(6.0));
+        let error = eval_expr("mean(2)").unwrap_err();
+        assert_eq!(error.to_string(), "Expected a list but found a number");
+        let error = eval_expr("sum(1, 2)").unwrap_err();
+        assert_eq!(error.to_string(), "The function sum takes 1 arguments but 2 were given");
+    }
+
+    #[test]
+    fn test_ast_node_range_limit() {
+        let ast = crate::parsemath::parser::Parser::new("len(1..1000000)").unwrap().parse().unwrap();
+        let limits = Limits { max_eval_steps: 1000, ..Limits::default() };
+        let error = eval_limited(&ast, &HashMap::new(), limits).unwrap_err();
+        assert_eq!(error, EvalError::LimitExceeded(LimitError::TooManySteps(1000)));
+    }
+
+    #[test]
+    fn test_ast_node_unknown_function() {
+        let node = FUNCTION("foo".into(), vec![NUMBER(2.0)]);
+        let error = eval(&node).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown function foo");
     }
 }
\ No newline at end of file
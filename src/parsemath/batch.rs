@@ -0,0 +1,409 @@
+//! This module holds the **batch evaluator**, it evaluates an AST over a table given by columns,
+//! one value per row for each variable. Instead of evaluating the tree once per row, every
+//! operation is applied to a whole chunk of rows with simple loops over slices that the compiler
+//! can turn into vector instructions.
+//!
+//! A row where an operation gives a non finite value, like a division by zero or the square
+//! root of a negative number, gets `NaN` as result and a `RowError`, the other rows are not
+//! affected
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{
+    ast::{EvalError, Node},
+    constants,
+    functions::{Builtin, HigherOrder, CONDITIONAL_NAME},
+    value::{not_a_number, DATE_TYPE, FUNCTION_TYPE, LIST_TYPE, QUANTITY_TYPE},
+};
+
+/// The number of rows evaluated together, the buffers of a chunk stay in the cache
+pub const CHUNK_SIZE: usize = 1024;
+
+/// Row error enum contains the errors found in a single row of a batch
+/// # Arguments
+/// * `row: usize` - the index of the row in the input columns
+/// * `operation: String` - the operator or function that gave an invalid value
+/// * `column: String` - the input column that holds a value that isn't finite, like `NaN`
+#[derive(Clone, Debug, PartialEq)]
+pub enum RowError {
+    DivisionByZero(usize),
+    InvalidResult(usize, String),
+    InvalidInput(usize, String),
+}
+
+impl RowError {
+    /// Returns the index of the row where the error happened
+    pub fn row(&self) -> usize {
+        match self {
+            RowError::DivisionByZero(row) | RowError::InvalidResult(row, _) | RowError::InvalidInput(row, _) => *row,
+        }
+    }
+}
+
+// The trait for display with format! or println!
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self {
+            self::RowError::DivisionByZero(row) => write!(f, "Division by zero at row {}", row),
+            self::RowError::InvalidResult(row, operation) => {
+                write!(f, "Invalid result of {} at row {}", operation, row)
+            }
+            self::RowError::InvalidInput(row, column) => {
+                write!(f, "Invalid value in the column {} at row {}", column, row)
+            }
+        }
+    }
+}
+
+/// The BatchOutput struct holds the result of a batch evaluation
+/// # Arguments
+/// * `values: Vec<f64>` - the output column, one value per row, `NaN` for the rows with errors
+/// * `errors: Vec<RowError>` - the first error of each failed row, ordered by row
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchOutput {
+    pub values: Vec<f64>,
+    pub errors: Vec<RowError>,
+}
+
+/// Evaluates an AST once per row of a table of columns
+/// # Arguments
+/// * `node: &Node` - the root of the AST
+/// * `columns: &[(&str, &[f64])]` - the name of each variable with its column of values, all
+///   the columns must have the same number of rows
+/// # Returns
+/// * `Result<BatchOutput, EvalError>` - the output column and the row errors, or an error if
+///   the AST uses a variable without column, an unknown function or the columns have different
+///   lengths. Without columns there are no rows to evaluate
+/// # Example
+/// ```
+/// let ast = Parser::new("price/units").unwrap().parse().unwrap();
+/// let price = [10.0, 20.0, 30.0];
+/// let units = [2.0, 0.0, 3.0];
+/// let output = evaluate_columns(&ast, &[("price", &price), ("units", &units)]).unwrap();
+/// // output.values will be [5.0, NaN, 10.0]
+/// // output.errors will be [RowError::DivisionByZero(1)]
+/// ```
+pub fn evaluate_columns(node: &Node, columns: &[(&str, &[f64])]) -> Result<BatchOutput, EvalError> {
+    let rows = columns.first().map_or(0, |(_, column)| column.len());
+    let mut table = HashMap::new();
+    for (name, column) in columns {
+        if column.len() != rows {
+            return Err(EvalError::WrongColumnLength(name.to_string(), rows, column.len()));
+        }
+        table.insert(*name, *column);
+    }
+    check_node(node, &table)?;
+
+    let mut output = BatchOutput {
+        values: Vec::with_capacity(rows),
+        errors: Vec::new(),
+    };
+    let mut start = 0;
+    while start < rows {
+        let len = CHUNK_SIZE.min(rows - start);
+        let mut chunk = Chunk {
+            table: &table,
+            start,
+            len,
+            failed: vec![false; len],
+            active: vec![true; len],
+            errors: Vec::new(),
+        };
+        let mut values = chunk.eval(node);
+        // A later operation can turn the NaN of a failed row back into a number, like max(NaN, 0)
+        for (value, failed) in values.iter_mut().zip(chunk.failed.iter()) {
+            if *failed {
+                *value = f64::NAN;
+            }
+        }
+        // The errors of a chunk are found operation by operation, we sort them by row
+        chunk.errors.sort_by_key(RowError::row);
+        output.values.extend_from_slice(&values);
+        output.errors.append(&mut chunk.errors);
+        start += len;
+    }
+    Ok(output)
+}
+
+// Checks the names before evaluating, so the chunks can't fail
+fn check_node(node: &Node, table: &HashMap<&str, &[f64]>) -> Result<(), EvalError> {
+    match node {
+        Node::VARIABLE(name) if !table.contains_key(name.as_str()) && constants::builtin(name).is_none() => {
+            return Err(EvalError::UnknownVariable(name.clone()))
+        }
+        Node::ERROR => return Err(EvalError::SyntaxError),
+        // The columns only hold numbers
+        Node::LAMBDA(..) => return Err(not_a_number(FUNCTION_TYPE)),
+        Node::LIST(..) | Node::INDEX(..) | Node::RANGE(..) => return Err(not_a_number(LIST_TYPE)),
+        Node::QUANTITY(..) | Node::CONVERT(..) => return Err(not_a_number(QUANTITY_TYPE)),
+        Node::DATE(_) => return Err(not_a_number(DATE_TYPE)),
+        Node::FUNCTION(name, args) if name == CONDITIONAL_NAME && args.len() != 3 => {
+            return Err(EvalError::WrongArgumentCount(name.clone(), 3, args.len()))
+        }
+        Node::FUNCTION(name, _) if name == CONDITIONAL_NAME => (),
+        Node::FUNCTION(name, args) => match Builtin::from_name(name) {
+            None if HigherOrder::from_name(name).is_some() => return Err(EvalError::UnsupportedFunction(name.clone())),
+            None => return Err(EvalError::UnknownFunction(name.clone())),
+            Some(builtin) if builtin.arity() != args.len() => {
+                return Err(EvalError::WrongArgumentCount(name.clone(), builtin.arity(), args.len()))
+            }
+            _ => (),
+        },
+        _ => (),
+    }
+    for child in node.children() {
+        check_node(child, table)?;
+    }
+    Ok(())
+}
+
+// The rows of the table that are evaluated together
+struct Chunk<'a> {
+    table: &'a HashMap<&'a str, &'a [f64]>,
+    start: usize,
+    len: usize,
+    // The rows that already have an error, only the first error of a row is reported
+    failed: Vec<bool>,
+    // The rows whose value is used, a branch of a conditional is only used in some rows so the
+    // errors of the other rows aren't reported
+    active: Vec<bool>,
+    errors: Vec<RowError>,
+}
+
+impl<'a> Chunk<'a> {
+    fn eval(&mut self, node: &Node) -> Vec<f64> {
+        use super::ast::Node::*;
+        match node {
+            NUMBER(value) => vec![*value; self.len],
+            // A column hides the constant with the same name
+            VARIABLE(name) => match self.table.get(name.as_str()) {
+                Some(column) => {
+                    let mut values = column[self.start..self.start + self.len].to_vec();
+                    // A value that isn't finite is reported with its column, before an operation
+                    // uses it
+                    self.check(&mut values, |_, row| RowError::InvalidInput(row, name.clone()));
+                    values
+                }
+                None => vec![constants::builtin(name).unwrap(); self.len],
+            },
+            NEGATIVE(expr) => {
+                let mut values = self.eval(expr);
+                values.iter_mut().for_each(|value| *value = -*value);
+                values
+            }
+            PERCENT(expr) => {
+                let mut values = self.eval(expr);
+                values.iter_mut().for_each(|value| *value /= 100.0);
+                values
+            }
+            ADD(left, right) => self.binary(left, right, "+", |a, b| a + b),
+            SUBTRACT(left, right) => self.binary(left, right, "-", |a, b| a - b),
+            MULTIPLY(left, right) => self.binary(left, right, "*", |a, b| a * b),
+            DIVIDE(left, right) => self.binary(left, right, "/", |a, b| a / b),
+            CARRET(left, right) => self.binary(left, right, "^", f64::powf),
+            // The placeholders, the lambdas, the lists, the quantities and the dates are rejected
+            // by the check
+            ERROR | LAMBDA(..) | LIST(..) | INDEX(..) | RANGE(..) | QUANTITY(..) | CONVERT(..) | DATE(_) => {
+                unreachable!()
+            }
+            FUNCTION(name, args) if name == CONDITIONAL_NAME => self.conditional(&args[0], &args[1], &args[2]),
+            FUNCTION(name, args) => {
+                let builtin = Builtin::from_name(name).unwrap();
+                let mut values = self.eval(&args[0]);
+                if builtin.arity() == 1 {
+                    values.iter_mut().for_each(|value| *value = builtin.apply(&[*value]));
+                } else {
+                    let second = self.eval(&args[1]);
+                    for (value, other) in values.iter_mut().zip(second.iter()) {
+                        *value = builtin.apply(&[*value, *other]);
+                    }
+                }
+                self.check(&mut values, |_, row| RowError::InvalidResult(row, name.clone()));
+                values
+            }
+        }
+    }
+
+    // Every branch is computed for the whole chunk, but only the rows that take it are active
+    fn conditional(&mut self, condition: &Node, then: &Node, otherwise: &Node) -> Vec<f64> {
+        let condition = self.eval(condition);
+        let active = self.active.clone();
+        self.active = active.iter().zip(condition.iter()).map(|(active, value)| *active && *value != 0.0).collect();
+        let mut values = self.eval(then);
+        self.active = active.iter().zip(condition.iter()).map(|(active, value)| *active && *value == 0.0).collect();
+        let other = self.eval(otherwise);
+        self.active = active;
+        for ((value, other), condition) in values.iter_mut().zip(other.iter()).zip(condition.iter()) {
+            if *condition == 0.0 {
+                *value = *other;
+            }
+        }
+        values
+    }
+
+    fn binary(&mut self, left: &Node, right: &Node, symbol: &str, operation: impl Fn(f64, f64) -> f64) -> Vec<f64> {
+        let mut values = self.eval(left);
+        let right = self.eval(right);
+        // A plain loop over two slices, the compiler can vectorize it
+        for (value, other) in values.iter_mut().zip(right.iter()) {
+            *value = operation(*value, *other);
+        }
+        self.check(&mut values, |i, row| {
+            if symbol == "/" && right[i] == 0.0 {
+                RowError::DivisionByZero(row)
+            } else {
+                RowError::InvalidResult(row, symbol.to_string())
+            }
+        });
+        values
+    }
+
+    // Reports the rows that hold a non finite value, their value becomes NaN. The error is built
+    // from the index in the chunk and the row
+    fn check(&mut self, values: &mut [f64], error: impl Fn(usize, usize) -> RowError) {
+        if values.iter().all(|value| value.is_finite()) {
+            return;
+        }
+        for (i, value) in values.iter_mut().enumerate() {
+            if value.is_finite() || self.failed[i] || !self.active[i] {
+                continue;
+            }
+            *value = f64::NAN;
+            self.failed[i] = true;
+            self.errors.push(error(i, self.start + i));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsemath::{ast, parser::Parser};
+
+    fn parse(expr: &str) -> Node {
+        let mut parser = Parser::new(expr).unwrap();
+        parser.parse().unwrap()
+    }
+
+    #[test]
+    fn test_batch_matches_eval() {
+        let ast = parse("p*r/(1-(1+r)^-n)+max(p,r)");
+        let rows = 3000;
+        let p: Vec<f64> = (0..rows).map(|i| 1000.0 + i as f64).collect();
+        let r: Vec<f64> = (0..rows).map(|i| 0.01 + (i % 7) as f64 / 100.0).collect();
+        let n: Vec<f64> = (0..rows).map(|i| 1.0 + (i % 24) as f64).collect();
+        let output = evaluate_columns(&ast, &[("p", &p), ("r", &r), ("n", &n)]).unwrap();
+        assert!(output.errors.is_empty());
+        assert_eq!(output.values.len(), rows);
+        for row in 0..rows {
+            let mut variables = HashMap::new();
+            variables.insert("p".to_string(), p[row]);
+            variables.insert("r".to_string(), r[row]);
+            variables.insert("n".to_string(), n[row]);
+            let expected = ast::eval_with(&ast, &variables).unwrap().as_number().unwrap();
+            assert_eq!(output.values[row], expected, "row {}", row);
+        }
+    }
+
+    #[test]
+    fn test_batch_row_errors() {
+        let ast = parse("sqrt(x)/y+1");
+        let mut x = vec![4.0; 2000];
+        let mut y = vec![2.0; 2000];
+        y[1234] = 0.0;
+        x[1500] = -1.0;
+        // The square root fails first, the division by zero of the same row isn't reported
+        x[7] = -1.0;
+        y[7] = 0.0;
+        let output = evaluate_columns(&ast, &[("x", &x), ("y", &y)]).unwrap();
+        assert_eq!(
+            output.errors,
+            vec![
+                RowError::InvalidResult(7, "sqrt".into()),
+                RowError::DivisionByZero(1234),
+                RowError::InvalidResult(1500, "sqrt".into()),
+            ]
+        );
+        assert_eq!(output.errors[1].to_string(), "Division by zero at row 1234");
+        assert!(output.values[1500].is_nan());
+        assert_eq!(output.values[0], 2.0);
+        assert_eq!(output.values[1999], 2.0);
+    }
+
+    #[test]
+    fn test_batch_invalid_input() {
+        // The NaN of x is reported with its column, not as the result of the square root
+        let ast = parse("sqrt(x)+y");
+        let x = [4.0, f64::NAN, -1.0, 9.0];
+        let y = [1.0, 1.0, f64::INFINITY, 1.0];
+        let output = evaluate_columns(&ast, &[("x", &x), ("y", &y)]).unwrap();
+        assert_eq!(
+            output.errors,
+            vec![
+                RowError::InvalidInput(1, "x".into()),
+                RowError::InvalidResult(2, "sqrt".into()),
+            ]
+        );
+        assert_eq!(output.errors[0].to_string(), "Invalid value in the column x at row 1");
+        assert!(output.values[1].is_nan() && output.values[2].is_nan());
+        assert_eq!(output.values[3], 4.0);
+        // The NaN of x isn't reported in a row that doesn't take the branch that uses it
+        let output = evaluate_columns(&parse("if(y-1, x, 0)"), &[("x", &x), ("y", &y)]).unwrap();
+        assert_eq!(output.errors, vec![RowError::InvalidInput(2, "y".into())]);
+        assert_eq!(output.values[1], 0.0);
+    }
+
+    #[test]
+    fn test_batch_conditional() {
+        // The rows that don't take a branch don't report its errors
+        let ast = parse("if(y, sqrt(x)/y, 0)");
+        let x = [4.0, -1.0, -1.0, 9.0];
+        let y = [2.0, 0.0, 1.0, 0.0];
+        let output = evaluate_columns(&ast, &[("x", &x), ("y", &y)]).unwrap();
+        assert_eq!(output.errors, vec![RowError::InvalidResult(2, "sqrt".into())]);
+        assert_eq!(output.values[..2], [1.0, 0.0]);
+        assert!(output.values[2].is_nan());
+        assert_eq!(output.values[3], 0.0);
+    }
+
+    #[test]
+    fn test_batch_failed_rows_are_nan() {
+        let price = [10.0, 20.0, 30.0];
+        let units = [2.0, 0.0, 3.0];
+        let output = evaluate_columns(&parse("price/units"), &[("price", &price), ("units", &units)]).unwrap();
+        assert_eq!(output.errors, vec![RowError::DivisionByZero(1)]);
+        assert_eq!(output.values[0], 5.0);
+        assert!(output.values[1].is_nan());
+        assert_eq!(output.values[2], 10.0);
+
+        // The failed row stays NaN even if max gives back a number
+        let x = [4.0, -1.0];
+        let output = evaluate_columns(&parse("max(sqrt(x),0)"), &[("x", &x)]).unwrap();
+        assert_eq!(output.errors, vec![RowError::InvalidResult(1, "sqrt".into())]);
+        assert_eq!(output.values[0], 2.0);
+        assert!(output.values[1].is_nan());
+    }
+
+    #[test]
+    fn test_batch_errors() {
+        let x = [1.0, 2.0];
+        let y = [1.0];
+        assert_eq!(
+            evaluate_columns(&parse("x+y"), &[("x", &x), ("y", &y)]),
+            Err(EvalError::WrongColumnLength("y".into(), 2, 1))
+        );
+        assert_eq!(
+            evaluate_columns(&parse("x+z"), &[("x", &x)]),
+            Err(EvalError::UnknownVariable("z".into()))
+        );
+        assert_eq!(
+            evaluate_columns(&parse("foo(x)"), &[("x", &x)]),
+            Err(EvalError::UnknownFunction("foo".into()))
+        );
+        assert_eq!(
+            evaluate_columns(&parse("sum(i, 1, x, i)"), &[("x", &x)]),
+            Err(EvalError::UnsupportedFunction("sum".into()))
+        );
+    }
+}
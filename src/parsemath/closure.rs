@@ -0,0 +1,240 @@
+//! This module holds the **closure compiler**, an alternative to the bytecode virtual machine
+//! that turns every node of the AST into a closure that calls the closures of its children.
+//! The names are resolved once when compiling, so the result is a plain function of the values
+//! of the variables
+
+use super::{
+    ast::{EvalError, Node},
+    constants,
+    functions::{Builtin, HigherOrder, CONDITIONAL_NAME},
+    value::{not_a_number, DATE_TYPE, FUNCTION_TYPE, LIST_TYPE, QUANTITY_TYPE},
+};
+
+// A compiled node, takes the values of the variables and returns the value of the node
+type Compiled = Box<dyn Fn(&[f64]) -> f64 + Send + Sync>;
+
+/// Compiles an AST to a function of the values of its variables
+/// # Arguments
+/// * `node: &Node` - the root of the AST
+/// * `variables: &[&str]` - the names of the variables, the position of a name is the index of
+///   its value in the slice given to the function
+/// # Returns
+/// * `Result<impl Fn(&[f64]) -> Result<f64, EvalError>, EvalError>` - the function or an error
+///   if the AST uses a variable that isn't in the list, an unknown function or a function with
+///   the wrong number of arguments. The function returns `EvalError::WrongVariableCount` if the
+///   number of values isn't the number of variables
+/// # Example
+/// ```
+/// let ast = Parser::new("x*2+y").unwrap().parse().unwrap();
+/// let function = compile(&ast, &["x", "y"]).unwrap();
+/// let value = function(&[3.0, 1.0]);
+/// // This will be Ok(7.0)
+/// ```
+pub fn compile(
+    node: &Node,
+    variables: &[&str],
+) -> Result<impl Fn(&[f64]) -> Result<f64, EvalError> + Send + Sync, EvalError> {
+    let compiled = compile_node(node, variables)?;
+    let count = variables.len();
+    // The compiled nodes index the values without checks, so we check the length once here
+    Ok(move |values: &[f64]| {
+        if values.len() != count {
+            return Err(EvalError::WrongVariableCount(count, values.len()));
+        }
+        Ok(compiled(values))
+    })
+}
+
+fn compile_node(node: &Node, variables: &[&str]) -> Result<Compiled, EvalError> {
+    use super::ast::Node::*;
+    let compiled: Compiled = match node {
+        NUMBER(value) => {
+            let value = *value;
+            Box::new(move |_| value)
+        }
+        VARIABLE(name) => match variables.iter().position(|variable| variable == name) {
+            Some(index) => Box::new(move |values| values[index]),
+            None => match constants::builtin(name) {
+                Some(value) => Box::new(move |_| value),
+                None => return Err(EvalError::UnknownVariable(name.clone())),
+            },
+        },
+        ERROR => return Err(EvalError::SyntaxError),
+        // The compiled function returns a number, it can't return a lambda or a list
+        LAMBDA(..) => return Err(not_a_number(FUNCTION_TYPE)),
+        LIST(..) | INDEX(..) | RANGE(..) => return Err(not_a_number(LIST_TYPE)),
+        QUANTITY(..) | CONVERT(..) => return Err(not_a_number(QUANTITY_TYPE)),
+        DATE(_) => return Err(not_a_number(DATE_TYPE)),
+        NEGATIVE(expr) => {
+            let expr = compile_node(expr, variables)?;
+            Box::new(move |values| -expr(values))
+        }
+        PERCENT(expr) => {
+            let expr = compile_node(expr, variables)?;
+            Box::new(move |values| expr(values) / 100.0)
+        }
+        ADD(left, right) => {
+            let (left, right) = (compile_node(left, variables)?, compile_node(right, variables)?);
+            Box::new(move |values| left(values) + right(values))
+        }
+        SUBTRACT(left, right) => {
+            let (left, right) = (compile_node(left, variables)?, compile_node(right, variables)?);
+            Box::new(move |values| left(values) - right(values))
+        }
+        MULTIPLY(left, right) => {
+            let (left, right) = (compile_node(left, variables)?, compile_node(right, variables)?);
+            Box::new(move |values| left(values) * right(values))
+        }
+        DIVIDE(left, right) => {
+            let (left, right) = (compile_node(left, variables)?, compile_node(right, variables)?);
+            Box::new(move |values| left(values) / right(values))
+        }
+        CARRET(left, right) => {
+            let (left, right) = (compile_node(left, variables)?, compile_node(right, variables)?);
+            Box::new(move |values| left(values).powf(right(values)))
+        }
+        // Only the branch chosen by the condition is evaluated, like in ast::eval
+        FUNCTION(name, args) if name == CONDITIONAL_NAME => {
+            if args.len() != 3 {
+                return Err(EvalError::WrongArgumentCount(name.clone(), 3, args.len()));
+            }
+            let condition = compile_node(&args[0], variables)?;
+            let (then, otherwise) = (compile_node(&args[1], variables)?, compile_node(&args[2], variables)?);
+            Box::new(move |values| if condition(values) != 0.0 { then(values) } else { otherwise(values) })
+        }
+        FUNCTION(name, args) => {
+            let builtin = match Builtin::from_name(name) {
+                Some(builtin) => builtin,
+                None if HigherOrder::from_name(name).is_some() => {
+                    return Err(EvalError::UnsupportedFunction(name.clone()))
+                }
+                None => return Err(EvalError::UnknownFunction(name.clone())),
+            };
+            if builtin.arity() != args.len() {
+                return Err(EvalError::WrongArgumentCount(name.clone(), builtin.arity(), args.len()));
+            }
+            // The built-in functions take one or two arguments, so we avoid collecting them
+            let mut args = args
+                .iter()
+                .map(|arg| compile_node(arg, variables))
+                .collect::<Result<Vec<Compiled>, EvalError>>()?
+                .into_iter();
+            let first = args.next().unwrap();
+            match args.next() {
+                Some(second) => Box::new(move |values| builtin.apply(&[first(values), second(values)])),
+                None => Box::new(move |values| builtin.apply(&[first(values)])),
+            }
+        }
+    };
+    Ok(compiled)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsemath::{ast, functions::BUILTIN_NAMES, parser::Parser};
+    use std::collections::HashMap;
+
+    const NAMES: [&str; 3] = ["x", "y", "z"];
+
+    // A small xorshift generator, so the random trees are the same in every run
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, limit: u64) -> usize {
+            (self.next() % limit) as usize
+        }
+    }
+
+    fn random_tree(random: &mut Random, depth: usize) -> Node {
+        use crate::parsemath::ast::Node::*;
+        let choice = if depth == 0 { random.below(2) } else { random.below(9) };
+        let child = |random: &mut Random| Box::new(random_tree(random, depth.saturating_sub(1)));
+        match choice {
+            0 => NUMBER(random.below(1000) as f64 / 100.0),
+            1 => VARIABLE(NAMES[random.below(3)].to_string()),
+            2 => ADD(child(random), child(random)),
+            3 => SUBTRACT(child(random), child(random)),
+            4 => MULTIPLY(child(random), child(random)),
+            5 => DIVIDE(child(random), child(random)),
+            6 => CARRET(child(random), child(random)),
+            7 => NEGATIVE(child(random)),
+            _ => {
+                let name = BUILTIN_NAMES[random.below(BUILTIN_NAMES.len() as u64)];
+                let arity = Builtin::from_name(name).unwrap().arity();
+                let args = (0..arity).map(|_| *child(random)).collect();
+                FUNCTION(name.to_string(), args)
+            }
+        }
+    }
+
+    fn same_value(left: f64, right: f64) -> bool {
+        left == right || (left.is_nan() && right.is_nan())
+    }
+
+    #[test]
+    fn test_closure_compile() {
+        let ast = Parser::new("x*2+-max(y,1)").unwrap().parse().unwrap();
+        let function = compile(&ast, &["x", "y"]).unwrap();
+        assert_eq!(function(&[3.0, 4.0]), Ok(2.0));
+        assert_eq!(function(&[3.0, -4.0]), Ok(5.0));
+        // The built-in constants are compiled as numbers
+        let ast = Parser::new("2*pi*r").unwrap().parse().unwrap();
+        assert_eq!(compile(&ast, &["r"]).unwrap()(&[0.5]), Ok(std::f64::consts::PI));
+        let ast = Parser::new("if(x, y, 1/0)").unwrap().parse().unwrap();
+        let function = compile(&ast, &["x", "y"]).unwrap();
+        assert_eq!(function(&[1.0, 4.0]), Ok(4.0));
+        assert_eq!(function(&[0.0, 4.0]), Ok(f64::INFINITY));
+    }
+
+    #[test]
+    fn test_closure_wrong_variable_count() {
+        let ast = Parser::new("x+y").unwrap().parse().unwrap();
+        let function = compile(&ast, &["x", "y"]).unwrap();
+        assert_eq!(function(&[1.0]), Err(EvalError::WrongVariableCount(2, 1)));
+        assert_eq!(function(&[1.0, 2.0, 3.0]), Err(EvalError::WrongVariableCount(2, 3)));
+        assert_eq!(function(&[1.0, 2.0]), Ok(3.0));
+    }
+
+    #[test]
+    fn test_closure_compile_errors() {
+        let ast = Parser::new("x+z").unwrap().parse().unwrap();
+        assert_eq!(compile(&ast, &["x"]).err(), Some(EvalError::UnknownVariable("z".into())));
+        let ast = Parser::new("sqrt(1,2)").unwrap().parse().unwrap();
+        assert_eq!(
+            compile(&ast, &[]).err(),
+            Some(EvalError::WrongArgumentCount("sqrt".into(), 1, 2))
+        );
+        let ast = Parser::new("sum(i, 1, 3, i)").unwrap().parse().unwrap();
+        assert_eq!(compile(&ast, &[]).err(), Some(EvalError::UnsupportedFunction("sum".into())));
+    }
+
+    #[test]
+    fn test_closure_matches_eval_on_random_trees() {
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        for _ in 0..500 {
+            let tree = random_tree(&mut random, 6);
+            let values = [
+                random.below(2000) as f64 / 100.0 - 10.0,
+                random.below(2000) as f64 / 100.0 - 10.0,
+                random.below(2000) as f64 / 100.0 - 10.0,
+            ];
+            let variables: HashMap<String, f64> = NAMES
+                .iter()
+                .map(|name| name.to_string())
+                .zip(values.iter().copied())
+                .collect();
+            let function = compile(&tree, &NAMES).unwrap();
+            let expected = ast::eval_with(&tree, &variables).unwrap().as_number().unwrap();
+            let value = function(&values).unwrap();
+            assert!(same_value(value, expected), "{} gave {} instead of {}", tree, value, expected);
+        }
+    }
+}
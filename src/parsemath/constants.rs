@@ -0,0 +1,116 @@
+//! This module holds the **constants**, the names like `pi` or `e` that always have the same
+//! value. A constant stays a name in the AST, so the printer writes `2*pi*r` and not a long
+//! decimal, and the evaluation reads its value from a table. The built-in constants are `pi`,
+//! also written `π`, `e`, `tau` and `phi`, a library can register more in a `Constants` table.
+//!
+//! The constants are read-only, a program can't assign them. A variable given by the caller of
+//! the evaluation or a parameter of a function with the same name hides the constant
+
+use super::ast::EvalError;
+use super::functions;
+use super::tokenizer::CONVERSION_KEYWORD;
+
+/// The built-in constants with their values, the golden ratio is `phi`
+pub const BUILTIN_CONSTANTS: [(&str, f64); 5] = [
+    ("pi", std::f64::consts::PI),
+    ("π", std::f64::consts::PI),
+    ("e", std::f64::consts::E),
+    ("tau", std::f64::consts::TAU),
+    ("phi", 1.618_033_988_749_895),
+];
+
+/// Returns the value of a built-in constant
+pub fn builtin(name: &str) -> Option<f64> {
+    BUILTIN_CONSTANTS.iter().find(|(constant, _)| *constant == name).map(|(_, value)| *value)
+}
+
+/// The Constants struct holds the built-in constants and the ones registered by the library
+/// user, a registered constant with the name of a built-in one replaces it
+/// # Example
+/// ```
+/// let mut constants = Constants::new();
+/// constants.register("c", 299_792_458.0).unwrap();
+/// let ast = Parser::new("c * 2").unwrap().parse().unwrap();
+/// let value = eval_with_constants(&ast, &HashMap::new(), &constants, Limits::default());
+/// // This will be Ok(Value::NUMBER(599584916.0))
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Constants {
+    registered: Vec<(String, f64)>,
+}
+
+impl Constants {
+    /// Returns a table with only the built-in constants
+    pub fn new() -> Self {
+        Constants {
+            registered: Vec::new(),
+        }
+    }
+
+    /// Adds a constant to the table or replaces the value of a constant with the same name
+    /// # Arguments
+    /// * `name: &str` - the name, a letter followed by letters, digits or underscores
+    /// * `value: f64` - the value of the constant
+    /// # Returns
+    /// * `Result<(), EvalError>` - `EvalError::InvalidConstant` if the tokenizer doesn't read the
+    ///   name as a name, or if it's a function or a keyword
+    /// # Example
+    /// ```
+    /// let mut constants = Constants::new();
+    /// let error = constants.register("sqrt", 2.0);
+    /// // This will be Err(EvalError::InvalidConstant("sqrt")), sqrt is a function
+    /// ```
+    pub fn register(&mut self, name: &str, value: f64) -> Result<(), EvalError> {
+        let mut chars = name.chars();
+        let is_name = chars.next().is_some_and(char::is_alphabetic)
+            && chars.all(|c| c.is_alphanumeric() || c == '_');
+        if !is_name || functions::is_reserved(name) || name == CONVERSION_KEYWORD {
+            return Err(EvalError::InvalidConstant(name.to_string()));
+        }
+        match self.registered.iter_mut().find(|(constant, _)| constant == name) {
+            Some(constant) => constant.1 = value,
+            None => self.registered.push((name.to_string(), value)),
+        }
+        Ok(())
+    }
+
+    /// Returns the value of a constant, a registered one or a built-in one
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.registered
+            .iter()
+            .find(|(constant, _)| constant == name)
+            .map(|(_, value)| *value)
+            .or_else(|| builtin(name))
+    }
+
+    /// Returns the names and the values of all the constants of the table
+    pub fn iter(&self) -> impl Iterator<Item = (&str, f64)> {
+        // A built-in constant replaced by a registered one is listed once, with the new value
+        let builtin = BUILTIN_CONSTANTS
+            .iter()
+            .filter(move |(name, _)| !self.registered.iter().any(|(constant, _)| constant == name))
+            .map(|(name, value)| (*name, *value));
+        builtin.chain(self.registered.iter().map(|(name, value)| (name.as_str(), *value)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_constants_register() {
+        let mut constants = Constants::new();
+        assert_eq!(constants.get("π"), Some(std::f64::consts::PI));
+        assert_eq!(constants.get("c"), None);
+        constants.register("c", 299_792_458.0).unwrap();
+        constants.register("pi", 3.0).unwrap();
+        assert_eq!(constants.get("c"), Some(299_792_458.0));
+        assert_eq!(constants.get("pi"), Some(3.0));
+        assert_eq!(constants.iter().filter(|(name, _)| *name == "pi").count(), 1);
+        assert_eq!(constants.iter().count(), BUILTIN_CONSTANTS.len() + 1);
+        for name in ["sqrt", "in", "2x", "a-b", ""] {
+            assert_eq!(constants.register(name, 1.0), Err(EvalError::InvalidConstant(name.to_string())));
+        }
+    }
+}
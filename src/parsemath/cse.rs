@@ -0,0 +1,317 @@
+//! This module holds the **common subexpression elimination** for a set of formulas. The trees
+//! are added to an arena with hash-consing, so a subexpression like `(1+r)^n` that appears in
+//! many formulas becomes a single node of a shared DAG. The DAG is evaluated once per set of
+//! inputs, every node is computed a single time and all the formulas read the shared values
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{
+    arena::{Arena, ArenaNode, NodeId},
+    ast::{EvalError, Node},
+    constants,
+    functions::{self, HigherOrder, CONDITIONAL_NAME},
+    value::{not_a_number, DATE_TYPE, FUNCTION_TYPE, LIST_TYPE, QUANTITY_TYPE},
+};
+
+/// The SharedSubexpression struct describes a subexpression used more than once, for auditing
+/// # Arguments
+/// * `id: NodeId` - the node of the subexpression in the DAG
+/// * `expression: String` - the subexpression printed as infix
+/// * `uses: usize` - the number of places that use the subexpression, counting the formulas
+///   that are just the subexpression
+/// * `formulas: Vec<usize>` - the indexes of the formulas that contain the subexpression
+#[derive(Clone, Debug, PartialEq)]
+pub struct SharedSubexpression {
+    pub id: NodeId,
+    pub expression: String,
+    pub uses: usize,
+    pub formulas: Vec<usize>,
+}
+
+// The trait for display with format! or println!
+impl fmt::Display for SharedSubexpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let formulas: Vec<String> = self.formulas.iter().map(|index| index.to_string()).collect();
+        write!(
+            f,
+            "{} is used {} times in the formulas {}",
+            self.expression,
+            self.uses,
+            formulas.join(", ")
+        )
+    }
+}
+
+/// The FormulaSet struct holds a set of formulas as a shared DAG
+/// # Example
+/// ```
+/// let formulas: Vec<Node> = ["p*(1+r)^n", "(1+r)^n-1"]
+///     .iter()
+///     .map(|expr| Parser::new(expr).unwrap().parse().unwrap())
+///     .collect();
+/// let set = FormulaSet::new(&formulas);
+/// // set.shared() will report (1+r)^n used 2 times in the formulas 0, 1
+/// ```
+#[derive(Clone, Debug)]
+pub struct FormulaSet {
+    arena: Arena,
+    roots: Vec<NodeId>,
+}
+
+impl FormulaSet {
+    /// Builds the shared DAG of the formulas
+    /// # Arguments
+    /// * `formulas: &[Node]` - the parsed formulas, the order is kept in the outputs
+    /// # Returns
+    /// * `FormulaSet` - the formulas with their identical subtrees shared
+    pub fn new(formulas: &[Node]) -> Self {
+        let mut arena = Arena::with_hash_consing();
+        let roots = formulas.iter().map(|formula| arena.from_node(formula)).collect();
+        FormulaSet { arena, roots }
+    }
+
+    /// Returns the DAG, the children of a node are always before the node
+    pub fn arena(&self) -> &Arena {
+        &self.arena
+    }
+
+    /// Returns the root node of every formula
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// Evaluates all the formulas, the shared subexpressions are computed once
+    /// # Arguments
+    /// * `variables: &HashMap<String, f64>` - the values of the variables by name
+    /// # Returns
+    /// * `Result<Vec<f64>, EvalError>` - the value of each formula in the given order, or an error
+    ///   if a variable has no value, a function is unknown or binds variables like `sum`
+    /// # Example
+    /// ```
+    /// let mut variables = HashMap::new();
+    /// variables.insert("p".to_string(), 100.0);
+    /// variables.insert("r".to_string(), 1.0);
+    /// variables.insert("n".to_string(), 2.0);
+    /// let values = set.evaluate(&variables);
+    /// // This will be Ok(vec![400.0, 3.0])
+    /// ```
+    pub fn evaluate(&self, variables: &HashMap<String, f64>) -> Result<Vec<f64>, EvalError> {
+        // The values are computed on demand from the roots, so a shared node is computed once
+        // and the branch of a conditional that isn't taken isn't computed
+        let mut values: Vec<Option<f64>> = vec![None; self.arena.len()];
+        for root in self.roots.iter() {
+            let mut pending = vec![*root];
+            while let Some(&id) = pending.last() {
+                if values[id.index()].is_some() {
+                    pending.pop();
+                    continue;
+                }
+                let node = self.arena.get(id);
+                let needed = match node {
+                    ArenaNode::FUNCTION(name, _) if HigherOrder::from_name(name).is_some() => {
+                        return Err(EvalError::UnsupportedFunction(name.clone()))
+                    }
+                    ArenaNode::FUNCTION(name, args) if name == CONDITIONAL_NAME && args.len() == 3 => {
+                        match values[args[0].index()] {
+                            None => vec![args[0]],
+                            Some(condition) => vec![if condition != 0.0 { args[1] } else { args[2] }],
+                        }
+                    }
+                    node => node.children(),
+                };
+                let missing: Vec<NodeId> = needed.into_iter().filter(|child| values[child.index()].is_none()).collect();
+                if !missing.is_empty() {
+                    // The children are computed from left to right, like the recursive evaluator
+                    pending.extend(missing.into_iter().rev());
+                    continue;
+                }
+                values[id.index()] = Some(compute(node, &values, variables)?);
+                pending.pop();
+            }
+        }
+        Ok(self.roots.iter().map(|root| values[root.index()].unwrap()).collect())
+    }
+
+    /// Returns the subexpressions used more than once, the numbers and variables aren't reported
+    /// # Returns
+    /// * `Vec<SharedSubexpression>` - the shared subexpressions from the innermost to the outermost
+    pub fn shared(&self) -> Vec<SharedSubexpression> {
+        // We mark the nodes that belong to each formula, without looking inside the bodies
+        let mut formulas = vec![Vec::new(); self.arena.len()];
+        for (formula, root) in self.roots.iter().enumerate() {
+            let mut pending = vec![*root];
+            while let Some(id) = pending.pop() {
+                if formulas[id.index()].last() == Some(&formula) {
+                    continue;
+                }
+                formulas[id.index()].push(formula);
+                if !binds_variables(self.arena.get(id)) {
+                    pending.extend(self.arena.get(id).children());
+                }
+            }
+        }
+        // And we count the references from these nodes and from the formulas
+        let mut uses = vec![0; self.arena.len()];
+        for id in self.arena.ids() {
+            if formulas[id.index()].is_empty() || binds_variables(self.arena.get(id)) {
+                continue;
+            }
+            for child in self.arena.get(id).children() {
+                uses[child.index()] += 1;
+            }
+        }
+        for root in self.roots.iter() {
+            uses[root.index()] += 1;
+        }
+
+        let mut shared = Vec::new();
+        for (id, formulas) in self.arena.ids().zip(formulas) {
+            let index = id.index();
+            let leaf = matches!(
+                self.arena.get(id),
+                ArenaNode::NUMBER(_) | ArenaNode::VARIABLE(_) | ArenaNode::ERROR
+            );
+            if uses[index] > 1 && !leaf {
+                shared.push(SharedSubexpression {
+                    id,
+                    expression: self.arena.to_node(id).to_string(),
+                    uses: uses[index],
+                    formulas,
+                });
+            }
+        }
+        shared
+    }
+}
+
+// The bodies of sum, prod, fold, map and the lambdas bind their own variables, like the i of
+// sum(i, 1, 3, i^2). The DAG shares a bound i with a free one, so these bodies are neither
+// evaluated nor reported as shared
+fn binds_variables(node: &ArenaNode) -> bool {
+    match node {
+        ArenaNode::LAMBDA(..) => true,
+        ArenaNode::FUNCTION(name, _) => HigherOrder::from_name(name).is_some(),
+        _ => false,
+    }
+}
+
+// Computes a node of the DAG, the values of the children that it needs are already computed
+fn compute(node: &ArenaNode, values: &[Option<f64>], variables: &HashMap<String, f64>) -> Result<f64, EvalError> {
+    let value_of = |id: &NodeId| values[id.index()].unwrap();
+    let value = match node {
+        ArenaNode::NUMBER(value) => *value,
+        ArenaNode::VARIABLE(name) => match variables.get(name).copied().or_else(|| constants::builtin(name)) {
+            Some(value) => value,
+            None => return Err(EvalError::UnknownVariable(name.clone())),
+        },
+        ArenaNode::NEGATIVE(expr) => -value_of(expr),
+        ArenaNode::PERCENT(expr) => value_of(expr) / 100.0,
+        ArenaNode::ADD(left, right) => value_of(left) + value_of(right),
+        ArenaNode::SUBTRACT(left, right) => value_of(left) - value_of(right),
+        ArenaNode::MULTIPLY(left, right) => value_of(left) * value_of(right),
+        ArenaNode::DIVIDE(left, right) => value_of(left) / value_of(right),
+        ArenaNode::CARRET(left, right) => value_of(left).powf(value_of(right)),
+        ArenaNode::FUNCTION(name, args) if name == CONDITIONAL_NAME => {
+            if args.len() != 3 {
+                return Err(EvalError::WrongArgumentCount(name.clone(), 3, args.len()));
+            }
+            if value_of(&args[0]) != 0.0 {
+                value_of(&args[1])
+            } else {
+                value_of(&args[2])
+            }
+        }
+        ArenaNode::FUNCTION(name, args) => {
+            let args: Vec<f64> = args.iter().map(value_of).collect();
+            functions::call(name, &args)?
+        }
+        ArenaNode::ERROR => return Err(EvalError::SyntaxError),
+        ArenaNode::LAMBDA(..) => return Err(not_a_number(FUNCTION_TYPE)),
+        ArenaNode::LIST(..) | ArenaNode::INDEX(..) | ArenaNode::RANGE(..) => return Err(not_a_number(LIST_TYPE)),
+        ArenaNode::QUANTITY(..) | ArenaNode::CONVERT(..) => return Err(not_a_number(QUANTITY_TYPE)),
+        ArenaNode::DATE(_) => return Err(not_a_number(DATE_TYPE)),
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsemath::{ast, parser::Parser};
+
+    fn parse_all(exprs: &[&str]) -> Vec<Node> {
+        exprs
+            .iter()
+            .map(|expr| Parser::new(expr).unwrap().parse().unwrap())
+            .collect()
+    }
+
+    fn variables() -> HashMap<String, f64> {
+        let mut variables = HashMap::new();
+        variables.insert("p".to_string(), 1000.0);
+        variables.insert("r".to_string(), 0.05);
+        variables.insert("n".to_string(), 12.0);
+        variables
+    }
+
+    #[test]
+    fn test_cse_evaluate_matches_eval() {
+        let formulas = parse_all(&[
+            "p*(1+r)^n",
+            "p*(1+r)^n-p",
+            "p*r/(1-(1+r)^-n)",
+            "(1+r)^n",
+            "sqrt((1+r)^n)+max(p,r)",
+        ]);
+        let set = FormulaSet::new(&formulas);
+        let values = set.evaluate(&variables()).unwrap();
+        for (formula, value) in formulas.iter().zip(values.iter()) {
+            assert_eq!(*value, ast::eval_with(formula, &variables()).unwrap().as_number().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_cse_shared_report() {
+        let formulas = parse_all(&["p*(1+r)^n", "(1+r)^n-1", "p*(1+r)^n/2", "1-r"]);
+        let set = FormulaSet::new(&formulas);
+        let shared = set.shared();
+        let expressions: Vec<&str> = shared.iter().map(|item| item.expression.as_str()).collect();
+        // 1+r is only used by (1+r)^n, so it isn't shared by itself
+        assert_eq!(expressions, vec!["(1+r)^n", "p*(1+r)^n"]);
+        assert_eq!(shared[0].uses, 2);
+        assert_eq!(shared[0].formulas, vec![0, 1, 2]);
+        assert_eq!(shared[1].formulas, vec![0, 2]);
+        assert_eq!(shared[1].to_string(), "p*(1+r)^n is used 2 times in the formulas 0, 2");
+    }
+
+    #[test]
+    fn test_cse_unknown_variable() {
+        let set = FormulaSet::new(&parse_all(&["p+1", "q*2"]));
+        assert_eq!(set.evaluate(&variables()), Err(EvalError::UnknownVariable("q".into())));
+    }
+
+    #[test]
+    fn test_cse_higher_order() {
+        // The bound i of the sums and the free i are the same node of the DAG
+        let formulas = parse_all(&["sum(i, 1, n, i^2)", "i^2+sum(i, 1, n, i^2)", "(i^2+1)*i^2"]);
+        let set = FormulaSet::new(&formulas);
+        assert_eq!(set.evaluate(&variables()), Err(EvalError::UnsupportedFunction("sum".into())));
+        let shared = set.shared();
+        let expressions: Vec<&str> = shared.iter().map(|item| item.expression.as_str()).collect();
+        assert_eq!(expressions, vec!["i^2", "sum(i,1,n,i^2)"]);
+        assert_eq!(shared[0].formulas, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_cse_conditional() {
+        // The branch that isn't taken isn't evaluated, even when another formula shares it
+        let set = FormulaSet::new(&parse_all(&["if(p, r, q)", "if(0, q, n)", "if(p, 2)"]));
+        assert_eq!(set.evaluate(&variables()), Err(EvalError::WrongArgumentCount("if".into(), 3, 2)));
+        let set = FormulaSet::new(&parse_all(&["if(p, r, q)", "if(0, q, n)", "if(r, q, n)"]));
+        assert_eq!(set.evaluate(&variables()), Err(EvalError::UnknownVariable("q".into())));
+        let set = FormulaSet::new(&parse_all(&["if(p, r, q)", "if(0, q, n)"]));
+        let values = set.evaluate(&variables()).unwrap();
+        assert_eq!(values, vec![variables()["r"], variables()["n"]]);
+    }
+}
@@ -0,0 +1,289 @@
+//! This module holds the **dates** like `2026-10-16` or `2026-10-16T14:30`. A date is a point in
+//! time and a duration is a quantity of time like `30 days` or `2 h`, so the deadlines are
+//! computed with the usual operators: a date minus a date is a duration, a date plus or minus a
+//! duration is a date and `(end - start) in hours` writes the duration in hours. Adding two dates
+//! fails with `EvalError::TypeMismatch`.
+//!
+//! The dates use the proleptic Gregorian calendar without time zones, every day has 86400
+//! seconds, so there is nothing to download
+
+use std::fmt;
+
+use super::ast::{EvalError, Node};
+use super::units::{self, TIME};
+use super::value::{not_a_number, Value, DATE_TYPE, DURATION_TYPE};
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// The DateTime struct holds a date and a time of the day
+/// # Arguments
+/// * `seconds: f64` - the seconds since 1970-01-01T00:00, negative before
+/// # Example
+/// ```
+/// let date = DateTime::parse("1970-01-02").unwrap();
+/// // date.seconds() will be 86400 and the date is displayed as 1970-01-02
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DateTime {
+    seconds: f64,
+}
+
+impl DateTime {
+    /// Returns the date of the given day and time, or `None` if it doesn't exist like the
+    /// 2026-02-29
+    /// # Arguments
+    /// * `year: i64, month: u32, day: u32` - the day, the months and the days start at 1
+    /// * `hour: u32, minute: u32, second: f64` - the time of the day
+    pub fn new(year: i64, month: u32, day: u32, hour: u32, minute: u32, second: f64) -> Option<DateTime> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+        if hour > 23 || minute > 59 || !(0.0..60.0).contains(&second) {
+            return None;
+        }
+        let days = days_from_civil(year, month, day) as f64;
+        let time = (hour * 3600 + minute * 60) as f64 + second;
+        Some(DateTime {
+            seconds: days * SECONDS_PER_DAY + time,
+        })
+    }
+
+    /// Returns the date written like `2026-10-16`, `2026-10-16T14:30` or
+    /// `2026-10-16T14:30:15.5`
+    /// # Arguments
+    /// * `text: &str` - the date with four digits for the year and two for the other parts
+    /// # Returns
+    /// * `Option<DateTime>` - the date or `None` if the text isn't a date of the calendar
+    /// # Example
+    /// ```
+    /// let date = DateTime::parse("2026-13-01");
+    /// // This will be None, there are only 12 months
+    /// ```
+    pub fn parse(text: &str) -> Option<DateTime> {
+        let (date, time) = match text.split_once('T') {
+            Some((date, time)) => (date, Some(time)),
+            None => (text, None),
+        };
+        let mut parts = date.split('-');
+        let year = digits(parts.next()?, 4)?;
+        let month = digits(parts.next()?, 2)?;
+        let day = digits(parts.next()?, 2)?;
+        if parts.next().is_some() {
+            return None;
+        }
+        let (hour, minute, second) = match time {
+            Some(time) => {
+                let mut parts = time.split(':');
+                let hour = digits(parts.next()?, 2)?;
+                let minute = digits(parts.next()?, 2)?;
+                // The seconds are optional and can have decimals
+                let second = match parts.next() {
+                    Some(second) if second.len() >= 2 && second[..2].chars().all(|c| c.is_ascii_digit()) => {
+                        second.parse::<f64>().ok()?
+                    }
+                    Some(_) => return None,
+                    None => 0.0,
+                };
+                if parts.next().is_some() {
+                    return None;
+                }
+                (hour, minute, second)
+            }
+            None => (0, 0, 0.0),
+        };
+        DateTime::new(year as i64, month, day, hour, minute, second)
+    }
+
+    /// Returns the seconds since 1970-01-01T00:00
+    pub fn seconds(&self) -> f64 {
+        self.seconds
+    }
+
+    /// Returns the date a number of seconds later, earlier for a negative number
+    pub fn add_seconds(self, seconds: f64) -> DateTime {
+        DateTime {
+            seconds: self.seconds + seconds,
+        }
+    }
+}
+
+// The trait for display with format! or println!, the time is only written when it isn't
+// midnight and the seconds when they aren't zero
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let days = (self.seconds / SECONDS_PER_DAY).floor();
+        let (year, month, day) = civil_from_days(days as i64);
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)?;
+        let time = self.seconds - days * SECONDS_PER_DAY;
+        if time == 0.0 {
+            return Ok(());
+        }
+        let minutes = (time / 60.0).floor();
+        write!(f, "T{:02}:{:02}", minutes as u32 / 60, minutes as u32 % 60)?;
+        let second = time - minutes * 60.0;
+        if second == 0.0 {
+            Ok(())
+        } else if second < 10.0 {
+            write!(f, ":0{}", second)
+        } else {
+            write!(f, ":{}", second)
+        }
+    }
+}
+
+// Reads a part of a date with the given number of digits
+fn digits(text: &str, count: usize) -> Option<u32> {
+    if text.len() != count || !text.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// The days since 1970-01-01 of a day of the calendar. We count the years from March, so the
+// leap day is the last day of the year, and the eras of 400 years repeat the same calendar
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+// The day of the calendar of a number of days since 1970-01-01, the inverse of days_from_civil
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 } as u32;
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+/// Applies an arithmetic operation where one of the operands is a date. A date minus a date is
+/// a duration, a date plus or minus a duration is a date and a duration plus a date is a date
+/// # Arguments
+/// * `expr: &Node` - the operation node, it gives the operator
+/// * `left: Value, right: Value` - the values of the operands
+/// # Returns
+/// * `Result<Value, EvalError>` - the result, the duration between two dates is written in days
+///   when it's a whole number of days, `EvalError::TypeMismatch` for the other operands and
+///   `EvalError::DimensionMismatch` for a quantity that isn't a duration
+/// # Example
+/// ```
+/// let deadline = apply(&add_node, date, duration);
+/// // For 2026-10-16 and 30 days this will be Ok with the date displayed as 2026-11-15
+/// ```
+pub fn apply(expr: &Node, left: Value, right: Value) -> Result<Value, EvalError> {
+    match (expr, left, right) {
+        (Node::SUBTRACT(..), Value::DATE(left), Value::DATE(right)) => duration(left.seconds - right.seconds),
+        (Node::ADD(..), Value::DATE(date), duration) | (Node::ADD(..), duration, Value::DATE(date)) => {
+            Ok(Value::DATE(date.add_seconds(seconds(duration)?)))
+        }
+        (Node::SUBTRACT(..), Value::DATE(date), duration) => {
+            Ok(Value::DATE(date.add_seconds(-seconds(duration)?)))
+        }
+        // Only a date can be subtracted from a date
+        (Node::SUBTRACT(..), other, _) => {
+            Err(EvalError::TypeMismatch(DATE_TYPE.to_string(), other.type_name().to_string()))
+        }
+        _ => Err(not_a_number(DATE_TYPE)),
+    }
+}
+
+// The seconds of a duration
+fn seconds(duration: Value) -> Result<f64, EvalError> {
+    match duration {
+        Value::QUANTITY(quantity) if quantity.dimension() == TIME => Ok(quantity.value()),
+        Value::QUANTITY(quantity) => {
+            Err(EvalError::DimensionMismatch(TIME.to_string(), quantity.dimension().to_string()))
+        }
+        other => Err(EvalError::TypeMismatch(DURATION_TYPE.to_string(), other.type_name().to_string())),
+    }
+}
+
+// The duration of a number of seconds in the largest unit that gives a whole number
+fn duration(seconds: f64) -> Result<Value, EvalError> {
+    let unit = [("days", SECONDS_PER_DAY), ("h", 3600.0), ("min", 60.0)]
+        .iter()
+        .find(|(_, factor)| (seconds / factor).fract() == 0.0);
+    match unit {
+        Some(&(unit, factor)) => units::quantity(seconds / factor, unit),
+        None => units::quantity(seconds, "s"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_datetime_parse() {
+        assert_eq!(DateTime::parse("1970-01-02").unwrap().seconds(), SECONDS_PER_DAY);
+        assert_eq!(DateTime::parse("1969-12-31T23:59:30").unwrap().seconds(), -30.0);
+        assert_eq!(DateTime::parse("2024-02-29"), DateTime::new(2024, 2, 29, 0, 0, 0.0));
+        // The days and the times that don't exist
+        for text in ["2026-02-29", "1900-02-29", "2026-13-01", "2026-10-16T24:00", "2026-1-16", "2026-10-16T"] {
+            assert_eq!(DateTime::parse(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_datetime_display() {
+        for text in ["2026-10-16", "2026-10-16T14:30", "2026-10-16T14:30:05", "2000-02-29T00:00:15.5", "1969-07-20T20:17"] {
+            assert_eq!(DateTime::parse(text).unwrap().to_string(), text);
+        }
+        // The days of the calendar follow each other
+        let mut date = DateTime::parse("1599-12-31").unwrap();
+        for _ in 0..200_000 {
+            let next = date.add_seconds(SECONDS_PER_DAY);
+            assert_eq!(DateTime::parse(&next.to_string()), Some(next));
+            date = next;
+        }
+    }
+
+    #[test]
+    fn test_datetime_apply() {
+        let date = |text: &str| Value::DATE(DateTime::parse(text).unwrap());
+        let add = Node::ADD(Box::new(Node::ERROR), Box::new(Node::ERROR));
+        let subtract = Node::SUBTRACT(Box::new(Node::ERROR), Box::new(Node::ERROR));
+        let days = units::quantity(30.0, "days").unwrap();
+        assert_eq!(apply(&add, date("2026-10-16"), days.clone()), Ok(date("2026-11-15")));
+        assert_eq!(apply(&add, days.clone(), date("2026-10-16")), Ok(date("2026-11-15")));
+        assert_eq!(apply(&subtract, date("2026-03-01"), days.clone()), Ok(date("2026-01-30")));
+        let between = apply(&subtract, date("2026-10-16T12:00"), date("2026-10-14")).unwrap();
+        assert_eq!(between.to_string(), "60 h");
+        let between = apply(&subtract, date("2026-10-16"), date("2026-10-14")).unwrap();
+        assert_eq!(between.to_string(), "2 days");
+        let found = |found: &str| Err(EvalError::TypeMismatch(DURATION_TYPE.to_string(), found.to_string()));
+        assert_eq!(apply(&add, date("2026-10-16"), date("2026-10-16")), found(DATE_TYPE));
+        assert_eq!(apply(&add, date("2026-10-16"), Value::NUMBER(1.0)), found("a number"));
+        let meters = units::quantity(1.0, "m").unwrap();
+        assert_eq!(
+            apply(&add, date("2026-10-16"), meters),
+            Err(EvalError::DimensionMismatch("s".to_string(), "m".to_string()))
+        );
+        assert_eq!(
+            apply(&subtract, days, date("2026-10-16")),
+            Err(EvalError::TypeMismatch(DATE_TYPE.to_string(), "a quantity".to_string()))
+        );
+    }
+}
@@ -0,0 +1,443 @@
+//! This module holds the **exporters** that convert an AST to the math notations used by
+//! documents, LaTeX and Presentation MathML. Unlike the printer the output follows the usual
+//! math conventions, so `-2^2` (that the parser reads as `(-2)^2`) is rendered with parentheses
+
+use super::{ast::Node, token::OperPrec};
+
+/// The side of a binary operation where an operand is placed
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+    LEFT,
+    RIGHT,
+}
+
+/// Converts an AST to a LaTeX math expression
+/// # Arguments
+/// * `node: &Node` - the root of the AST
+/// # Returns
+/// * `String` - the LaTeX code without the math delimiters (`$`)
+/// # Example
+/// ```
+/// let mut parser = Parser::new("sqrt(2)/2*3^2").unwrap();
+/// let latex = to_latex(&parser.parse().unwrap());
+/// // This will be \frac{\sqrt{2}}{2} \cdot 3^{2}
+/// ```
+pub fn to_latex(node: &Node) -> String {
+    let mut out = String::new();
+    write_latex(&mut out, node);
+    out
+}
+
+/// Converts an AST to a Presentation MathML element
+/// # Arguments
+/// * `node: &Node` - the root of the AST
+/// # Returns
+/// * `String` - a `<math>` element holding the expression
+/// # Example
+/// ```
+/// let mut parser = Parser::new("1+2").unwrap();
+/// let mathml = to_mathml(&parser.parse().unwrap());
+/// // This will be <math xmlns="..."><mrow><mn>1</mn><mo>+</mo><mn>2</mn></mrow></math>
+/// ```
+pub fn to_mathml(node: &Node) -> String {
+    let mut out = String::from("<math xmlns=\"http://www.w3.org/1998/Math/MathML\">");
+    write_mathml_row(&mut out, node);
+    out.push_str("</math>");
+    out
+}
+
+// The precedence of a node as is read in a math document, a fraction is drawn as a block so
+// it never needs parentheses
+fn math_prec(node: &Node) -> OperPrec {
+    match node {
+        Node::DIVIDE(..) => OperPrec::NEGATIVE,
+        Node::NEGATIVE(..) => OperPrec::ADDSUB,
+        Node::NUMBER(value) if *value < 0.0 => OperPrec::ADDSUB,
+        // A value with its unit reads as a single term, only a power needs it in parentheses
+        Node::QUANTITY(..) => OperPrec::POWER,
+        _ => node.get_oper_prec(),
+    }
+}
+
+// Checks if an operand of the parent node must be wrapped with parentheses
+fn needs_parens(parent: &Node, child: &Node, side: Side) -> bool {
+    let child_prec = math_prec(child);
+    match parent {
+        // The fraction bar and the exponent group their content
+        Node::DIVIDE(..) => false,
+        Node::CARRET(..) | Node::INDEX(..) if side == Side::RIGHT => false,
+        // The base of a power, a list with a subscript and a percentage must be a single number
+        // or a call
+        Node::CARRET(..) | Node::INDEX(..) | Node::PERCENT(..) => {
+            child_prec < OperPrec::NEGATIVE || matches!(child, Node::DIVIDE(..))
+        }
+        Node::NEGATIVE(..) => child_prec <= OperPrec::ADDSUB,
+        Node::FUNCTION(..) | Node::VARIABLE(..) | Node::NUMBER(..) | Node::LAMBDA(..) | Node::LIST(..) => false,
+        Node::QUANTITY(..) | Node::DATE(_) => false,
+        _ => {
+            let prec = parent.get_oper_prec();
+            match side {
+                // A negative on the left reads the same as a subtraction from zero
+                Side::LEFT => child_prec < prec && !is_negative(child),
+                Side::RIGHT => child_prec <= prec,
+            }
+        }
+    }
+}
+
+// The LaTeX command and the letter of the constants written with a Greek letter
+fn greek_letter(name: &str) -> Option<(&'static str, &'static str)> {
+    match name {
+        "pi" | "π" => Some(("\\pi", "π")),
+        "tau" => Some(("\\tau", "τ")),
+        "phi" => Some(("\\varphi", "φ")),
+        _ => None,
+    }
+}
+
+fn is_negative(node: &Node) -> bool {
+    match node {
+        Node::NEGATIVE(..) => true,
+        Node::NUMBER(value) => *value < 0.0,
+        _ => false,
+    }
+}
+
+fn write_latex_operand(out: &mut String, parent: &Node, child: &Node, side: Side) {
+    if needs_parens(parent, child, side) {
+        out.push_str("\\left(");
+        write_latex(out, child);
+        out.push_str("\\right)");
+    } else {
+        write_latex(out, child);
+    }
+}
+
+// Writes a unit in upright letters, the products use a centered dot, m/s^2 is \mathrm{m/s^{2}}
+fn write_latex_unit(out: &mut String, unit: &str) {
+    out.push_str("\\mathrm{");
+    let mut chars = unit.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => out.push_str(" \\cdot "),
+            '^' => {
+                out.push_str("^{");
+                while let Some(digit) = chars.next_if(|c| c.is_ascii_digit() || *c == '-') {
+                    out.push(digit);
+                }
+                out.push('}');
+            }
+            _ => out.push(c),
+        }
+    }
+    out.push('}');
+}
+
+fn write_latex(out: &mut String, node: &Node) {
+    use super::ast::Node::*;
+    match node {
+        NUMBER(value) => out.push_str(&value.to_string()),
+        // A thin space separates the value from its unit
+        QUANTITY(value, unit) => {
+            out.push_str(&value.to_string());
+            out.push_str("\\,");
+            write_latex_unit(out, unit);
+        }
+        // The dashes of a date aren't minus signs
+        DATE(date) => out.push_str(&format!("\\text{{{}}}", date)),
+        CONVERT(expr, unit) => {
+            write_latex_operand(out, node, expr, Side::LEFT);
+            out.push_str(" \\;\\text{in}\\; ");
+            write_latex_unit(out, unit);
+        }
+        // The constants are written with their Greek letter and the names longer than a letter
+        // in italic as a single word
+        VARIABLE(name) => match greek_letter(name) {
+            Some((command, _)) => out.push_str(command),
+            None if name.chars().count() == 1 => out.push_str(name),
+            None => out.push_str(&format!("\\mathit{{{}}}", name)),
+        },
+        ERROR => out.push_str("\\text{?}"),
+        LAMBDA(parameters, body) => {
+            let names: Vec<Node> = parameters.iter().map(|name| VARIABLE(name.clone())).collect();
+            if names.len() != 1 {
+                out.push_str("\\left(");
+            }
+            for (i, name) in names.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_latex(out, name);
+            }
+            if names.len() != 1 {
+                out.push_str("\\right)");
+            }
+            out.push_str(" \\mapsto ");
+            write_latex(out, body);
+        }
+        LIST(items) => {
+            out.push_str("\\left[");
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_latex(out, item);
+            }
+            out.push_str("\\right]");
+        }
+        // The index is written as a subscript, v_{0}
+        INDEX(list, index) => {
+            write_latex_operand(out, node, list, Side::LEFT);
+            out.push_str("_{");
+            write_latex(out, index);
+            out.push('}');
+        }
+        PERCENT(expr) => {
+            write_latex_operand(out, node, expr, Side::LEFT);
+            out.push_str("\\%");
+        }
+        RANGE(from, to) => {
+            write_latex_operand(out, node, from, Side::LEFT);
+            out.push_str(" \\ldots ");
+            write_latex_operand(out, node, to, Side::RIGHT);
+        }
+        NEGATIVE(expr) => {
+            out.push('-');
+            write_latex_operand(out, node, expr, Side::RIGHT);
+        }
+        ADD(left, right) | SUBTRACT(left, right) | MULTIPLY(left, right) => {
+            let symbol = match node {
+                ADD(..) => " + ",
+                SUBTRACT(..) => " - ",
+                _ => " \\cdot ",
+            };
+            write_latex_operand(out, node, left, Side::LEFT);
+            out.push_str(symbol);
+            write_latex_operand(out, node, right, Side::RIGHT);
+        }
+        DIVIDE(left, right) => {
+            out.push_str("\\frac{");
+            write_latex(out, left);
+            out.push_str("}{");
+            write_latex(out, right);
+            out.push('}');
+        }
+        CARRET(left, right) => {
+            write_latex_operand(out, node, left, Side::LEFT);
+            out.push_str("^{");
+            write_latex(out, right);
+            out.push('}');
+        }
+        FUNCTION(name, args) => match (name.as_str(), args.as_slice()) {
+            ("sqrt", [arg]) => {
+                out.push_str("\\sqrt{");
+                write_latex(out, arg);
+                out.push('}');
+            }
+            ("abs", [arg]) => {
+                out.push_str("\\left|");
+                write_latex(out, arg);
+                out.push_str("\\right|");
+            }
+            _ => {
+                // The functions known by LaTeX have their own command
+                match name.as_str() {
+                    "exp" | "ln" | "log" | "sin" | "cos" | "tan" | "min" | "max" => {
+                        out.push('\\');
+                        out.push_str(name);
+                    }
+                    _ => {
+                        out.push_str("\\operatorname{");
+                        out.push_str(name);
+                        out.push('}');
+                    }
+                }
+                out.push_str("\\left(");
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    write_latex(out, arg);
+                }
+                out.push_str("\\right)");
+            }
+        },
+    }
+}
+
+fn write_mathml_operand(out: &mut String, parent: &Node, child: &Node, side: Side) {
+    if needs_parens(parent, child, side) {
+        out.push_str("<mrow><mo>(</mo>");
+        write_mathml(out, child);
+        out.push_str("<mo>)</mo></mrow>");
+    } else {
+        write_mathml_row(out, child);
+    }
+}
+
+// Writes a node as a single MathML element, the operations are grouped inside a mrow
+fn write_mathml_row(out: &mut String, node: &Node) {
+    match node {
+        Node::NUMBER(value) if *value >= 0.0 => write_mathml(out, node),
+        Node::VARIABLE(..) => write_mathml(out, node),
+        Node::DIVIDE(..) | Node::CARRET(..) | Node::INDEX(..) => write_mathml(out, node),
+        Node::FUNCTION(name, args) if (name == "sqrt" || name == "abs") && args.len() == 1 => {
+            write_mathml(out, node)
+        }
+        _ => {
+            out.push_str("<mrow>");
+            write_mathml(out, node);
+            out.push_str("</mrow>");
+        }
+    }
+}
+
+fn write_mathml(out: &mut String, node: &Node) {
+    use super::ast::Node::*;
+    match node {
+        NUMBER(value) => {
+            if *value < 0.0 {
+                out.push_str("<mo>&#x2212;</mo>");
+            }
+            out.push_str(&format!("<mn>{}</mn>", value.abs()));
+        }
+        // The unit follows the value with the invisible times operator
+        QUANTITY(value, unit) => {
+            out.push_str(&format!("<mn>{}</mn><mo>&#x2062;</mo><mi mathvariant=\"normal\">{}</mi>", value, unit));
+        }
+        DATE(date) => out.push_str(&format!("<mn>{}</mn>", date)),
+        CONVERT(expr, unit) => {
+            write_mathml_operand(out, node, expr, Side::LEFT);
+            out.push_str(&format!("<mo>in</mo><mi mathvariant=\"normal\">{}</mi>", unit));
+        }
+        VARIABLE(name) => {
+            let name = greek_letter(name).map_or(name.as_str(), |(_, letter)| letter);
+            out.push_str(&format!("<mi>{}</mi>", name))
+        }
+        ERROR => out.push_str("<merror><mtext>?</mtext></merror>"),
+        LAMBDA(parameters, body) => {
+            let names: Vec<String> = parameters.iter().map(|name| format!("<mi>{}</mi>", name)).collect();
+            if names.len() == 1 {
+                out.push_str(&names[0]);
+            } else {
+                out.push_str(&format!("<mrow><mo>(</mo>{}<mo>)</mo></mrow>", names.join("<mo>,</mo>")));
+            }
+            out.push_str("<mo>&#x21A6;</mo>");
+            write_mathml_row(out, body);
+        }
+        LIST(items) => {
+            out.push_str("<mo>[</mo>");
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str("<mo>,</mo>");
+                }
+                write_mathml_row(out, item);
+            }
+            out.push_str("<mo>]</mo>");
+        }
+        INDEX(list, index) => {
+            out.push_str("<msub>");
+            write_mathml_operand(out, node, list, Side::LEFT);
+            write_mathml_row(out, index);
+            out.push_str("</msub>");
+        }
+        PERCENT(expr) => {
+            write_mathml_operand(out, node, expr, Side::LEFT);
+            out.push_str("<mo>%</mo>");
+        }
+        RANGE(from, to) => {
+            write_mathml_operand(out, node, from, Side::LEFT);
+            out.push_str("<mo>&#x2026;</mo>");
+            write_mathml_operand(out, node, to, Side::RIGHT);
+        }
+        NEGATIVE(expr) => {
+            out.push_str("<mo>&#x2212;</mo>");
+            write_mathml_operand(out, node, expr, Side::RIGHT);
+        }
+        ADD(left, right) | SUBTRACT(left, right) | MULTIPLY(left, right) => {
+            let symbol = match node {
+                ADD(..) => "+",
+                SUBTRACT(..) => "&#x2212;",
+                _ => "&#x22C5;",
+            };
+            write_mathml_operand(out, node, left, Side::LEFT);
+            out.push_str(&format!("<mo>{}</mo>", symbol));
+            write_mathml_operand(out, node, right, Side::RIGHT);
+        }
+        DIVIDE(left, right) => {
+            out.push_str("<mfrac>");
+            write_mathml_row(out, left);
+            write_mathml_row(out, right);
+            out.push_str("</mfrac>");
+        }
+        CARRET(left, right) => {
+            out.push_str("<msup>");
+            write_mathml_operand(out, node, left, Side::LEFT);
+            write_mathml_row(out, right);
+            out.push_str("</msup>");
+        }
+        FUNCTION(name, args) => match (name.as_str(), args.as_slice()) {
+            ("sqrt", [arg]) => {
+                out.push_str("<msqrt>");
+                write_mathml(out, arg);
+                out.push_str("</msqrt>");
+            }
+            ("abs", [arg]) => {
+                out.push_str("<mrow><mo>|</mo>");
+                write_mathml(out, arg);
+                out.push_str("<mo>|</mo></mrow>");
+            }
+            _ => {
+                // The name is followed by the invisible function application operator
+                out.push_str(&format!("<mi>{}</mi><mo>&#x2061;</mo><mrow><mo>(</mo>", name));
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str("<mo>,</mo>");
+                    }
+                    write_mathml_row(out, arg);
+                }
+                out.push_str("<mo>)</mo></mrow>");
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsemath::parser::Parser;
+
+    // The golden files hold pairs of lines, the input expression and the expected output,
+    // separated by a blank line
+    fn check_golden(golden: &str, export: fn(&Node) -> String) {
+        let mut cases = 0;
+        for case in golden.split("\n\n") {
+            let mut lines = case.lines();
+            let (input, expected) = match (lines.next(), lines.next()) {
+                (Some(input), Some(expected)) => (input, expected),
+                _ => continue,
+            };
+            let mut parser = Parser::new(input).unwrap();
+            let ast = parser.parse().unwrap();
+            assert_eq!(export(&ast), expected, "exporting {}", input);
+            cases += 1;
+        }
+        assert!(cases > 0);
+    }
+
+    #[test]
+    fn test_export_latex_golden() {
+        check_golden(include_str!("golden/latex.txt"), to_latex);
+    }
+
+    #[test]
+    fn test_export_mathml_golden() {
+        check_golden(include_str!("golden/mathml.txt"), to_mathml);
+    }
+
+    #[test]
+    fn test_export_negative_literal() {
+        let node = Node::CARRET(Box::new(Node::NUMBER(-2.0)), Box::new(Node::NUMBER(2.0)));
+        assert_eq!(to_latex(&node), "\\left(-2\\right)^{2}");
+    }
+}
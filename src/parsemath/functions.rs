@@ -0,0 +1,392 @@
+//! This module holds the **built-in functions** that can be called inside an expression like
+//! `sqrt(2)` or `max(1, 2)`, the functions are resolved by name when the AST is evaluated
+
+use super::ast::EvalError;
+use super::matrix::Matrix;
+use super::value::Value;
+
+/// The names of all the built-in functions
+pub const BUILTIN_NAMES: [&str; 10] = [
+    "sqrt", "abs", "exp", "ln", "log", "sin", "cos", "tan", "min", "max",
+];
+
+/// The name of the conditional `if(condition, then, otherwise)`, it isn't a built-in function
+/// because only the chosen branch is evaluated. The condition is true when it isn't zero
+pub const CONDITIONAL_NAME: &str = "if";
+
+/// The names of the higher-order built-in functions, their arguments are names, expressions or
+/// lambdas that are evaluated many times
+pub const HIGHER_ORDER_NAMES: [&str; 4] = ["sum", "prod", "fold", "map"];
+
+/// The HigherOrder enum holds a built-in function that takes expressions or lambdas instead of
+/// numbers, the evaluator decides when their arguments are evaluated
+/// - SUM: `sum(i, from, to, expr)`, the sum of the expression for every integer `i` of the range
+/// - PROD: `prod(i, from, to, expr)`, the product of the expression for every integer `i`
+/// - FOLD: `fold(f, init, from, to)`, calls `f(accumulator, i)` for every integer `i` of the range
+/// - MAP: `map(f, value)`, calls the lambda `f` with the value or with every element of a list
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HigherOrder {
+    SUM,
+    PROD,
+    FOLD,
+    MAP,
+}
+
+impl HigherOrder {
+    /// Returns the higher-order function with the given name
+    /// # Arguments
+    /// * `name: &str` - the name of the function
+    /// # Returns
+    /// * `Option<HigherOrder>` - `Some(function)` if the function exists or `None` if isn't one
+    pub fn from_name(name: &str) -> Option<HigherOrder> {
+        use self::HigherOrder::*;
+        match name {
+            "sum" => Some(SUM),
+            "prod" => Some(PROD),
+            "fold" => Some(FOLD),
+            "map" => Some(MAP),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of arguments that the function takes
+    pub fn arity(self) -> usize {
+        match self {
+            HigherOrder::MAP => 2,
+            _ => 4,
+        }
+    }
+}
+
+/// The names of the aggregate functions, they take lists and return a number. The `sum` with a
+/// single argument is the sum of a list, with four it's the higher-order function
+pub const AGGREGATE_NAMES: [&str; 4] = ["sum", "mean", "len", "dot"];
+
+/// The Aggregate enum holds a built-in function that reduces lists to a number
+/// - SUM: `sum(v)`, the sum of the elements
+/// - MEAN: `mean(v)`, the arithmetic mean of the elements, NaN for an empty list
+/// - LEN: `len(v)`, the number of elements
+/// - DOT: `dot(v, w)`, the dot product of two lists with the same length
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aggregate {
+    SUM,
+    MEAN,
+    LEN,
+    DOT,
+}
+
+impl Aggregate {
+    /// Returns the aggregate function with the given name
+    /// # Arguments
+    /// * `name: &str` - the name of the function
+    /// # Returns
+    /// * `Option<Aggregate>` - `Some(function)` if the function exists or `None` if isn't one
+    pub fn from_name(name: &str) -> Option<Aggregate> {
+        use self::Aggregate::*;
+        match name {
+            "sum" => Some(SUM),
+            "mean" => Some(MEAN),
+            "len" => Some(LEN),
+            "dot" => Some(DOT),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of lists that the function takes
+    pub fn arity(self) -> usize {
+        match self {
+            Aggregate::DOT => 2,
+            _ => 1,
+        }
+    }
+
+    /// Applies the function to the lists, the caller must pass as many lists as the arity
+    /// # Arguments
+    /// * `lists: &[&[f64]]` - the elements of every list
+    /// # Returns
+    /// * `Result<f64, EvalError>` - the result or `EvalError::LengthMismatch` if the lists of a
+    ///   dot product have different lengths
+    /// # Example
+    /// ```
+    /// let mean = Aggregate::MEAN.apply(&[&[1.0, 2.0, 6.0]]);
+    /// // This will be Ok(3.0)
+    /// ```
+    pub fn apply(self, lists: &[&[f64]]) -> Result<f64, EvalError> {
+        use self::Aggregate::*;
+        let list = lists[0];
+        let result = match self {
+            SUM => list.iter().sum(),
+            MEAN => list.iter().sum::<f64>() / list.len() as f64,
+            LEN => list.len() as f64,
+            DOT => {
+                let other = lists[1];
+                if list.len() != other.len() {
+                    return Err(EvalError::LengthMismatch(list.len(), other.len()));
+                }
+                list.iter().zip(other).map(|(a, b)| a * b).sum()
+            }
+        };
+        Ok(result)
+    }
+}
+
+/// The names of the linear algebra functions, they take matrices
+pub const MATRIX_NAMES: [&str; 4] = ["transpose", "det", "inverse", "solve"];
+
+/// The MatrixFunction enum holds a built-in function of linear algebra
+/// - TRANSPOSE: `transpose(m)`, the matrix with the rows as columns
+/// - DET: `det(m)`, the determinant of a square matrix
+/// - INVERSE: `inverse(m)`, the inverse of a square matrix that isn't singular
+/// - SOLVE: `solve(m, b)`, the solution `x` of `m * x = b` where `b` is a list or a matrix
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MatrixFunction {
+    TRANSPOSE,
+    DET,
+    INVERSE,
+    SOLVE,
+}
+
+impl MatrixFunction {
+    /// Returns the matrix function with the given name
+    /// # Arguments
+    /// * `name: &str` - the name of the function
+    /// # Returns
+    /// * `Option<MatrixFunction>` - `Some(function)` if the function exists or `None` if isn't one
+    pub fn from_name(name: &str) -> Option<MatrixFunction> {
+        use self::MatrixFunction::*;
+        match name {
+            "transpose" => Some(TRANSPOSE),
+            "det" => Some(DET),
+            "inverse" => Some(INVERSE),
+            "solve" => Some(SOLVE),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of arguments that the function takes
+    pub fn arity(self) -> usize {
+        match self {
+            MatrixFunction::SOLVE => 2,
+            _ => 1,
+        }
+    }
+
+    /// Applies the function to the values, the caller must pass as many values as the arity
+    /// # Arguments
+    /// * `values: &[Value]` - the arguments, the first one must be a matrix
+    /// # Returns
+    /// * `Result<Value, EvalError>` - the result, `EvalError::TypeMismatch` for an argument of
+    ///   the wrong type or the errors of the shapes from `Matrix`
+    /// # Example
+    /// ```
+    /// let matrix = Matrix::from_rows(vec![vec![2.0, 0.0], vec![0.0, 4.0]]).unwrap();
+    /// let x = MatrixFunction::SOLVE.apply(&[Value::MATRIX(matrix), Value::LIST(vec![2.0, 2.0])]);
+    /// // This will be Ok(Value::LIST(vec![1.0, 0.5]))
+    /// ```
+    pub fn apply(self, values: &[Value]) -> Result<Value, EvalError> {
+        use self::MatrixFunction::*;
+        let matrix = values[0].as_matrix()?;
+        match self {
+            TRANSPOSE => Ok(Value::MATRIX(matrix.transpose())),
+            DET => matrix.determinant().map(Value::NUMBER),
+            INVERSE => matrix.inverse().map(Value::MATRIX),
+            SOLVE => match &values[1] {
+                // A list is a single system and its solution is a list
+                Value::LIST(b) => Ok(Value::LIST(matrix.solve(&Matrix::column(b))?.data().to_vec())),
+                b => matrix.solve(b.as_matrix()?).map(Value::MATRIX),
+            },
+        }
+    }
+}
+
+/// Returns true if the name belongs to a function of the evaluator, a built-in, a higher-order
+/// function, an aggregate, a matrix function or the conditional, a program can't define a
+/// function with that name
+pub fn is_reserved(name: &str) -> bool {
+    get_arity(name).is_some()
+        || HigherOrder::from_name(name).is_some()
+        || Aggregate::from_name(name).is_some()
+        || MatrixFunction::from_name(name).is_some()
+        || name == CONDITIONAL_NAME
+}
+
+/// The Builtin enum holds a built-in function already resolved from its name, so it can be
+/// called without comparing strings
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Builtin {
+    SQRT,
+    ABS,
+    EXP,
+    LN,
+    LOG,
+    SIN,
+    COS,
+    TAN,
+    MIN,
+    MAX,
+}
+
+impl Builtin {
+    /// Returns the built-in function with the given name
+    /// # Arguments
+    /// * `name: &str` - the name of the function
+    /// # Returns
+    /// * `Option<Builtin>` - `Some(builtin)` if the function exists or `None` if is unknown
+    /// # Example
+    /// ```
+    /// let builtin = Builtin::from_name("sqrt");
+    /// // This will be Some(Builtin::SQRT)
+    /// ```
+    pub fn from_name(name: &str) -> Option<Builtin> {
+        use self::Builtin::*;
+        match name {
+            "sqrt" => Some(SQRT),
+            "abs" => Some(ABS),
+            "exp" => Some(EXP),
+            "ln" => Some(LN),
+            "log" => Some(LOG),
+            "sin" => Some(SIN),
+            "cos" => Some(COS),
+            "tan" => Some(TAN),
+            "min" => Some(MIN),
+            "max" => Some(MAX),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of arguments that the function takes
+    pub fn arity(self) -> usize {
+        match self {
+            Builtin::MIN | Builtin::MAX => 2,
+            _ => 1,
+        }
+    }
+
+    /// Applies the function to the values of the arguments, the caller must pass as many values
+    /// as the arity of the function
+    pub fn apply(self, args: &[f64]) -> f64 {
+        use self::Builtin::*;
+        match self {
+            SQRT => args[0].sqrt(),
+            ABS => args[0].abs(),
+            EXP => args[0].exp(),
+            LN => args[0].ln(),
+            LOG => args[0].log10(),
+            SIN => args[0].sin(),
+            COS => args[0].cos(),
+            TAN => args[0].tan(),
+            MIN => args[0].min(args[1]),
+            MAX => args[0].max(args[1]),
+        }
+    }
+}
+
+/// Returns the number of arguments that a built-in function takes
+/// # Arguments
+/// * `name: &str` - the name of the function
+/// # Returns
+/// * `Option<usize>` - `Some(arity)` if the function exists or `None` if is unknown
+/// # Example
+/// ```
+/// let arity = get_arity("max");
+/// // This will be Some(2)
+/// ```
+pub fn get_arity(name: &str) -> Option<usize> {
+    Builtin::from_name(name).map(Builtin::arity)
+}
+
+/// Calls a built-in function with the already evaluated arguments
+/// # Arguments
+/// * `name: &str` - the name of the function
+/// * `args: &[f64]` - the values of the arguments
+/// # Returns
+/// * `Result<f64, EvalError>` - the result of the call or an error if the function doesn't exist
+///   or the number of arguments is wrong
+/// # Example
+/// ```
+/// let root = call("sqrt", &[16.0]);
+/// // This will be Ok(4.0)
+/// ```
+pub fn call(name: &str, args: &[f64]) -> Result<f64, EvalError> {
+    // We check the function exist and receive the right number of arguments
+    let builtin = match Builtin::from_name(name) {
+        Some(builtin) => builtin,
+        None => return Err(EvalError::UnknownFunction(name.to_string())),
+    };
+    if builtin.arity() != args.len() {
+        return Err(EvalError::WrongArgumentCount(name.to_string(), builtin.arity(), args.len()));
+    }
+    Ok(builtin.apply(args))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_functions_arity() {
+        for name in BUILTIN_NAMES.iter() {
+            assert!(get_arity(name).is_some());
+        }
+        assert_eq!(get_arity("max"), Some(2));
+        assert_eq!(get_arity("foo"), None);
+    }
+
+    #[test]
+    fn test_functions_higher_order() {
+        for name in HIGHER_ORDER_NAMES.iter() {
+            assert!(HigherOrder::from_name(name).is_some());
+            assert!(is_reserved(name));
+        }
+        assert_eq!(HigherOrder::from_name("map").map(HigherOrder::arity), Some(2));
+        assert!(is_reserved("if") && is_reserved("sqrt"));
+        assert!(!is_reserved("f"));
+    }
+
+    #[test]
+    fn test_functions_aggregate() {
+        for name in AGGREGATE_NAMES.iter() {
+            assert!(Aggregate::from_name(name).is_some());
+            assert!(is_reserved(name));
+        }
+        let list = [1.0, 2.0, 6.0];
+        assert_eq!(Aggregate::SUM.apply(&[&list]), Ok(9.0));
+        assert_eq!(Aggregate::MEAN.apply(&[&list]), Ok(3.0));
+        assert_eq!(Aggregate::LEN.apply(&[&[]]), Ok(0.0));
+        assert_eq!(Aggregate::DOT.apply(&[&list, &[1.0, 0.0, 2.0]]), Ok(13.0));
+        assert_eq!(Aggregate::DOT.apply(&[&list, &[1.0]]), Err(EvalError::LengthMismatch(3, 1)));
+    }
+
+    #[test]
+    fn test_functions_matrix() {
+        for name in MATRIX_NAMES.iter() {
+            assert!(MatrixFunction::from_name(name).is_some());
+            assert!(is_reserved(name));
+        }
+        let matrix = Value::MATRIX(Matrix::from_rows(vec![vec![2.0, 0.0], vec![0.0, 4.0]]).unwrap());
+        assert_eq!(MatrixFunction::DET.apply(std::slice::from_ref(&matrix)), Ok(Value::NUMBER(8.0)));
+        let x = MatrixFunction::SOLVE.apply(&[matrix.clone(), Value::LIST(vec![2.0, 2.0])]);
+        assert_eq!(x, Ok(Value::LIST(vec![1.0, 0.5])));
+        let error = MatrixFunction::SOLVE.apply(&[matrix, Value::NUMBER(1.0)]).unwrap_err();
+        assert_eq!(error.to_string(), "Expected a matrix but found a number");
+        let error = MatrixFunction::TRANSPOSE.apply(&[Value::LIST(vec![1.0])]).unwrap_err();
+        assert_eq!(error.to_string(), "Expected a matrix but found a list");
+    }
+
+    #[test]
+    fn test_functions_call() {
+        assert_eq!(call("sqrt", &[16.0]).unwrap(), 4.0);
+        assert_eq!(call("abs", &[-3.0]).unwrap(), 3.0);
+        assert_eq!(call("log", &[1000.0]).unwrap(), 3.0);
+        assert_eq!(call("max", &[1.0, 2.0]).unwrap(), 2.0);
+    }
+
+    #[test]
+    fn test_functions_errors() {
+        assert_eq!(call("foo", &[1.0]), Err(EvalError::UnknownFunction("foo".into())));
+        assert_eq!(
+            call("sqrt", &[1.0, 2.0]),
+            Err(EvalError::WrongArgumentCount("sqrt".into(), 1, 2))
+        );
+    }
+}
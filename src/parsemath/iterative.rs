@@ -0,0 +1,479 @@
+//! This module holds the **iterative parser and evaluator**, they give the same results than the
+//! recursive `Parser` and `ast::eval_with` but keep their pending work in a `Vec` instead of the
+//! call stack, so an expression can be as deep as the memory allows.
+//!
+//! The parser follows the recursive descent step by step, every call that the recursive parser
+//! would make is a `Pending` step that waits for the value of the call
+
+use std::collections::HashMap;
+
+use super::{
+    ast::{EvalError, Node},
+    constants,
+    functions::{self, HigherOrder, CONDITIONAL_NAME},
+    parser::{parameter_names, ParseError},
+    token::{OperPrec, Token},
+    tokenizer::{Tokenizer, PERCENT_KEYWORD},
+    value::{not_a_number, DATE_TYPE, FUNCTION_TYPE, LIST_TYPE, QUANTITY_TYPE},
+};
+
+// The steps of the parser that wait for a value
+enum Pending {
+    // Reads the range and the conversions after an expression, like `generate_ast` with the
+    // lowest precedence
+    Ast,
+    // A range with its lower bound, waits for the upper bound
+    Range(Node),
+    // Reads the conversions after an expression like x in km
+    Conversions,
+    // Reads the operators with more precedence than the given one, like `generate_operation`
+    Operators(OperPrec),
+    // A binary operator with its left operand, waits for the right operand
+    Right(Token, Node),
+    // A minus sign, waits for its operand
+    Negative,
+    // Reads the indexes and the percent signs after an operand, like `parse_postfix`
+    Postfix,
+    // An index with its list, waits for the index
+    Index(Node),
+    // A percentage followed by the keyword, waits for the value it multiplies
+    PercentOf(Node),
+    // A left parenthesis, waits for the expression inside
+    Paren,
+    // A closed group, a parenthesis after it multiplies it by another group
+    Group,
+    // A parenthesized expression followed by another one, waits for the second one
+    ImplicitMultiply(Node),
+    // A function call with the arguments already read, waits for the next argument
+    Args(String, Vec<Node>),
+    // A list with the items already read, waits for the next item
+    Items(Vec<Node>),
+    // The parameters of a lambda in parentheses already read, waits for the next one
+    Parameters(Vec<Node>),
+    // A lambda with its parameters, waits for the body
+    Lambda(Vec<String>),
+}
+
+/// Parses an infix expression without recursion
+/// # Arguments
+/// * `expr: &str` - the expression, with the same syntax than `Parser`
+/// # Returns
+/// * `Result<Node, ParseError>` - the same AST or the same error than `Parser::parse` without
+///   limits, the input length, depth and size aren't limited
+/// # Example
+/// ```
+/// let expr = "-".repeat(100_000) + "1";
+/// let ast = iterative::parse(&expr).unwrap();
+/// // The recursive parser would need 100000 nested calls
+/// ```
+pub fn parse(expr: &str) -> Result<Node, ParseError> {
+    let mut tokenizer = Tokenizer::new(expr);
+    let mut current_token = match tokenizer.next() {
+        Some(token) => token,
+        None => return Err(tokenizer.error()),
+    };
+    // We advance to the next token
+    let mut next_token = |current_token: &mut Token| -> Result<(), ParseError> {
+        match tokenizer.next() {
+            Some(token) => {
+                *current_token = token;
+                Ok(())
+            }
+            None => Err(tokenizer.error()),
+        }
+    };
+
+    let mut pending = vec![Pending::Ast, Pending::Operators(OperPrec::DEFAULTZERO)];
+    loop {
+        // We read a primary, a token that opens a group leaves a pending step and reads another
+        let mut value = loop {
+            match current_token.clone() {
+                Token::SUBTRACT => {
+                    next_token(&mut current_token)?;
+                    pending.push(Pending::Negative);
+                    pending.push(Pending::Operators(OperPrec::NEGATIVE));
+                }
+                Token::NUM(i) => {
+                    next_token(&mut current_token)?;
+                    pending.push(Pending::Postfix);
+                    // A unit after the number makes a quantity like 3 km
+                    if let Token::UNIT(unit) = current_token.clone() {
+                        next_token(&mut current_token)?;
+                        break Node::QUANTITY(i, unit);
+                    }
+                    break Node::NUMBER(i);
+                }
+                Token::DATE(date) => {
+                    next_token(&mut current_token)?;
+                    break Node::DATE(date);
+                }
+                Token::LEFTPAREN => {
+                    next_token(&mut current_token)?;
+                    pending.push(Pending::Paren);
+                    pending.push(Pending::Ast);
+                    pending.push(Pending::Operators(OperPrec::DEFAULTZERO));
+                }
+                Token::IDENT(name) => {
+                    next_token(&mut current_token)?;
+                    // A name followed by an arrow is the parameter of a lambda
+                    if current_token == Token::ARROW {
+                        next_token(&mut current_token)?;
+                        pending.push(Pending::Lambda(vec![name]));
+                        pending.push(Pending::Ast);
+                        pending.push(Pending::Operators(OperPrec::DEFAULTZERO));
+                        continue;
+                    }
+                    if current_token != Token::LEFTPAREN {
+                        pending.push(Pending::Postfix);
+                        break Node::VARIABLE(name);
+                    }
+                    next_token(&mut current_token)?;
+                    if current_token == Token::RIGHTPAREN {
+                        next_token(&mut current_token)?;
+                        pending.push(Pending::Postfix);
+                        break Node::FUNCTION(name, Vec::new());
+                    }
+                    pending.push(Pending::Args(name, Vec::new()));
+                    pending.push(Pending::Ast);
+                    pending.push(Pending::Operators(OperPrec::DEFAULTZERO));
+                }
+                Token::LEFTBRACKET => {
+                    next_token(&mut current_token)?;
+                    if current_token == Token::RIGHTBRACKET {
+                        next_token(&mut current_token)?;
+                        pending.push(Pending::Postfix);
+                        break Node::LIST(Vec::new());
+                    }
+                    pending.push(Pending::Items(Vec::new()));
+                    pending.push(Pending::Ast);
+                    pending.push(Pending::Operators(OperPrec::DEFAULTZERO));
+                }
+                _ => return Err(ParseError::UnableToParse("Unable to Parse".to_string())),
+            }
+        };
+        // And we give the value to the pending steps until one of them needs another operand
+        loop {
+            match pending.pop() {
+                None => return Ok(value),
+                Some(Pending::Ast) => {
+                    // A range binds looser than any operator, its bounds are whole expressions
+                    pending.push(Pending::Conversions);
+                    if current_token == Token::RANGE {
+                        next_token(&mut current_token)?;
+                        pending.push(Pending::Range(value));
+                        pending.push(Pending::Operators(OperPrec::DEFAULTZERO));
+                        break;
+                    }
+                }
+                Some(Pending::Range(from)) => {
+                    if current_token == Token::RANGE {
+                        return Err(ParseError::UnableToParse("The bound of a range can't be a range".to_string()));
+                    }
+                    value = Node::RANGE(Box::new(from), Box::new(value));
+                }
+                Some(Pending::Conversions) => {
+                    while current_token == Token::IN {
+                        next_token(&mut current_token)?;
+                        let unit = match current_token.clone() {
+                            Token::UNIT(unit) => unit,
+                            _ => return Err(ParseError::UnableToParse("Expected a unit after in".to_string())),
+                        };
+                        next_token(&mut current_token)?;
+                        value = Node::CONVERT(Box::new(value), unit);
+                    }
+                }
+                Some(Pending::Operators(oper_prec)) => {
+                    let operator = current_token.clone();
+                    if oper_prec < operator.get_oper_prec() && operator != Token::EOF {
+                        next_token(&mut current_token)?;
+                        // The loop goes on after the right operand, like the loop of `generate_operation`
+                        let right_prec = operator.get_oper_prec();
+                        pending.push(Pending::Operators(oper_prec));
+                        pending.push(Pending::Right(operator, value));
+                        pending.push(Pending::Operators(right_prec));
+                        break;
+                    }
+                }
+                Some(Pending::Right(operator, left)) => {
+                    let constructor = match operator {
+                        Token::ADD => Node::ADD,
+                        Token::SUBTRACT => Node::SUBTRACT,
+                        Token::MULTIPLY => Node::MULTIPLY,
+                        Token::DIVIDE => Node::DIVIDE,
+                        _ => Node::CARRET,
+                    };
+                    value = constructor(Box::new(left), Box::new(value));
+                }
+                Some(Pending::Negative) => value = Node::NEGATIVE(Box::new(value)),
+                Some(Pending::Postfix) => match current_token {
+                    Token::LEFTBRACKET => {
+                        next_token(&mut current_token)?;
+                        pending.push(Pending::Index(value));
+                        pending.push(Pending::Ast);
+                        pending.push(Pending::Operators(OperPrec::DEFAULTZERO));
+                        break;
+                    }
+                    Token::PERCENT => {
+                        next_token(&mut current_token)?;
+                        value = Node::PERCENT(Box::new(value));
+                        if matches!(&current_token, Token::IDENT(name) if name == PERCENT_KEYWORD) {
+                            next_token(&mut current_token)?;
+                            pending.push(Pending::PercentOf(value));
+                            pending.push(Pending::Operators(OperPrec::MULDIV));
+                            break;
+                        }
+                        pending.push(Pending::Postfix);
+                    }
+                    _ => (),
+                },
+                Some(Pending::Index(list)) => {
+                    check_token(&current_token, Token::RIGHTBRACKET)?;
+                    next_token(&mut current_token)?;
+                    value = Node::INDEX(Box::new(list), Box::new(value));
+                    pending.push(Pending::Postfix);
+                }
+                Some(Pending::PercentOf(percent)) => {
+                    value = Node::MULTIPLY(Box::new(percent), Box::new(value));
+                }
+                Some(Pending::Paren) => {
+                    // A comma inside the parentesis separates the parameters of a lambda
+                    if current_token == Token::COMMA {
+                        next_token(&mut current_token)?;
+                        pending.push(Pending::Parameters(vec![value]));
+                        pending.push(Pending::Ast);
+                        pending.push(Pending::Operators(OperPrec::DEFAULTZERO));
+                        break;
+                    }
+                    check_token(&current_token, Token::RIGHTPAREN)?;
+                    next_token(&mut current_token)?;
+                    // A name in parentesis followed by an arrow is the parameter of a lambda
+                    if current_token == Token::ARROW {
+                        next_token(&mut current_token)?;
+                        pending.push(Pending::Lambda(parameter_names(&[value])?));
+                        pending.push(Pending::Ast);
+                        pending.push(Pending::Operators(OperPrec::DEFAULTZERO));
+                        break;
+                    }
+                    pending.push(Pending::Group);
+                    pending.push(Pending::Postfix);
+                }
+                Some(Pending::Group) => {
+                    if current_token == Token::LEFTPAREN {
+                        pending.push(Pending::ImplicitMultiply(value));
+                        pending.push(Pending::Operators(OperPrec::MULDIV));
+                        break;
+                    }
+                }
+                Some(Pending::ImplicitMultiply(left)) => {
+                    value = Node::MULTIPLY(Box::new(left), Box::new(value));
+                }
+                Some(Pending::Args(name, mut args)) => {
+                    args.push(value);
+                    if current_token == Token::COMMA {
+                        next_token(&mut current_token)?;
+                        pending.push(Pending::Args(name, args));
+                        pending.push(Pending::Ast);
+                        pending.push(Pending::Operators(OperPrec::DEFAULTZERO));
+                        break;
+                    }
+                    check_token(&current_token, Token::RIGHTPAREN)?;
+                    next_token(&mut current_token)?;
+                    value = Node::FUNCTION(name, args);
+                    pending.push(Pending::Postfix);
+                }
+                Some(Pending::Items(mut items)) => {
+                    items.push(value);
+                    if current_token == Token::COMMA {
+                        next_token(&mut current_token)?;
+                        pending.push(Pending::Items(items));
+                        pending.push(Pending::Ast);
+                        pending.push(Pending::Operators(OperPrec::DEFAULTZERO));
+                        break;
+                    }
+                    check_token(&current_token, Token::RIGHTBRACKET)?;
+                    next_token(&mut current_token)?;
+                    value = Node::LIST(items);
+                    pending.push(Pending::Postfix);
+                }
+                Some(Pending::Parameters(mut items)) => {
+                    items.push(value);
+                    if current_token == Token::COMMA {
+                        next_token(&mut current_token)?;
+                        pending.push(Pending::Parameters(items));
+                        pending.push(Pending::Ast);
+                        pending.push(Pending::Operators(OperPrec::DEFAULTZERO));
+                        break;
+                    }
+                    check_token(&current_token, Token::RIGHTPAREN)?;
+                    next_token(&mut current_token)?;
+                    check_token(&current_token, Token::ARROW)?;
+                    let parameters = parameter_names(&items)?;
+                    next_token(&mut current_token)?;
+                    pending.push(Pending::Lambda(parameters));
+                    pending.push(Pending::Ast);
+                    pending.push(Pending::Operators(OperPrec::DEFAULTZERO));
+                    break;
+                }
+                Some(Pending::Lambda(parameters)) => value = Node::LAMBDA(parameters, Box::new(value)),
+            }
+        }
+    }
+}
+
+// Checks the token expected after an expression, like the parenthesis that closes a group
+fn check_token(current_token: &Token, expected: Token) -> Result<(), ParseError> {
+    if *current_token == expected {
+        Ok(())
+    } else {
+        Err(ParseError::InvalidOperator(format!(
+            "Expected {:?}, got {:?}",
+            expected, current_token
+        )))
+    }
+}
+
+/// Evaluates an AST without recursion, the values of the children are kept in a stack
+/// # Arguments
+/// * `expr: &Node` - the node to evaluate
+/// * `variables: &HashMap<String, f64>` - the values of the variables by name
+/// # Returns
+/// * `Result<f64, EvalError>` - the same value or error than `ast::eval_with` for an AST that
+///   only holds numbers, the lambdas, lists, quantities and dates aren't numbers and the
+///   functions that bind variables like `sum` aren't supported
+/// # Example
+/// ```
+/// let ast = iterative::parse(&("-".repeat(100_001) + "2")).unwrap();
+/// let value = iterative::eval_with(&ast, &HashMap::new());
+/// // This will be Ok(-2.0)
+/// ```
+pub fn eval_with(expr: &Node, variables: &HashMap<String, f64>) -> Result<f64, EvalError> {
+    use super::ast::Node::*;
+    // A node is visited to push its children and applied when their values are in the stack. A
+    // conditional waits for its condition to visit a single branch
+    enum Task<'a> {
+        Visit(&'a Node),
+        Apply(&'a Node),
+        Branch(&'a Node, &'a Node),
+    }
+    let mut tasks = vec![Task::Visit(expr)];
+    let mut values: Vec<f64> = Vec::new();
+    while let Some(task) = tasks.pop() {
+        match task {
+            Task::Visit(NUMBER(value)) => values.push(*value),
+            Task::Visit(ERROR) => return Err(EvalError::SyntaxError),
+            // The stack only holds numbers
+            Task::Visit(LAMBDA(..)) => return Err(not_a_number(FUNCTION_TYPE)),
+            Task::Visit(LIST(..)) | Task::Visit(INDEX(..)) | Task::Visit(RANGE(..)) => {
+                return Err(not_a_number(LIST_TYPE))
+            }
+            Task::Visit(QUANTITY(..)) | Task::Visit(CONVERT(..)) => return Err(not_a_number(QUANTITY_TYPE)),
+            Task::Visit(DATE(_)) => return Err(not_a_number(DATE_TYPE)),
+            Task::Visit(VARIABLE(name)) => match variables.get(name).copied().or_else(|| constants::builtin(name)) {
+                Some(value) => values.push(value),
+                None => return Err(EvalError::UnknownVariable(name.clone())),
+            },
+            Task::Visit(FUNCTION(name, args)) if name == CONDITIONAL_NAME => {
+                if args.len() != 3 {
+                    return Err(EvalError::WrongArgumentCount(name.clone(), 3, args.len()));
+                }
+                tasks.push(Task::Branch(&args[1], &args[2]));
+                tasks.push(Task::Visit(&args[0]));
+            }
+            // The bodies of sum, prod, fold and map bind their own variables, like the i of
+            // sum(i, 1, 3, i), so they can't be evaluated as numbers
+            Task::Visit(FUNCTION(name, _)) if HigherOrder::from_name(name).is_some() => {
+                return Err(EvalError::UnsupportedFunction(name.clone()))
+            }
+            Task::Branch(then, otherwise) => {
+                let condition = values.pop().unwrap();
+                tasks.push(Task::Visit(if condition != 0.0 { then } else { otherwise }));
+            }
+            Task::Visit(node) => {
+                tasks.push(Task::Apply(node));
+                // The children are evaluated from left to right, like the recursive evaluator
+                tasks.extend(node.children().into_iter().rev().map(Task::Visit));
+            }
+            Task::Apply(FUNCTION(name, args)) => {
+                let start = values.len() - args.len();
+                let value = functions::call(name, &values[start..])?;
+                values.truncate(start);
+                values.push(value);
+            }
+            Task::Apply(NEGATIVE(_)) => {
+                let top = values.last_mut().unwrap();
+                *top = -*top;
+            }
+            Task::Apply(PERCENT(_)) => {
+                let top = values.last_mut().unwrap();
+                *top /= 100.0;
+            }
+            Task::Apply(node) => {
+                let right = values.pop().unwrap();
+                let left = values.last_mut().unwrap();
+                *left = match node {
+                    ADD(..) => *left + right,
+                    SUBTRACT(..) => *left - right,
+                    MULTIPLY(..) => *left * right,
+                    DIVIDE(..) => *left / right,
+                    _ => left.powf(right),
+                };
+            }
+        }
+    }
+    Ok(values.pop().unwrap())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_iterative_deep_negations() {
+        let ast = parse(&("-".repeat(100_001) + "2")).unwrap();
+        assert_eq!(eval_with(&ast, &HashMap::new()), Ok(-2.0));
+    }
+
+    #[test]
+    fn test_iterative_deep_parens() {
+        let expr = "(".repeat(100_000) + "1+x" + &")".repeat(100_000) + "*2";
+        let ast = parse(&expr).unwrap();
+        let mut variables = HashMap::new();
+        variables.insert("x".to_string(), 2.0);
+        assert_eq!(eval_with(&ast, &variables), Ok(6.0));
+    }
+
+    #[test]
+    fn test_iterative_long_chain() {
+        let expr = vec!["1"; 200_000].join("+");
+        let ast = parse(&expr).unwrap();
+        assert_eq!(eval_with(&ast, &HashMap::new()), Ok(200_000.0));
+    }
+
+    #[test]
+    fn test_iterative_deep_calls() {
+        let expr = "max(1,".repeat(50_000) + "7" + &")".repeat(50_000);
+        let ast = parse(&expr).unwrap();
+        assert_eq!(eval_with(&ast, &HashMap::new()), Ok(7.0));
+        assert!(parse(&("sqrt(".repeat(1000) + "4" + &")".repeat(999))).is_err());
+    }
+
+    #[test]
+    fn test_iterative_conditional() {
+        // The branch that isn't taken isn't evaluated, z is unknown
+        let ast = parse("if(1, 2, z) + if(0, z, 3)").unwrap();
+        assert_eq!(eval_with(&ast, &HashMap::new()), Ok(5.0));
+        let ast = parse("if(z, 1, 2)").unwrap();
+        assert_eq!(eval_with(&ast, &HashMap::new()), Err(EvalError::UnknownVariable("z".into())));
+        let ast = parse("if(1, 2)").unwrap();
+        assert_eq!(eval_with(&ast, &HashMap::new()), Err(EvalError::WrongArgumentCount("if".into(), 3, 2)));
+    }
+
+    #[test]
+    fn test_iterative_higher_order() {
+        // The i of the body is bound by sum, it isn't an unknown variable
+        let ast = parse("1 + sum(i, 1, 3, i)").unwrap();
+        assert_eq!(eval_with(&ast, &HashMap::new()), Err(EvalError::UnsupportedFunction("sum".into())));
+        let ast = parse("fold((a, i) -> a + i, 0, 1, 3)").unwrap();
+        assert_eq!(eval_with(&ast, &HashMap::new()), Err(EvalError::UnsupportedFunction("fold".into())));
+    }
+}
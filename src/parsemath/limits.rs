@@ -0,0 +1,231 @@
+//! This module holds the **resource limits** used to parse and evaluate untrusted input. The
+//! parser and the evaluator are recursive, so an input like `((((...1))))` or `----...1` with
+//! thousands of levels would overflow the stack, the limits stop them before with an error. A
+//! chain like `1+1+...+1` doesn't nest the parser, but its AST is as deep as the number of terms
+//! for the functions that walk the AST, so the depth of the AST has its own limit
+
+use std::error;
+use std::fmt;
+
+/// The Limits struct holds the maximum resources that a formula can use
+/// # Arguments
+/// * `max_input_length: usize` - the maximum number of bytes of the input
+/// * `max_depth: usize` - the maximum nesting of the input, every parenthesis, bracket, call,
+///   sign or operator with a higher precedence than the one before it adds a level
+/// * `max_tree_depth: usize` - the maximum depth of the AST and of the evaluation, a number
+///   alone has depth 1 and every node above it adds a level, so `1+1+1` has depth 3
+/// * `max_nodes: usize` - the maximum number of nodes of the AST
+/// * `max_call_depth: usize` - the maximum nesting of the calls to user functions and lambdas
+/// * `max_eval_steps: usize` - the maximum number of nodes evaluated
+/// # Example
+/// ```
+/// let limits = Limits {
+///     max_depth: 64,
+///     ..Limits::default()
+/// };
+/// let parsed = Parser::with_limits("((((((1))))))", limits).unwrap().parse();
+/// // This will be Ok(NUMBER(1.0)), seven levels are below the limit
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limits {
+    pub max_input_length: usize,
+    pub max_depth: usize,
+    pub max_tree_depth: usize,
+    pub max_nodes: usize,
+    pub max_call_depth: usize,
+    pub max_eval_steps: usize,
+}
+
+impl Limits {
+    /// Returns limits that never stop the parser or the evaluator, only for trusted input
+    pub fn unlimited() -> Self {
+        Limits {
+            max_input_length: usize::MAX,
+            max_depth: usize::MAX,
+            max_tree_depth: usize::MAX,
+            max_nodes: usize::MAX,
+            max_call_depth: usize::MAX,
+            max_eval_steps: usize::MAX,
+        }
+    }
+}
+
+// The default nesting is low enough for the 2 MiB stack of a spawned thread in a debug build,
+// where every level of the parser takes a few KiB. A level of the AST takes less to print or
+// evaluate, the default tree depth allows chains of a thousand terms. A call to a user function
+// takes more, so the recursions have a lower limit
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_input_length: 64 * 1024,
+            max_depth: 256,
+            max_tree_depth: 1024,
+            max_nodes: 100_000,
+            max_call_depth: 64,
+            max_eval_steps: 1_000_000,
+        }
+    }
+}
+
+/// Limit error enum contains the limit that was exceeded with its maximum value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LimitError {
+    InputTooLong(usize),
+    TooDeep(usize),
+    TooManyNodes(usize),
+    TooManySteps(usize),
+    TooDeepRecursion(usize),
+}
+
+// The trait for display with format! or println!
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self {
+            self::LimitError::InputTooLong(max) => write!(f, "The input is longer than {} bytes", max),
+            self::LimitError::TooDeep(max) => write!(f, "The expression is nested deeper than {} levels", max),
+            self::LimitError::TooManyNodes(max) => write!(f, "The expression has more than {} nodes", max),
+            self::LimitError::TooManySteps(max) => write!(f, "The evaluation takes more than {} steps", max),
+            self::LimitError::TooDeepRecursion(max) => write!(f, "The function calls are nested deeper than {} levels", max),
+        }
+    }
+}
+
+impl error::Error for LimitError {}
+
+// Counts the steps and the nested calls of an evaluation against the limits
+#[derive(Debug)]
+pub(crate) struct Budget {
+    limits: Limits,
+    steps: usize,
+    calls: usize,
+}
+
+impl Budget {
+    pub(crate) fn new(limits: Limits) -> Self {
+        Budget { limits, steps: 0, calls: 0 }
+    }
+
+    // Enters the body of a user function or a lambda, every call must be left after its body
+    pub(crate) fn enter_call(&mut self) -> Result<(), LimitError> {
+        self.calls += 1;
+        if self.calls > self.limits.max_call_depth {
+            return Err(LimitError::TooDeepRecursion(self.limits.max_call_depth));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn leave_call(&mut self) {
+        self.calls -= 1;
+    }
+
+    // Takes a step to evaluate a node at the given depth
+    pub(crate) fn step(&mut self, depth: usize) -> Result<(), LimitError> {
+        if depth > self.limits.max_tree_depth {
+            return Err(LimitError::TooDeep(self.limits.max_tree_depth));
+        }
+        self.steps += 1;
+        if self.steps > self.limits.max_eval_steps {
+            return Err(LimitError::TooManySteps(self.limits.max_eval_steps));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsemath::{
+        ast::{self, EvalError, Node},
+        parser::{ParseError, Parser},
+        value::Value,
+    };
+    use std::collections::HashMap;
+
+    fn parse_error(expr: &str, limits: Limits) -> LimitError {
+        let parsed = Parser::with_limits(expr, limits).and_then(|mut parser| parser.parse());
+        match parsed {
+            Err(ParseError::LimitExceeded(error)) => error,
+            other => panic!("Expected a limit error, got {:?}", other),
+        }
+    }
+
+    fn no_length_limit() -> Limits {
+        Limits {
+            max_input_length: usize::MAX,
+            ..Limits::default()
+        }
+    }
+
+    #[test]
+    fn test_limits_input_length() {
+        let expr = "1+".repeat(40_000) + "1";
+        assert_eq!(parse_error(&expr, Limits::default()), LimitError::InputTooLong(64 * 1024));
+    }
+
+    #[test]
+    fn test_limits_nested_parens() {
+        let expr = "(".repeat(100_000) + "1" + &")".repeat(100_000);
+        assert_eq!(parse_error(&expr, no_length_limit()), LimitError::TooDeep(256));
+        // Below the limit the parentheses are accepted
+        let expr = "(".repeat(200) + "1" + &")".repeat(200);
+        assert_eq!(Parser::new(&expr).unwrap().parse().unwrap(), Node::NUMBER(1.0));
+    }
+
+    #[test]
+    fn test_limits_negations() {
+        let expr = "-".repeat(100_000) + "1";
+        assert_eq!(parse_error(&expr, no_length_limit()), LimitError::TooDeep(256));
+    }
+
+    #[test]
+    fn test_limits_long_chain() {
+        // The chain doesn't nest the parser but gives a deep AST
+        let expr = "1+".repeat(100_000) + "1";
+        assert_eq!(parse_error(&expr, no_length_limit()), LimitError::TooDeep(1024));
+        let limits = Limits {
+            max_depth: 2,
+            ..Limits::default()
+        };
+        let expr = vec!["1"; 1000].join("+");
+        assert!(Parser::with_limits(&expr, limits).unwrap().parse().is_ok());
+        let expr = vec!["1"; 1025].join("+");
+        assert_eq!(parse_error(&expr, limits), LimitError::TooDeep(1024));
+        let expr = "1*".repeat(10_000) + "1";
+        let limits = Limits {
+            max_nodes: 5_000,
+            ..Limits::unlimited()
+        };
+        assert_eq!(parse_error(&expr, limits), LimitError::TooManyNodes(5_000));
+    }
+
+    #[test]
+    fn test_limits_malformed_numbers() {
+        // A malformed number is an error of the parse, not a panic
+        for expr in ["1.2.3", "2²", "1+2..3.4.5"].iter() {
+            assert!(Parser::new(expr).and_then(|mut parser| parser.parse()).is_err(), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn test_limits_eval_steps() {
+        let ast = Parser::new("max(1,2)+sqrt(4)*3").unwrap().parse().unwrap();
+        let limits = Limits {
+            max_eval_steps: 5,
+            ..Limits::default()
+        };
+        let error = ast::eval_limited(&ast, &HashMap::new(), limits).unwrap_err();
+        assert_eq!(error, EvalError::LimitExceeded(LimitError::TooManySteps(5)));
+        assert_eq!(ast::eval_limited(&ast, &HashMap::new(), Limits::default()), Ok(Value::NUMBER(8.0)));
+    }
+
+    #[test]
+    fn test_limits_eval_depth() {
+        // A tree built by hand isn't checked by the parser
+        let mut ast = Node::NUMBER(1.0);
+        for _ in 0..2_000 {
+            ast = Node::NEGATIVE(Box::new(ast));
+        }
+        let error = ast::eval_limited(&ast, &HashMap::new(), Limits::default()).unwrap_err();
+        assert_eq!(error.to_string(), "The expression is nested deeper than 1024 levels");
+    }
+}
@@ -0,0 +1,363 @@
+//! This module holds the **matrices** used by the evaluation. A list of lists with the same
+//! length like `[[1, 2], [3, 4]]` evaluates to a matrix, `*` between two matrices is the matrix
+//! product and `^` an integer power. The linear algebra functions check the shapes of their
+//! operands and fail with an `EvalError` that names the operation and the shapes
+
+use std::fmt;
+
+use super::ast::EvalError;
+use super::value::INTEGER_TYPE;
+
+/// The Matrix struct holds a matrix of numbers stored row by row
+/// # Example
+/// ```
+/// let matrix = Matrix::from_rows(vec![vec![1.0, 2.0], vec![3.0, 4.0]]).unwrap();
+/// let determinant = matrix.determinant();
+/// // This will be Ok(-2.0)
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix {
+    rows: usize,
+    columns: usize,
+    data: Vec<f64>,
+}
+
+impl Matrix {
+    /// Returns a matrix from its rows
+    /// # Arguments
+    /// * `rows: Vec<Vec<f64>>` - the rows, all with the same number of elements
+    /// # Returns
+    /// * `Result<Matrix, EvalError>` - the matrix or `EvalError::LengthMismatch` with the lengths
+    ///   of the first row and of the first row that is different
+    pub fn from_rows(rows: Vec<Vec<f64>>) -> Result<Matrix, EvalError> {
+        let columns = rows.first().map_or(0, Vec::len);
+        let mut data = Vec::with_capacity(rows.len() * columns);
+        for row in &rows {
+            if row.len() != columns {
+                return Err(EvalError::LengthMismatch(columns, row.len()));
+            }
+            data.extend_from_slice(row);
+        }
+        Ok(Matrix {
+            rows: rows.len(),
+            columns,
+            data,
+        })
+    }
+
+    /// Returns a matrix with a single column, the way a list is used by the matrix product and
+    /// by `solve`
+    pub fn column(values: &[f64]) -> Matrix {
+        Matrix {
+            rows: values.len(),
+            columns: 1,
+            data: values.to_vec(),
+        }
+    }
+
+    /// Returns the identity matrix of the given size
+    pub fn identity(size: usize) -> Matrix {
+        let mut data = vec![0.0; size * size];
+        for i in 0..size {
+            data[i * size + i] = 1.0;
+        }
+        Matrix {
+            rows: size,
+            columns: size,
+            data,
+        }
+    }
+
+    /// Returns the number of rows and columns
+    pub fn shape(&self) -> (usize, usize) {
+        (self.rows, self.columns)
+    }
+
+    /// Returns the elements of a row
+    /// # Arguments
+    /// * `row: usize` - the position of the row from zero, it must be lower than the rows
+    pub fn row(&self, row: usize) -> &[f64] {
+        &self.data[row * self.columns..(row + 1) * self.columns]
+    }
+
+    /// Returns the elements of the matrix row by row
+    pub fn data(&self) -> &[f64] {
+        &self.data
+    }
+
+    fn get(&self, row: usize, column: usize) -> f64 {
+        self.data[row * self.columns + column]
+    }
+
+    /// Applies a function to every element
+    pub fn map(mut self, function: impl Fn(f64) -> f64) -> Matrix {
+        self.data.iter_mut().for_each(|element| *element = function(*element));
+        self
+    }
+
+    /// Combines the elements in the same position of two matrices with the same shape
+    /// # Arguments
+    /// * `other: &Matrix` - the right operand
+    /// * `operation: &str` - the name of the operation for the error, like `add`
+    /// * `function: fn(f64, f64) -> f64` - the operation on two elements
+    /// # Returns
+    /// * `Result<Matrix, EvalError>` - the result or `EvalError::ShapeMismatch`
+    pub fn zip(mut self, other: &Matrix, operation: &str, function: fn(f64, f64) -> f64) -> Result<Matrix, EvalError> {
+        if self.shape() != other.shape() {
+            return Err(shape_mismatch(operation, &self, other));
+        }
+        self.data.iter_mut().zip(&other.data).for_each(|(element, other)| *element = function(*element, *other));
+        Ok(self)
+    }
+
+    /// Returns the transpose, the rows become the columns
+    pub fn transpose(&self) -> Matrix {
+        let mut data = Vec::with_capacity(self.data.len());
+        for column in 0..self.columns {
+            data.extend((0..self.rows).map(|row| self.get(row, column)));
+        }
+        Matrix {
+            rows: self.columns,
+            columns: self.rows,
+            data,
+        }
+    }
+
+    /// Returns the matrix product
+    /// # Arguments
+    /// * `other: &Matrix` - the right factor, it must have as many rows as this matrix columns
+    /// # Returns
+    /// * `Result<Matrix, EvalError>` - the product or `EvalError::ShapeMismatch`
+    /// # Example
+    /// ```
+    /// let a = Matrix::from_rows(vec![vec![1.0, 2.0]]).unwrap();
+    /// let product = a.multiply(&Matrix::column(&[3.0, 4.0]));
+    /// // This will be the 1x1 matrix [[11]]
+    /// ```
+    pub fn multiply(&self, other: &Matrix) -> Result<Matrix, EvalError> {
+        if self.columns != other.rows {
+            return Err(shape_mismatch("multiply", self, other));
+        }
+        let mut data = vec![0.0; self.rows * other.columns];
+        for row in 0..self.rows {
+            for k in 0..self.columns {
+                let factor = self.get(row, k);
+                for column in 0..other.columns {
+                    data[row * other.columns + column] += factor * other.get(k, column);
+                }
+            }
+        }
+        Ok(Matrix {
+            rows: self.rows,
+            columns: other.columns,
+            data,
+        })
+    }
+
+    /// Returns the matrix raised to an integer power, a negative power is a power of the inverse
+    /// # Arguments
+    /// * `exponent: f64` - the power, it must be a whole number
+    /// # Returns
+    /// * `Result<Matrix, EvalError>` - the power, `EvalError::TypeMismatch` if the power isn't a
+    ///   whole number, `EvalError::NotSquare` or `EvalError::SingularMatrix` for a negative power
+    ///   of a singular matrix
+    pub fn power(&self, exponent: f64) -> Result<Matrix, EvalError> {
+        self.check_square("power")?;
+        if exponent.fract() != 0.0 || !exponent.is_finite() {
+            return Err(EvalError::TypeMismatch(INTEGER_TYPE.to_string(), exponent.to_string()));
+        }
+        let mut base = if exponent < 0.0 { self.inverse()? } else { self.clone() };
+        let mut exponent = exponent.abs();
+        // We multiply by the squares of the base for every bit of the exponent
+        let mut result = Matrix::identity(self.rows);
+        while exponent >= 1.0 {
+            if exponent % 2.0 == 1.0 {
+                result = result.multiply(&base)?;
+            }
+            exponent = (exponent / 2.0).floor();
+            if exponent >= 1.0 {
+                base = base.multiply(&base)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Returns the determinant of a square matrix, computed by elimination with partial pivoting
+    /// # Returns
+    /// * `Result<f64, EvalError>` - the determinant or `EvalError::NotSquare`
+    pub fn determinant(&self) -> Result<f64, EvalError> {
+        self.check_square("determinant")?;
+        let size = self.rows;
+        let mut a = self.clone();
+        let mut determinant = 1.0;
+        for column in 0..size {
+            let pivot = a.pivot_row(column);
+            if a.get(pivot, column) == 0.0 {
+                return Ok(0.0);
+            }
+            if pivot != column {
+                a.swap_rows(pivot, column);
+                determinant = -determinant;
+            }
+            let value = a.get(column, column);
+            determinant *= value;
+            for row in column + 1..size {
+                let factor = a.get(row, column) / value;
+                a.subtract_row(row, column, factor);
+            }
+        }
+        Ok(determinant)
+    }
+
+    /// Returns the inverse of a square matrix
+    /// # Returns
+    /// * `Result<Matrix, EvalError>` - the inverse, `EvalError::NotSquare` or
+    ///   `EvalError::SingularMatrix` if the matrix has no inverse
+    pub fn inverse(&self) -> Result<Matrix, EvalError> {
+        self.check_square("inverse")?;
+        self.solve(&Matrix::identity(self.rows))
+    }
+
+    /// Solves the linear system `A x = b` where this matrix is `A`
+    /// # Arguments
+    /// * `b: &Matrix` - the right side, a column for a single system or a column per system
+    /// # Returns
+    /// * `Result<Matrix, EvalError>` - the solutions with the shape of `b`,
+    ///   `EvalError::NotSquare`, `EvalError::ShapeMismatch` if `b` doesn't have a row per row
+    ///   of `A` or `EvalError::SingularMatrix` if the system has no single solution
+    /// # Example
+    /// ```
+    /// let a = Matrix::from_rows(vec![vec![2.0, 0.0], vec![0.0, 4.0]]).unwrap();
+    /// let x = a.solve(&Matrix::column(&[2.0, 2.0]));
+    /// // This will be the column [1, 0.5]
+    /// ```
+    pub fn solve(&self, b: &Matrix) -> Result<Matrix, EvalError> {
+        self.check_square("solve")?;
+        if b.rows != self.rows {
+            return Err(shape_mismatch("solve", self, b));
+        }
+        let size = self.rows;
+        // The pivots smaller than the tolerance are zeros left by the rounding errors
+        let scale = self.data.iter().fold(0.0_f64, |max, element| max.max(element.abs()));
+        let tolerance = scale * size as f64 * f64::EPSILON;
+        let (mut a, mut x) = (self.clone(), b.clone());
+        // Gauss-Jordan elimination, every column of a is reduced to a column of the identity
+        for column in 0..size {
+            let pivot = a.pivot_row(column);
+            if a.get(pivot, column).abs() <= tolerance {
+                return Err(EvalError::SingularMatrix);
+            }
+            a.swap_rows(pivot, column);
+            x.swap_rows(pivot, column);
+            let value = a.get(column, column);
+            a.scale_row(column, 1.0 / value);
+            x.scale_row(column, 1.0 / value);
+            for row in (0..size).filter(|row| *row != column) {
+                let factor = a.get(row, column);
+                a.subtract_row(row, column, factor);
+                x.subtract_row(row, column, factor);
+            }
+        }
+        Ok(x)
+    }
+
+    fn check_square(&self, operation: &str) -> Result<(), EvalError> {
+        if self.rows != self.columns {
+            return Err(EvalError::NotSquare(operation.to_string(), self.shape()));
+        }
+        Ok(())
+    }
+
+    // Returns the row from the given column down with the largest element in the column
+    fn pivot_row(&self, column: usize) -> usize {
+        (column..self.rows)
+            .max_by(|a, b| self.get(*a, column).abs().total_cmp(&self.get(*b, column).abs()))
+            .unwrap_or(column)
+    }
+
+    fn swap_rows(&mut self, first: usize, second: usize) {
+        for column in 0..self.columns {
+            self.data.swap(first * self.columns + column, second * self.columns + column);
+        }
+    }
+
+    fn scale_row(&mut self, row: usize, factor: f64) {
+        for column in 0..self.columns {
+            self.data[row * self.columns + column] *= factor;
+        }
+    }
+
+    // Subtracts the source row times the factor from the row
+    fn subtract_row(&mut self, row: usize, source: usize, factor: f64) {
+        for column in 0..self.columns {
+            let value = self.get(source, column);
+            self.data[row * self.columns + column] -= factor * value;
+        }
+    }
+}
+
+fn shape_mismatch(operation: &str, left: &Matrix, right: &Matrix) -> EvalError {
+    EvalError::ShapeMismatch(operation.to_string(), left.shape(), right.shape())
+}
+
+// The trait for display with format! or println!, a matrix is written like a list of rows
+impl fmt::Display for Matrix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[")?;
+        for row in 0..self.rows {
+            if row > 0 {
+                write!(f, ",")?;
+            }
+            let elements: Vec<String> = self.row(row).iter().map(|element| element.to_string()).collect();
+            write!(f, "[{}]", elements.join(","))?;
+        }
+        write!(f, "]")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn matrix(rows: &[&[f64]]) -> Matrix {
+        Matrix::from_rows(rows.iter().map(|row| row.to_vec()).collect()).unwrap()
+    }
+
+    #[test]
+    fn test_matrix_product_and_transpose() {
+        let a = matrix(&[&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]]);
+        assert_eq!(a.transpose(), matrix(&[&[1.0, 4.0], &[2.0, 5.0], &[3.0, 6.0]]));
+        assert_eq!(a.multiply(&a.transpose()).unwrap(), matrix(&[&[14.0, 32.0], &[32.0, 77.0]]));
+        let error = a.multiply(&a).unwrap_err();
+        assert_eq!(error.to_string(), "Cannot multiply a 2x3 matrix and a 2x3 matrix");
+        let ragged = Matrix::from_rows(vec![vec![1.0, 2.0], vec![3.0]]).unwrap_err();
+        assert_eq!(ragged, EvalError::LengthMismatch(2, 1));
+        assert_eq!(a.to_string(), "[[1,2,3],[4,5,6]]");
+    }
+
+    #[test]
+    fn test_matrix_determinant_and_inverse() {
+        let a = matrix(&[&[0.0, 2.0], &[1.0, 3.0]]);
+        assert_eq!(a.determinant(), Ok(-2.0));
+        assert_eq!(a.inverse().unwrap(), matrix(&[&[-1.5, 1.0], &[0.5, 0.0]]));
+        let singular = matrix(&[&[1.0, 2.0], &[2.0, 4.0]]);
+        assert_eq!(singular.determinant(), Ok(0.0));
+        assert_eq!(singular.inverse(), Err(EvalError::SingularMatrix));
+        let error = matrix(&[&[1.0, 2.0]]).determinant().unwrap_err();
+        assert_eq!(error.to_string(), "The determinant needs a square matrix but found a 1x2 matrix");
+    }
+
+    #[test]
+    fn test_matrix_solve_and_power() {
+        let a = matrix(&[&[2.0, 1.0], &[1.0, 3.0]]);
+        assert_eq!(a.solve(&Matrix::column(&[4.0, 7.0])).unwrap(), Matrix::column(&[1.0, 2.0]));
+        let error = a.solve(&Matrix::column(&[1.0])).unwrap_err();
+        assert_eq!(error.to_string(), "Cannot solve a 2x2 matrix and a 1x1 matrix");
+        let fibonacci = matrix(&[&[1.0, 1.0], &[1.0, 0.0]]);
+        assert_eq!(fibonacci.power(10.0).unwrap(), matrix(&[&[89.0, 55.0], &[55.0, 34.0]]));
+        assert_eq!(fibonacci.power(0.0).unwrap(), Matrix::identity(2));
+        assert_eq!(fibonacci.power(-1.0).unwrap(), matrix(&[&[0.0, 1.0], &[1.0, -1.0]]));
+        let error = fibonacci.power(0.5).unwrap_err();
+        assert_eq!(error.to_string(), "Expected an integer but found 0.5");
+    }
+}
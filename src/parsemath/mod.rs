@@ -1,4 +1,26 @@
+pub mod arena;
 pub mod ast;
+pub mod batch;
+pub mod closure;
+pub mod constants;
+pub mod cse;
+pub mod datetime;
+pub mod export;
+pub mod functions;
+pub mod iterative;
+pub mod limits;
+pub mod matrix;
 pub mod parser;
+pub mod pratt;
+pub mod printer;
+pub mod program;
+pub mod rpn;
+#[cfg(feature = "serde")]
+pub mod serialization;
+pub mod sexpr;
 pub mod token;
-pub mod tokenizer;
\ No newline at end of file
+pub mod tokenizer;
+pub mod units;
+pub mod value;
+pub mod visualize;
+pub mod vm;
\ No newline at end of file
@@ -1,7 +1,12 @@
 use super::{
+    arena::{Arena, NodeId},
     ast::Node, 
+    datetime::DateTime,
+    functions,
+    limits::{LimitError, Limits},
+    program::{Statement, UserFunction},
     token::{Token, OperPrec}, 
-    tokenizer::Tokenizer
+    tokenizer::{Tokenizer, PERCENT_KEYWORD}
 };
 
 use std::{convert::From};
@@ -11,11 +16,23 @@ use std::fmt;
 pub struct Parser<'a> {
     tokenizer: Tokenizer<'a>,
     current_token: Token,
+    limits: Limits,
+    // The nested calls of the parser and the nodes created, to check the limits
+    nesting: usize,
+    nodes: usize,
+    // The byte offset of the current token
+    position: usize,
+    // In recovery mode the errors are kept and the parse goes on
+    recovering: bool,
+    diagnostics: Vec<Diagnostic>,
+    // In business mode a percentage added to a value is a percentage of that value
+    business: bool,
 }
 
 /// Parse error enum contains all the parse errors and display them with the `Display` trait 
 /// # Arguments
 /// * `message: String` - is the error message to display when this is formatted as string
+/// * `limit: LimitError` - the resource limit exceeded by the input
 /// # Example
 /// ```
 /// // We can take the unable to parse enum with an string and display them
@@ -36,6 +53,7 @@ pub struct Parser<'a> {
 pub enum ParseError {
     UnableToParse(String),
     InvalidOperator(String),
+    LimitExceeded(LimitError),
 }
 
 // The trait for display with format! or println!
@@ -44,14 +62,32 @@ impl fmt::Display for ParseError {
         match &self {
             self::ParseError::UnableToParse(e) => write!(f, "Error in evaluating {}", e),
             self::ParseError::InvalidOperator(e) => write!(f, "Error in evaluating {}", e),
+            self::ParseError::LimitExceeded(e) => write!(f, "Error in evaluating {}", e),
         }
     }
 }
 
+/// The Diagnostic struct holds an error found by the parser in recovery mode
+/// # Arguments
+/// * `error: ParseError` - the error, the first one is the same error that `parse` returns
+/// * `position: usize` - the byte offset in the input of the token where the error was found
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub error: ParseError,
+    pub position: usize,
+}
+
+// The trait for display with format! or println!
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.error, self.position)
+    }
+}
+
 // Convert from boxed to an error enum variant
 impl From<std::boxed::Box<dyn std::error::Error>> for ParseError {
     fn from(_evalerr: std::boxed::Box<dyn std::error::Error>) -> Self {
-        return ParseError::UnableToParse("Unable to parse".into());
+        ParseError::UnableToParse("Unable to parse".into())
     }
 }
 
@@ -67,19 +103,80 @@ impl<'a> Parser<'a> {
     /// let add = Paser::new("2+2");
     /// ```
     pub fn new(expr: &'a str) -> Result<Self, ParseError> {
-        // We create a new lexer instance
-        let mut lexer = Tokenizer::new(expr);
-        let curr_token = match lexer.next() {
-            Some(token) => token,
-            // If there is an invalid character
-            None => return Err(ParseError::InvalidOperator("Invalid character".into()))
-        };
+        Parser::with_limits(expr, Limits::default())
+    }
+
+    /// Returns a new parse instance that checks the given limits, the input is rejected if it's too
+    /// long and the parse fails with `ParseError::LimitExceeded` if the AST is too deep or too big
+    /// # Arguments
+    /// * `expr: 'a str` - An string representing an arithmetic expression
+    /// * `limits: Limits` - the limits of the input, `Parser::new` uses `Limits::default()`
+    /// # Returns
+    /// * `Result<Parser, ParseError>` - Returns an parser instance `Ok(parser)` or an error `Err(err)`
+    /// # Example
+    /// ```
+    /// let limits = Limits { max_tree_depth: 2, ..Limits::default() };
+    /// let parsed = Parser::with_limits("1+2*3", limits).unwrap().parse();
+    /// // This will be Err(ParseError::LimitExceeded(LimitError::TooDeep(2)))
+    /// ```
+    pub fn with_limits(expr: &'a str, limits: Limits) -> Result<Self, ParseError> {
+        Parser::create(expr, Tokenizer::new(expr), limits, false)
+    }
+
+    /// Returns a new parse instance in recovery mode, for the `parse_recovering` method. The
+    /// invalid characters are reported instead of failing, only a too long input is rejected
+    /// # Arguments
+    /// * `expr: 'a str` - An string representing an arithmetic expression
+    /// * `limits: Limits` - the limits of the input
+    /// # Returns
+    /// * `Result<Parser, ParseError>` - Returns an parser instance `Ok(parser)` or an error `Err(err)`
+    pub fn with_recovery(expr: &'a str, limits: Limits) -> Result<Self, ParseError> {
+        Parser::create(expr, Tokenizer::new(expr), limits, true)
+    }
+
+    /// Returns a new parse instance in business mode, where a percentage added to or subtracted
+    /// from a value is a percentage of that value like in a business calculator, `a + b%` is
+    /// `a*(1+b/100)`. It's parsed as `a*(100+b)/100`, that keeps the exact result when the
+    /// numbers are integers. Anywhere else a percentage is the hundredth part of its operand
+    /// # Arguments
+    /// * `expr: 'a str` - An string representing an arithmetic expression
+    /// * `limits: Limits` - the limits of the input
+    /// # Returns
+    /// * `Result<Parser, ParseError>` - Returns an parser instance `Ok(parser)` or an error `Err(err)`
+    /// # Example
+    /// ```
+    /// let ast = Parser::with_business_mode("200 + 15%", Limits::default()).unwrap().parse();
+    /// // This will be 200*(100+15)/100, it evaluates to 230 while Parser::new gives 200.15
+    /// ```
+    pub fn with_business_mode(expr: &'a str, limits: Limits) -> Result<Self, ParseError> {
+        let mut parser = Parser::create(expr, Tokenizer::new(expr), limits, false)?;
+        parser.business = true;
+        Ok(parser)
+    }
 
-        // We set the curr_token and the lexer
-        Ok(Parser {
+    // Returns a new parse instance for the statements of a program
+    pub(crate) fn for_program(source: &'a str, limits: Limits) -> Result<Self, ParseError> {
+        Parser::create(source, Tokenizer::for_program(source), limits, false)
+    }
+
+    fn create(expr: &'a str, lexer: Tokenizer<'a>, limits: Limits, recovering: bool) -> Result<Self, ParseError> {
+        if expr.len() > limits.max_input_length {
+            return Err(ParseError::LimitExceeded(LimitError::InputTooLong(limits.max_input_length)));
+        }
+        let mut parser = Parser {
             tokenizer: lexer,
-            current_token: curr_token,
-        })
+            current_token: Token::EOF,
+            limits,
+            nesting: 0,
+            nodes: 0,
+            position: 0,
+            recovering,
+            diagnostics: Vec::new(),
+            business: false,
+        };
+        // We read the first token, if there is an invalid character we return an error
+        parser.get_next_token()?;
+        Ok(parser)
     }
 
     /// Parse function is the responsible of parse the input `&str` and returns an ast
@@ -95,39 +192,180 @@ impl<'a> Parser<'a> {
     /// // ADD(Box::new(NUMBER(1.0)), Box::new(NUMBER(2.0)))
     /// ```
     pub fn parse(&mut self) -> Result<Node, ParseError> {
-        // We generate the ast
-        let ast = self.generate_ast(OperPrec::DEFAULTZERO);
-        // And handle a possible error
-        match ast {
-            Ok(ast) => Ok(ast),
-            Err(e) => Err(e)
+        // We generate the ast with boxed nodes
+        let (ast, _) = self.generate_ast(&mut BoxedBuilder, OperPrec::DEFAULTZERO)?;
+        Ok(ast)
+    }
+
+    /// Parses the input into an arena instead of boxed nodes, the nodes are added to the arena
+    /// as they are parsed so the boxed tree is never built
+    /// # Arguments
+    /// * `arena: &mut Arena` - the arena that receives the nodes, it can already hold other trees
+    /// # Returns
+    /// * `Result<NodeId, ParseError>` - the id of the root node or an error
+    /// # Example
+    /// ```
+    /// let mut arena = Arena::new();
+    /// let root = Parser::new("1+2").unwrap().parse_into(&mut arena).unwrap();
+    /// // arena.get(root) will be ArenaNode::ADD(left, right)
+    /// ```
+    pub fn parse_into(&mut self, arena: &mut Arena) -> Result<NodeId, ParseError> {
+        let (root, _) = self.generate_ast(arena, OperPrec::DEFAULTZERO)?;
+        Ok(root)
+    }
+
+    /// Parses the whole input without stopping at the first error. A missing operand is replaced
+    /// by a `Node::ERROR` placeholder, the tokens that can't be used are skipped until the next
+    /// operator, comma or parenthesis, and every error is reported with its position. Unlike
+    /// `parse` the tokens after the expression are reported too
+    /// # Returns
+    /// * `(Node, Vec<Diagnostic>)` - the best-effort AST and the errors in the order of the input,
+    ///   the AST has no placeholders when there are no errors. An exceeded limit stops the parse,
+    ///   it is the last diagnostic and the AST is a placeholder
+    /// # Example
+    /// ```
+    /// let mut parser = Parser::with_recovery("(1+*2) + max(3 4, 5", Limits::default()).unwrap();
+    /// let (ast, diagnostics) = parser.parse_recovering();
+    /// // The AST will be 1+?*2+max(3,5) with 3 diagnostics: the operand missing at position 3
+    /// // and the parenthesis expected at 15 and at the end
+    /// ```
+    pub fn parse_recovering(&mut self) -> (Node, Vec<Diagnostic>) {
+        self.recovering = true;
+        let ast = match self.parse_all(&mut BoxedBuilder) {
+            Ok(ast) => ast,
+            Err(error) => {
+                let position = self.position;
+                self.diagnostics.push(Diagnostic { error, position });
+                Node::ERROR
+            }
+        };
+        (ast, std::mem::take(&mut self.diagnostics))
+    }
+
+    // Parses the statements of a program, every statement ends with a semicolon or the end
+    pub(crate) fn parse_statements(&mut self) -> Result<Vec<Statement>, ParseError> {
+        let mut statements = Vec::new();
+        loop {
+            // The empty statements are skipped
+            while self.current_token == Token::SEMICOLON {
+                self.get_next_token()?;
+            }
+            if self.current_token == Token::EOF {
+                return Ok(statements);
+            }
+            let (expr, _) = self.generate_ast(&mut BoxedBuilder, OperPrec::DEFAULTZERO)?;
+            let statement = if self.current_token == Token::ASSIGN {
+                // The left side of an assignment is parsed as an expression, it must be a name
+                // or a call with the names of the parameters for a function definition
+                self.get_next_token()?;
+                let (value, _) = self.generate_ast(&mut BoxedBuilder, OperPrec::DEFAULTZERO)?;
+                match &expr {
+                    Node::VARIABLE(name) => Statement::ASSIGN(name.clone(), value),
+                    Node::FUNCTION(name, args) => {
+                        let parameters = definition_parameters(name, args)?;
+                        Statement::DEFINE(name.clone(), UserFunction { parameters, body: value })
+                    }
+                    _ => return Err(ParseError::UnableToParse(format!("Cannot assign to {}", expr))),
+                }
+            } else {
+                Statement::EXPRESSION(expr)
+            };
+            statements.push(statement);
+            if self.current_token != Token::SEMICOLON && self.current_token != Token::EOF {
+                return Err(self.unexpected(Token::SEMICOLON));
+            }
         }
     }
 
+    fn parse_all<B: NodeBuilder>(&mut self, builder: &mut B) -> Result<B::Output, ParseError> {
+        let mut ast = self.generate_ast(builder, OperPrec::DEFAULTZERO)?;
+        // The tokens left are reported, the operators after them continue the expression
+        while self.current_token != Token::EOF {
+            self.recover(ParseError::UnableToParse(format!("Unexpected {:?}", self.current_token)))?;
+            self.get_next_token()?;
+            while OperPrec::DEFAULTZERO < self.current_token.get_oper_prec() {
+                ast = self.convert_to_node(builder, ast)?;
+            }
+        }
+        Ok(ast.0)
+    }
+
     fn get_next_token(&mut self) -> Result<(), ParseError> {
-        // We advance to the next token
-        let next_token = match self.tokenizer.next() {
-            Some(token) => token,
-            None => return Err(ParseError::InvalidOperator("Invalid character".into()))
-        };
+        // We advance to the next token, in recovery mode the invalid characters are skipped
+        loop {
+            match self.tokenizer.next() {
+                Some(token) => {
+                    self.current_token = token;
+                    self.position = self.tokenizer.position();
+                    return Ok(());
+                }
+                None => {
+                    self.position = self.tokenizer.position();
+                    self.recover(self.tokenizer.error())?;
+                }
+            }
+        }
+    }
 
-        self.current_token = next_token;
+    // Returns the error in the usual mode, in recovery mode keeps it and the parse goes on
+    fn recover(&mut self, error: ParseError) -> Result<(), ParseError> {
+        if !self.recovering {
+            return Err(error);
+        }
+        let position = self.position;
+        self.diagnostics.push(Diagnostic { error, position });
         Ok(())
     }
 
+    fn unexpected(&self, expected: Token) -> ParseError {
+        ParseError::InvalidOperator(format!("Expected {:?}, got {:?}", expected, self.current_token))
+    }
+
     fn check_paren(&mut self, expected: Token) -> Result<(), ParseError> {
         // We chek if the partentesis are missmatched
         // And if there is missmatched we return an error
+        if expected == self.current_token {
+            return self.get_next_token();
+        }
+        self.recover(self.unexpected(expected.clone()))?;
+        // In recovery mode we skip the tokens until the parenthesis
+        self.synchronize()?;
         if expected == self.current_token {
             self.get_next_token()?;
-            Ok(())
-        } else {
-            Err(ParseError::InvalidOperator(
-                format!("Expected {:?}, got {:?}", expected, self.current_token)))
         }
+        Ok(())
     }
 
-    fn parse_number(&mut self) -> Result<Node, ParseError> {
+    // Skips the tokens until a comma, a closing parenthesis or bracket or the end that isn't
+    // inside the skipped parentheses or brackets
+    fn synchronize(&mut self) -> Result<(), ParseError> {
+        let mut open = 0;
+        loop {
+            match self.current_token {
+                Token::EOF => return Ok(()),
+                Token::COMMA | Token::RIGHTPAREN | Token::RIGHTBRACKET if open == 0 => return Ok(()),
+                Token::LEFTPAREN | Token::LEFTBRACKET => open += 1,
+                Token::RIGHTPAREN | Token::RIGHTBRACKET => open -= 1,
+                _ => (),
+            }
+            self.get_next_token()?;
+        }
+    }
+
+    // Counts a new node with the given depth, the parse stops as soon as a limit is exceeded
+    fn add_node(&mut self, depth: usize) -> Result<usize, ParseError> {
+        self.nodes += 1;
+        if self.nodes > self.limits.max_nodes {
+            return Err(ParseError::LimitExceeded(LimitError::TooManyNodes(self.limits.max_nodes)));
+        }
+        if depth > self.limits.max_tree_depth {
+            return Err(ParseError::LimitExceeded(LimitError::TooDeep(self.limits.max_tree_depth)));
+        }
+        Ok(depth)
+    }
+
+    // The nodes are returned with the depth of their subtree
+    fn parse_number<B: NodeBuilder>(&mut self, builder: &mut B) -> Result<(B::Output, usize), ParseError> {
         // We clone the current token in the instance
         let token = self.current_token.clone();
 
@@ -137,101 +375,478 @@ impl<'a> Parser<'a> {
                 // We advance the token to get the number token
                 self.get_next_token()?;
                 // We generate the negative number operator token
-                let expr = self.generate_ast(OperPrec::NEGATIVE)?;
+                let (expr, depth) = self.generate_ast(builder, OperPrec::NEGATIVE)?;
                 // And return the Ok with the value
-                Ok(Node::NEGATIVE(Box::new(expr)))
+                let depth = self.add_node(depth + 1)?;
+                Ok((builder.negative(expr), depth))
             },
 
             Token::NUM(i) => {
                 // If the token is a number we advance and return
                 self.get_next_token()?;
-                Ok(Node::NUMBER(i))
+                let depth = self.add_node(1)?;
+                // A unit after the number makes a quantity like 3 km
+                let number = match &self.current_token {
+                    Token::UNIT(unit) => {
+                        let unit = unit.clone();
+                        self.get_next_token()?;
+                        builder.quantity(i, unit)
+                    }
+                    _ => builder.number(i),
+                };
+                self.parse_postfix(builder, (number, depth))
             },
 
+            Token::DATE(date) => self.parse_date(builder, date),
+
             Token::LEFTPAREN => {
                 // If the token is a left parentesis
                 self.get_next_token()?;
                 // We generate a default zero token 
-                let expr = self.generate_ast(OperPrec::DEFAULTZERO)?;
+                let (expr, depth) = self.generate_ast(builder, OperPrec::DEFAULTZERO)?;
+                // A comma inside the parentesis separates the parameters of a lambda
+                if self.current_token == Token::COMMA {
+                    return self.parse_lambda_parameters(builder, expr);
+                }
                 // Check for mismatched parentesis
                 self.check_paren(Token::RIGHTPAREN)?;
+                // A name in parentesis followed by an arrow is the parameter of a lambda
+                if self.current_token == Token::ARROW {
+                    let parameters = self.lambda_parameters(builder, &[expr])?;
+                    return self.parse_lambda(builder, parameters);
+                }
+                let (expr, depth) = self.parse_postfix(builder, (expr, depth))?;
                 // If the current token is a left parent
                 if self.current_token == Token::LEFTPAREN {
                     // We generate the node with a multiply and division precedence
-                    let right = self.generate_ast(OperPrec::MULDIV)?;
+                    let (right, right_depth) = self.generate_ast(builder, OperPrec::MULDIV)?;
                     // and we return de expression node
-                    return Ok(Node::MULTIPLY(Box::new(expr), Box::new(right)));
+                    let depth = self.add_node(depth.max(right_depth) + 1)?;
+                    return Ok((builder.binary(&Token::MULTIPLY, expr, right), depth));
                 }
                 // We return the expression node
-                Ok(expr)
+                Ok((expr, depth))
+            },
+            Token::IDENT(name) => {
+                // If the token is a name followed by parentesis is a call, if not is a variable
+                self.get_next_token()?;
+                // A name followed by an arrow is the parameter of a lambda
+                if self.current_token == Token::ARROW {
+                    return self.parse_lambda(builder, vec![name]);
+                }
+                if self.current_token != Token::LEFTPAREN {
+                    let depth = self.add_node(1)?;
+                    let variable = builder.variable(name);
+                    return self.parse_postfix(builder, (variable, depth));
+                }
+                let (args, depth) = self.parse_args(builder)?;
+                let depth = self.add_node(depth + 1)?;
+                let call = builder.function(name, args);
+                self.parse_postfix(builder, (call, depth))
+            },
+            Token::LEFTBRACKET => {
+                // A list is a sequence of expressions separated by commas inside brackets
+                let (items, depth) = self.parse_items(builder, Token::LEFTBRACKET, Token::RIGHTBRACKET)?;
+                let depth = self.add_node(depth + 1)?;
+                let list = builder.list(items);
+                self.parse_postfix(builder, (list, depth))
             },
             // If the value is unexpected we return an error
-            _ => Err(ParseError::UnableToParse("Unable to Parse".to_string()))
+            _ => {
+                self.recover(ParseError::UnableToParse("Unable to Parse".to_string()))?;
+                // In recovery mode the missing operand is a placeholder and the token is left
+                // to the operator loop or the parenthesis that expects it
+                let depth = self.add_node(1)?;
+                Ok((builder.error(), depth))
+            }
+        }
+    }
+
+    // Parses the indexes and the percent signs after an operand like v[0][1] or 15%, every
+    // index takes the element of the value at its left
+    fn parse_postfix<B: NodeBuilder>(&mut self, builder: &mut B, mut expr: (B::Output, usize)) -> Result<(B::Output, usize), ParseError> {
+        loop {
+            match self.current_token {
+                Token::LEFTBRACKET => {
+                    self.get_next_token()?;
+                    let (index, index_depth) = self.generate_ast(builder, OperPrec::DEFAULTZERO)?;
+                    self.check_paren(Token::RIGHTBRACKET)?;
+                    let (list, depth) = expr;
+                    let depth = self.add_node(depth.max(index_depth) + 1)?;
+                    expr = (builder.index(list, index), depth);
+                }
+                Token::PERCENT => {
+                    self.get_next_token()?;
+                    let (value, depth) = expr;
+                    let depth = self.add_node(depth + 1)?;
+                    expr = (builder.percent(value), depth);
+                    if matches!(&self.current_token, Token::IDENT(name) if name == PERCENT_KEYWORD) {
+                        return self.parse_percent_of(builder, expr);
+                    }
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    // Parses the value after the keyword of a percentage like 50% of 80, that is the product
+    // of the percentage and the value
+    fn parse_percent_of<B: NodeBuilder>(&mut self, builder: &mut B, percent: (B::Output, usize)) -> Result<(B::Output, usize), ParseError> {
+        self.get_next_token()?;
+        let (value, value_depth) = self.generate_ast(builder, OperPrec::MULDIV)?;
+        let (percent, depth) = percent;
+        let depth = self.add_node(depth.max(value_depth) + 1)?;
+        Ok((builder.binary(&Token::MULTIPLY, percent, value), depth))
+    }
+
+    // Parses the upper bound of a range, the current token are the dots. The bounds can't be
+    // ranges, so 1..2..3 is an error
+    fn parse_range<B: NodeBuilder>(&mut self, builder: &mut B, from: (B::Output, usize)) -> Result<(B::Output, usize), ParseError> {
+        self.get_next_token()?;
+        let (to, to_depth) = self.generate_operation(builder, OperPrec::DEFAULTZERO)?;
+        if self.current_token == Token::RANGE {
+            self.recover(ParseError::UnableToParse("The bound of a range can't be a range".to_string()))?;
+            // In recovery mode the extra bound is skipped
+            self.get_next_token()?;
+            self.generate_operation(builder, OperPrec::DEFAULTZERO)?;
+        }
+        let (from, from_depth) = from;
+        let depth = self.add_node(from_depth.max(to_depth) + 1)?;
+        Ok((builder.binary(&Token::RANGE, from, to), depth))
+    }
+
+    // Parses the rest of the parameters of a lambda like (x, y) -> x*y, the current token is the
+    // comma after the first one
+    fn parse_lambda_parameters<B: NodeBuilder>(&mut self, builder: &mut B, first: B::Output) -> Result<(B::Output, usize), ParseError> {
+        let mut items = vec![first];
+        while self.current_token == Token::COMMA {
+            self.get_next_token()?;
+            let (item, _) = self.generate_ast(builder, OperPrec::DEFAULTZERO)?;
+            items.push(item);
+        }
+        self.check_paren(Token::RIGHTPAREN)?;
+        if self.current_token != Token::ARROW {
+            self.recover(self.unexpected(Token::ARROW))?;
+            let depth = self.add_node(1)?;
+            return Ok((builder.error(), depth));
+        }
+        let parameters = self.lambda_parameters(builder, &items)?;
+        self.parse_lambda(builder, parameters)
+    }
+
+    // Returns the names of the parameters of a lambda, they were parsed as expressions
+    fn lambda_parameters<B: NodeBuilder>(&mut self, builder: &B, items: &[B::Output]) -> Result<Vec<String>, ParseError> {
+        let mut parameters: Vec<String> = Vec::with_capacity(items.len());
+        for item in items {
+            match builder.variable_name(item) {
+                Some(name) if !parameters.contains(&name) => parameters.push(name),
+                // In recovery mode the lambda keeps the valid parameters
+                _ => self.recover(ParseError::UnableToParse(
+                    "The parameters of a lambda must be different names".to_string(),
+                ))?,
+            }
+        }
+        Ok(parameters)
+    }
+
+    // Parses the body of a lambda, the current token is the arrow. The body takes everything at
+    // its right, like an operator with the lowest precedence
+    fn parse_lambda<B: NodeBuilder>(&mut self, builder: &mut B, parameters: Vec<String>) -> Result<(B::Output, usize), ParseError> {
+        self.get_next_token()?;
+        let (body, depth) = self.generate_ast(builder, OperPrec::DEFAULTZERO)?;
+        let depth = self.add_node(depth + 1)?;
+        Ok((builder.lambda(parameters, body), depth))
+    }
+
+    fn parse_args<B: NodeBuilder>(&mut self, builder: &mut B) -> Result<(Vec<B::Output>, usize), ParseError> {
+        // The arguments are a list of expressions separated by commas inside parentesis
+        self.parse_items(builder, Token::LEFTPAREN, Token::RIGHTPAREN)
+    }
+
+    // Parses the expressions separated by commas between the opening and the closing token
+    fn parse_items<B: NodeBuilder>(&mut self, builder: &mut B, open: Token, close: Token) -> Result<(Vec<B::Output>, usize), ParseError> {
+        self.check_paren(open)?;
+        let mut args = Vec::new();
+        let mut depth = 0;
+        if self.current_token == close {
+            self.get_next_token()?;
+            return Ok((args, depth));
         }
+        loop {
+            let (arg, arg_depth) = self.generate_ast(builder, OperPrec::DEFAULTZERO)?;
+            args.push(arg);
+            depth = depth.max(arg_depth);
+            match &self.current_token {
+                Token::COMMA => self.get_next_token()?,
+                token if *token == close => {
+                    self.get_next_token()?;
+                    return Ok((args, depth));
+                }
+                _ => {
+                    self.recover(self.unexpected(close.clone()))?;
+                    // In recovery mode we skip the rest of the argument, the call goes on if
+                    // there is another one
+                    self.synchronize()?;
+                    match &self.current_token {
+                        Token::COMMA => self.get_next_token()?,
+                        token if *token == close => {
+                            self.get_next_token()?;
+                            return Ok((args, depth));
+                        }
+                        _ => return Ok((args, depth)),
+                    }
+                }
+            }
+        }
+    }
+
+    fn generate_ast<B: NodeBuilder>(&mut self, builder: &mut B, oper_prec: OperPrec) -> Result<(B::Output, usize), ParseError> {
+        let mut left_expr = self.generate_operation(builder, oper_prec)?;
+        if oper_prec != OperPrec::DEFAULTZERO {
+            return Ok(left_expr);
+        }
+        // A range binds looser than any operator, its bounds are whole expressions
+        if self.current_token == Token::RANGE {
+            left_expr = self.parse_range(builder, left_expr)?;
+        }
+        // A conversion applies to everything at its left, like a range
+        while self.current_token == Token::IN {
+            left_expr = self.parse_conversion(builder, left_expr)?;
+        }
+        Ok(left_expr)
+    }
+
+    // Parses a date, the current token. It has its own function because every arm of
+    // parse_number makes its stack frame bigger
+    fn parse_date<B: NodeBuilder>(&mut self, builder: &mut B, date: DateTime) -> Result<(B::Output, usize), ParseError> {
+        self.get_next_token()?;
+        let depth = self.add_node(1)?;
+        Ok((builder.date(date), depth))
+    }
+
+    // Parses the unit of a conversion like x in km, the current token is the keyword
+    fn parse_conversion<B: NodeBuilder>(&mut self, builder: &mut B, expr: (B::Output, usize)) -> Result<(B::Output, usize), ParseError> {
+        self.get_next_token()?;
+        let (expr, depth) = expr;
+        let unit = match &self.current_token {
+            Token::UNIT(unit) => unit.clone(),
+            _ => {
+                self.recover(ParseError::UnableToParse("Expected a unit after in".to_string()))?;
+                // In recovery mode the conversion is left out
+                return Ok((expr, depth));
+            }
+        };
+        self.get_next_token()?;
+        let depth = self.add_node(depth + 1)?;
+        Ok((builder.convert(expr, unit), depth))
     }
 
-    fn generate_ast(&mut self, oper_prec: OperPrec) -> Result<Node, ParseError> {
+    fn generate_operation<B: NodeBuilder>(&mut self, builder: &mut B, oper_prec: OperPrec) -> Result<(B::Output, usize), ParseError> {
+        // Every nested call uses the stack, so we stop before it overflows
+        self.nesting += 1;
+        if self.nesting > self.limits.max_depth {
+            return Err(ParseError::LimitExceeded(LimitError::TooDeep(self.limits.max_depth)));
+        }
         // To generate the ast we parse the fisrt number for the left side of the expression
-        let mut left_expr = self.parse_number()?;
+        let mut left_expr = self.parse_number(builder)?;
         // We check if the operation precedence is lowest
         while oper_prec < self.current_token.get_oper_prec() {
             if self.current_token == Token::EOF {
                 break
             }
-            let right_expr = self.convert_to_node(left_expr.clone())?;
-            left_expr = right_expr;
+            // The left expression is moved into the new node, so the tree isn't copied
+            left_expr = self.convert_to_node(builder, left_expr)?;
         }
+        self.nesting -= 1;
         // And we return a node for the left expression
         Ok(left_expr)
     }
 
-    fn convert_to_node(&mut self, left_expr: Node) -> Result<Node, ParseError> {
+    fn convert_to_node<B: NodeBuilder>(&mut self, builder: &mut B, left_expr: (B::Output, usize)) -> Result<(B::Output, usize), ParseError> {
         // Here we convert the tokens to nodes
         // is the same for all tokens
-        match self.current_token {
-            Token::ADD => {
-                // We Advance
-                self.get_next_token()?;
-                // We get the right side expression
-                let right_expr = self.generate_ast(OperPrec::ADDSUB)?;
-                // We return an operation node
-                Ok(Node::ADD(Box::new(left_expr), Box::new(right_expr)))
+        let oper_prec = match self.current_token {
+            Token::ADD | Token::SUBTRACT => OperPrec::ADDSUB,
+            Token::MULTIPLY | Token::DIVIDE => OperPrec::MULDIV,
+            Token::CARET => OperPrec::POWER,
+            _ => {
+                return Err(ParseError::InvalidOperator(format!(
+                    "Please enter valid operator {:?}",
+                    self.current_token
+                )))
             },
+        };
+        let operator = self.current_token.clone();
+        // We Advance
+        self.get_next_token()?;
+        // We get the right side expression
+        let (right_expr, right_depth) = self.generate_ast(builder, oper_prec)?;
+        // We return an operation node
+        let (left_expr, left_depth) = left_expr;
+        let percentage = match oper_prec {
+            OperPrec::ADDSUB if self.business => builder.percent_operand(&right_expr),
+            _ => None,
+        };
+        if let Some(percentage) = percentage {
+            // In business mode a + b% is a*(100+b)/100, the value plus a percentage of itself
+            let hundred = builder.number(100.0);
+            self.add_node(1)?;
+            // The sum takes the place of the percentage, so it has the same depth
+            let rate = builder.binary(&operator, hundred, percentage);
+            let rate_depth = self.add_node(right_depth)?;
+            let product = builder.binary(&Token::MULTIPLY, left_expr, rate);
+            let product_depth = self.add_node(left_depth.max(rate_depth) + 1)?;
+            let hundred = builder.number(100.0);
+            self.add_node(1)?;
+            let depth = self.add_node(product_depth + 1)?;
+            return Ok((builder.binary(&Token::DIVIDE, product, hundred), depth));
+        }
+        let depth = self.add_node(left_depth.max(right_depth) + 1)?;
+        Ok((builder.binary(&operator, left_expr, right_expr), depth))
+    }
+}
 
-            Token::SUBTRACT => {
-                self.get_next_token()?;
+// Returns the parameters of a function definition, they must be different names
+fn definition_parameters(name: &str, args: &[Node]) -> Result<Vec<String>, ParseError> {
+    if functions::is_reserved(name) {
+        return Err(ParseError::UnableToParse(format!("Cannot redefine the built-in function {}", name)));
+    }
+    let mut parameters: Vec<String> = Vec::with_capacity(args.len());
+    for arg in args {
+        match arg {
+            Node::VARIABLE(parameter) if !parameters.contains(parameter) => parameters.push(parameter.clone()),
+            _ => {
+                return Err(ParseError::UnableToParse(format!(
+                    "Invalid parameter {} of the function {}",
+                    arg, name
+                )))
+            }
+        }
+    }
+    Ok(parameters)
+}
 
-                let right_expr = self.generate_ast(OperPrec::ADDSUB)?;
-                Ok(Node::SUBTRACT(Box::new(left_expr), Box::new(right_expr)))
-            },
+// Returns the names of the parameters of a lambda parsed by the parsers without builder, like
+// `lambda_parameters` they must be different names
+pub(crate) fn parameter_names(items: &[Node]) -> Result<Vec<String>, ParseError> {
+    let mut parameters: Vec<String> = Vec::with_capacity(items.len());
+    for item in items {
+        match item {
+            Node::VARIABLE(name) if !parameters.contains(name) => parameters.push(name.clone()),
+            _ => {
+                return Err(ParseError::UnableToParse(
+                    "The parameters of a lambda must be different names".to_string(),
+                ))
+            }
+        }
+    }
+    Ok(parameters)
+}
 
-            Token::MULTIPLY => {
-                self.get_next_token()?;
+/// The NodeBuilder trait creates the nodes for the parser, so the same parser can produce boxed
+/// nodes or nodes inside an arena
+pub(crate) trait NodeBuilder {
+    type Output;
 
-                let right_expr = self.generate_ast(OperPrec::MULDIV)?;
-                Ok(Node::MULTIPLY(Box::new(left_expr), Box::new(right_expr)))
-            },
+    fn number(&mut self, value: f64) -> Self::Output;
+    fn variable(&mut self, name: String) -> Self::Output;
+    fn negative(&mut self, expr: Self::Output) -> Self::Output;
+    fn function(&mut self, name: String, args: Vec<Self::Output>) -> Self::Output;
+    // The operator is one of the tokens with an operator precedence or the dots of a range
+    fn binary(&mut self, operator: &Token, left: Self::Output, right: Self::Output) -> Self::Output;
+    // A placeholder for a part that couldn't be parsed, in recovery mode
+    fn error(&mut self) -> Self::Output;
+    fn lambda(&mut self, parameters: Vec<String>, body: Self::Output) -> Self::Output;
+    fn list(&mut self, items: Vec<Self::Output>) -> Self::Output;
+    fn index(&mut self, list: Self::Output, index: Self::Output) -> Self::Output;
+    fn quantity(&mut self, value: f64, unit: String) -> Self::Output;
+    fn date(&mut self, date: DateTime) -> Self::Output;
+    fn convert(&mut self, expr: Self::Output, unit: String) -> Self::Output;
+    fn percent(&mut self, expr: Self::Output) -> Self::Output;
+    // In business mode a percentage at the right of an addition changes the operation, this
+    // returns the operand of a percentage
+    fn percent_operand(&self, node: &Self::Output) -> Option<Self::Output>;
+    // The parameters of a lambda are parsed as expressions, they must be variables
+    fn variable_name(&self, node: &Self::Output) -> Option<String>;
+}
 
-            Token::DIVIDE => {
-                self.get_next_token()?;
+// Builds the boxed nodes of `Node`
+struct BoxedBuilder;
 
-                let right_expr = self.generate_ast(OperPrec::MULDIV)?;
-                Ok(Node::DIVIDE(Box::new(left_expr), Box::new(right_expr)))
-            },
+impl NodeBuilder for BoxedBuilder {
+    type Output = Node;
 
-            Token::CARET => {
-                self.get_next_token()?;
+    fn number(&mut self, value: f64) -> Node {
+        Node::NUMBER(value)
+    }
 
-                let right_expr = self.generate_ast(OperPrec::POWER)?;
-                Ok(Node::CARRET(Box::new(left_expr), Box::new(right_expr)))
-            },
+    fn variable(&mut self, name: String) -> Node {
+        Node::VARIABLE(name)
+    }
 
-            _ => {
-                Err(ParseError::InvalidOperator(format!(
-                    "Please enter valid operator {:?}",
-                    self.current_token
-                )))
-            },
+    fn negative(&mut self, expr: Node) -> Node {
+        Node::NEGATIVE(Box::new(expr))
+    }
+
+    fn function(&mut self, name: String, args: Vec<Node>) -> Node {
+        Node::FUNCTION(name, args)
+    }
+
+    fn binary(&mut self, operator: &Token, left: Node, right: Node) -> Node {
+        let constructor = match operator {
+            Token::ADD => Node::ADD,
+            Token::SUBTRACT => Node::SUBTRACT,
+            Token::MULTIPLY => Node::MULTIPLY,
+            Token::DIVIDE => Node::DIVIDE,
+            Token::RANGE => Node::RANGE,
+            _ => Node::CARRET,
+        };
+        constructor(Box::new(left), Box::new(right))
+    }
+
+    fn error(&mut self) -> Node {
+        Node::ERROR
+    }
+
+    fn lambda(&mut self, parameters: Vec<String>, body: Node) -> Node {
+        Node::LAMBDA(parameters, Box::new(body))
+    }
+
+    fn list(&mut self, items: Vec<Node>) -> Node {
+        Node::LIST(items)
+    }
+
+    fn index(&mut self, list: Node, index: Node) -> Node {
+        Node::INDEX(Box::new(list), Box::new(index))
+    }
+
+    fn quantity(&mut self, value: f64, unit: String) -> Node {
+        Node::QUANTITY(value, unit)
+    }
+
+    fn date(&mut self, date: DateTime) -> Node {
+        Node::DATE(date)
+    }
+
+    fn convert(&mut self, expr: Node, unit: String) -> Node {
+        Node::CONVERT(Box::new(expr), unit)
+    }
+
+    fn percent(&mut self, expr: Node) -> Node {
+        Node::PERCENT(Box::new(expr))
+    }
+
+    fn percent_operand(&self, node: &Node) -> Option<Node> {
+        match node {
+            Node::PERCENT(expr) => Some((**expr).clone()),
+            _ => None,
+        }
+    }
+
+    fn variable_name(&self, node: &Node) -> Option<String> {
+        match node {
+            Node::VARIABLE(name) => Some(name.clone()),
+            _ => None,
         }
     }
 }
@@ -240,6 +855,7 @@ impl<'a> Parser<'a> {
 mod test {
     use super::*;
     use crate::parsemath::ast::Node::*;
+    use crate::parsemath::value::Value;
     
     #[test]
     fn test_parser_add() {
@@ -282,4 +898,168 @@ mod test {
         let expected = NEGATIVE(Box::new(NUMBER(1.0)));
         assert_eq!(parser.parse().unwrap(), expected)
     }
+
+    #[test]
+    fn test_parser_function() {
+        let mut parser = Parser::new("max(1,2*3)").unwrap();
+        let expected = FUNCTION(
+            "max".into(),
+            vec![NUMBER(1.0), MULTIPLY(Box::new(NUMBER(2.0)), Box::new(NUMBER(3.0)))],
+        );
+        assert_eq!(parser.parse().unwrap(), expected)
+    }
+
+    #[test]
+    fn test_parser_lambda() {
+        let square = LAMBDA(vec!["x".into()], Box::new(CARRET(Box::new(VARIABLE("x".into())), Box::new(NUMBER(2.0)))));
+        assert_eq!(Parser::new("x -> x^2").unwrap().parse().unwrap(), square);
+        assert_eq!(Parser::new("(x) -> x^2").unwrap().parse().unwrap(), square);
+        // The body takes everything at its right, until the comma of the call
+        let ast = Parser::new("fold((a, i) -> a + i * 2, 0, 1, 3)").unwrap().parse().unwrap();
+        match &ast {
+            FUNCTION(_, args) => assert_eq!(args[0].to_string(), "(a,i)->a+i*2"),
+            _ => panic!("Expected a call"),
+        }
+        let error = Parser::new("(x, 1) -> x").unwrap().parse().unwrap_err();
+        assert_eq!(error.to_string(), "Error in evaluating The parameters of a lambda must be different names");
+        let error = Parser::new("(x, y) + 1").unwrap().parse().unwrap_err();
+        assert_eq!(error.to_string(), "Error in evaluating Expected ARROW, got ADD");
+    }
+
+    #[test]
+    fn test_parser_list() {
+        let parse = |expr: &str| Parser::new(expr).unwrap().parse().unwrap();
+        let items = vec![NUMBER(1.0), ADD(Box::new(NUMBER(2.0)), Box::new(NUMBER(3.0)))];
+        assert_eq!(parse("[1, 2+3]"), LIST(items));
+        assert_eq!(parse("[]"), LIST(Vec::new()));
+        // The index binds tighter than the minus sign
+        let index = INDEX(Box::new(VARIABLE("v".into())), Box::new(NUMBER(0.0)));
+        assert_eq!(parse("-v[0]"), NEGATIVE(Box::new(index)));
+        assert_eq!(parse("f(x)[1][2]").to_string(), "f(x)[1][2]");
+        // The bounds of a range are whole expressions
+        let range = RANGE(Box::new(NUMBER(1.0)), Box::new(ADD(Box::new(VARIABLE("n".into())), Box::new(NUMBER(1.0)))));
+        assert_eq!(parse("1..n+1"), range);
+        assert_eq!(parse("2*(1..3)").to_string(), "2*(1..3)");
+        let error = Parser::new("1..2..3").unwrap().parse().unwrap_err();
+        assert_eq!(error.to_string(), "Error in evaluating The bound of a range can't be a range");
+        let error = Parser::new("[1, 2").unwrap().parse().unwrap_err();
+        assert_eq!(error.to_string(), "Error in evaluating Expected RIGHTBRACKET, got EOF");
+    }
+
+    #[test]
+    fn test_parser_units() {
+        let parse = |expr: &str| Parser::new(expr).unwrap().parse().unwrap();
+        let quantity = |value: f64, unit: &str| Box::new(QUANTITY(value, unit.into()));
+        assert_eq!(parse("3 m / 2 s"), DIVIDE(quantity(3.0, "m"), quantity(2.0, "s")));
+        // The conversion applies to the whole expression at its left
+        let product = MULTIPLY(quantity(5.0, "kg"), quantity(9.81, "m/s^2"));
+        assert_eq!(parse("5 kg * 9.81 m/s^2 in N"), CONVERT(Box::new(product), "N".into()));
+        let error = Parser::new("2 in 3").unwrap().parse().unwrap_err();
+        assert_eq!(error.to_string(), "Error in evaluating Expected a unit after in");
+    }
+
+    #[test]
+    fn test_parser_percent() {
+        let percent = |value: f64| Box::new(PERCENT(Box::new(NUMBER(value))));
+        let parse = |expr: &str| Parser::new(expr).unwrap().parse().unwrap();
+        assert_eq!(parse("200 + 15%"), ADD(Box::new(NUMBER(200.0)), percent(15.0)));
+        assert_eq!(parse("50% of 80 + 1"), parse("50%*80+1"));
+        assert_eq!(parse("-5%"), NEGATIVE(percent(5.0)));
+        // The business mode only changes the percentages added to or subtracted from a value
+        let business = |expr: &str| Parser::with_business_mode(expr, Limits::default()).unwrap().parse().unwrap();
+        assert_eq!(business("200 + 15%"), parse("200*(100+15)/100"));
+        assert_eq!(business("200 - 15% - 10%"), parse("200*(100-15)/100*(100-10)/100"));
+        assert_eq!(business("200 * 15% + 50% of 10"), parse("200*15%+50%*10"));
+    }
+
+    #[test]
+    fn test_parser_variable() {
+        let mut parser = Parser::new("2*x").unwrap();
+        let expected = MULTIPLY(Box::new(NUMBER(2.0)), Box::new(VARIABLE("x".into())));
+        assert_eq!(parser.parse().unwrap(), expected)
+    }
+
+    #[test]
+    fn test_parser_date_input() {
+        // Since the dates, four digits with a month and a day are a date and no longer the
+        // subtraction 2026-10-16 = 2000, with spaces it's still a subtraction
+        let ast = Parser::new("2026-10-16").unwrap().parse().unwrap();
+        assert_eq!(ast, DATE(DateTime::parse("2026-10-16").unwrap()));
+        let ast = Parser::new("2026 - 10 - 16").unwrap().parse().unwrap();
+        assert_eq!(crate::parsemath::ast::eval(&ast).unwrap(), Value::NUMBER(2000.0));
+        let error = Parser::new("2026-13-45").and_then(|mut parser| parser.parse()).unwrap_err();
+        assert_eq!(error.to_string(), "Error in evaluating 2026-13-45 is not a valid date");
+    }
+
+    #[test]
+    fn test_parser_long_chain() {
+        let expr = vec!["1"; 1000].join("+");
+        let mut parser = Parser::new(&expr).unwrap();
+        let ast = parser.parse().unwrap();
+        // The chain is grouped from the left, so the first term is the deepest
+        match &ast {
+            ADD(_, right) => assert_eq!(**right, NUMBER(1.0)),
+            _ => panic!("Expected an addition"),
+        }
+        assert_eq!(crate::parsemath::ast::eval(&ast).unwrap(), Value::NUMBER(1000.0))
+    }
+
+    #[test]
+    fn test_parser_function_missing_paren() {
+        let mut parser = Parser::new("sqrt(2").unwrap();
+        assert!(parser.parse().is_err())
+    }
+
+    fn recover(expr: &str) -> (Node, Vec<String>) {
+        let mut parser = Parser::with_recovery(expr, Limits::default()).unwrap();
+        let (ast, diagnostics) = parser.parse_recovering();
+        (ast, diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect())
+    }
+
+    #[test]
+    fn test_parser_recovery_multiple_errors() {
+        let expr = "(1+*2) + max(3 4, 5";
+        let (ast, diagnostics) = recover(expr);
+        assert_eq!(ast.to_string(), "1+?*2+max(3,5)");
+        assert_eq!(
+            diagnostics,
+            vec![
+                "Error in evaluating Unable to Parse at position 3",
+                "Error in evaluating Expected RIGHTPAREN, got NUM(4.0) at position 15",
+                "Error in evaluating Expected RIGHTPAREN, got EOF at position 19",
+            ]
+        );
+        // The first diagnostic is the error of the usual mode
+        let error = Parser::new(expr).unwrap().parse().unwrap_err();
+        assert!(diagnostics[0].starts_with(&error.to_string()));
+        let error = crate::parsemath::ast::eval(&ast).unwrap_err();
+        assert_eq!(error.to_string(), "The expression has syntax errors");
+    }
+
+    #[test]
+    fn test_parser_recovery_invalid_characters() {
+        let (ast, diagnostics) = recover("1 # + 2 $");
+        assert_eq!(ast, ADD(Box::new(NUMBER(1.0)), Box::new(NUMBER(2.0))));
+        assert_eq!(
+            diagnostics,
+            vec![
+                "Error in evaluating Invalid character at position 2",
+                "Error in evaluating Invalid character at position 8",
+            ]
+        );
+        let (ast, diagnostics) = recover("-");
+        assert_eq!(ast, NEGATIVE(Box::new(ERROR)));
+        assert_eq!(diagnostics, vec!["Error in evaluating Unable to Parse at position 1"]);
+    }
+
+    #[test]
+    fn test_parser_recovery_trailing_tokens() {
+        let (ast, diagnostics) = recover("(1+2))*3");
+        assert_eq!(ast.to_string(), "(1+2)*3");
+        assert_eq!(diagnostics, vec!["Error in evaluating Unexpected RIGHTPAREN at position 5"]);
+        // A valid input gives the same AST than the usual mode
+        let (ast, diagnostics) = recover("max(1,2*x)^-y");
+        assert_eq!(ast, Parser::new("max(1,2*x)^-y").unwrap().parse().unwrap());
+        assert!(diagnostics.is_empty());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,628 @@
+//! This module holds the **Pratt parser**, a parser driven by a table of operators that can be
+//! changed at runtime. Every operator is registered with its symbol, its binding power, its
+//! associativity and the function that builds its node, so a new operator like `a @ b` or `√x`
+//! doesn't need changes in the parser.
+//!
+//! The numbers, quantities, dates, variables, calls, parentheses, lists, indexes, ranges,
+//! conversions and lambdas are part of the parser. A parenthesized expression followed by another
+//! one and a percentage followed by `of` and a value are multiplied with the `*` operator of the
+//! table. The default table gives the same ASTs than `Parser`
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::{
+    ast::Node,
+    limits::{LimitError, Limits},
+    parser::{parameter_names, ParseError},
+    token::Token,
+    tokenizer::{Tokenizer, PERCENT_KEYWORD},
+};
+
+/// The Associativity enum tells how a chain of the same infix operator is grouped
+/// - LEFT: `1-2-3` is `(1-2)-3`
+/// - RIGHT: `2^3^2` is `2^(3^2)`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Associativity {
+    LEFT,
+    RIGHT,
+}
+
+type UnaryConstructor = Arc<dyn Fn(Node) -> Node + Send + Sync>;
+type BinaryConstructor = Arc<dyn Fn(Node, Node) -> Node + Send + Sync>;
+
+#[derive(Clone)]
+struct UnaryOperator {
+    binding_power: u8,
+    constructor: UnaryConstructor,
+}
+
+#[derive(Clone)]
+struct InfixOperator {
+    binding_power: u8,
+    associativity: Associativity,
+    constructor: BinaryConstructor,
+}
+
+impl InfixOperator {
+    // The powers are doubled so the side of an operator can bind a bit more than the other
+    fn left_power(&self) -> u16 {
+        u16::from(self.binding_power) * 2
+    }
+
+    fn right_power(&self) -> u16 {
+        match self.associativity {
+            Associativity::LEFT => self.left_power() + 1,
+            Associativity::RIGHT => self.left_power(),
+        }
+    }
+}
+
+// The symbols read by the tokenizer as their own tokens, every other symbol of the table is read
+// as a `Token::SYMBOL`
+const TOKEN_SYMBOLS: [&str; 6] = ["+", "-", "*", "/", "^", "%"];
+
+/// The OperatorTable struct holds the prefix, infix and postfix operators by symbol. A higher
+/// binding power binds tighter, the default table uses 1 for `+` and `-`, 2 for `*` and `/`,
+/// 3 for `^`, 4 for the negation and 5 for the percent sign
+/// # Example
+/// ```
+/// let mut table = OperatorTable::default();
+/// table.add_infix("@", 2, Associativity::LEFT, |left, right| {
+///     Node::FUNCTION("max".into(), vec![left, right])
+/// });
+/// let ast = PrattParser::new("1 + 2 @ 3", &table).unwrap().parse().unwrap();
+/// // This will be 1+max(2,3)
+/// ```
+#[derive(Clone)]
+pub struct OperatorTable {
+    prefix: HashMap<String, UnaryOperator>,
+    infix: HashMap<String, InfixOperator>,
+    postfix: HashMap<String, UnaryOperator>,
+}
+
+impl OperatorTable {
+    /// Returns a table without operators, only the syntax of the parser like the numbers, names,
+    /// calls and parentheses can be parsed
+    pub fn empty() -> Self {
+        OperatorTable {
+            prefix: HashMap::new(),
+            infix: HashMap::new(),
+            postfix: HashMap::new(),
+        }
+    }
+
+    /// Registers a prefix operator like the negation `-x`, replacing the prefix operator with the
+    /// same symbol
+    /// # Arguments
+    /// * `symbol: &str` - the symbol of the operator
+    /// * `binding_power: u8` - the operand takes the infix operators with a higher power
+    /// * `constructor` - builds the node from the operand
+    pub fn add_prefix(
+        &mut self,
+        symbol: &str,
+        binding_power: u8,
+        constructor: impl Fn(Node) -> Node + Send + Sync + 'static,
+    ) -> &mut Self {
+        let operator = UnaryOperator {
+            binding_power,
+            constructor: Arc::new(constructor),
+        };
+        self.prefix.insert(symbol.to_string(), operator);
+        self
+    }
+
+    /// Registers an infix operator like `a + b`, replacing the infix operator with the same symbol
+    /// # Arguments
+    /// * `symbol: &str` - the symbol of the operator
+    /// * `binding_power: u8` - the operands take the operators with a higher power, and the ones
+    ///   with the same power on the right side if the operator is right associative
+    /// * `associativity: Associativity` - how a chain of operators of this power is grouped
+    /// * `constructor` - builds the node from the left and right operands
+    pub fn add_infix(
+        &mut self,
+        symbol: &str,
+        binding_power: u8,
+        associativity: Associativity,
+        constructor: impl Fn(Node, Node) -> Node + Send + Sync + 'static,
+    ) -> &mut Self {
+        let operator = InfixOperator {
+            binding_power,
+            associativity,
+            constructor: Arc::new(constructor),
+        };
+        self.infix.insert(symbol.to_string(), operator);
+        self
+    }
+
+    /// Registers a postfix operator like a percentage `5%`, replacing the postfix operator with
+    /// the same symbol
+    /// # Arguments
+    /// * `symbol: &str` - the symbol of the operator
+    /// * `binding_power: u8` - the operator applies to an operand of an infix or prefix operator
+    ///   with a lower power, `-5%` is `-(5%)` if the power is higher than the negation
+    /// * `constructor` - builds the node from the operand
+    pub fn add_postfix(
+        &mut self,
+        symbol: &str,
+        binding_power: u8,
+        constructor: impl Fn(Node) -> Node + Send + Sync + 'static,
+    ) -> &mut Self {
+        let operator = UnaryOperator {
+            binding_power,
+            constructor: Arc::new(constructor),
+        };
+        self.postfix.insert(symbol.to_string(), operator);
+        self
+    }
+
+    // The symbols that the tokenizer must read as `Token::SYMBOL`
+    fn extra_symbols(&self) -> Vec<&str> {
+        let mut symbols: Vec<&str> = self
+            .prefix
+            .keys()
+            .chain(self.infix.keys())
+            .chain(self.postfix.keys())
+            .map(|symbol| symbol.as_str())
+            .filter(|symbol| !TOKEN_SYMBOLS.contains(symbol))
+            .collect();
+        symbols.sort_unstable();
+        symbols.dedup();
+        symbols
+    }
+}
+
+// The default table has the operators of `Parser`
+impl Default for OperatorTable {
+    fn default() -> Self {
+        let mut table = OperatorTable::empty();
+        table
+            .add_infix("+", 1, Associativity::LEFT, |left, right| {
+                Node::ADD(Box::new(left), Box::new(right))
+            })
+            .add_infix("-", 1, Associativity::LEFT, |left, right| {
+                Node::SUBTRACT(Box::new(left), Box::new(right))
+            })
+            .add_infix("*", 2, Associativity::LEFT, |left, right| {
+                Node::MULTIPLY(Box::new(left), Box::new(right))
+            })
+            .add_infix("/", 2, Associativity::LEFT, |left, right| {
+                Node::DIVIDE(Box::new(left), Box::new(right))
+            })
+            .add_infix("^", 3, Associativity::LEFT, |left, right| {
+                Node::CARRET(Box::new(left), Box::new(right))
+            })
+            .add_prefix("-", 4, |operand| Node::NEGATIVE(Box::new(operand)))
+            .add_postfix("%", 5, |operand| Node::PERCENT(Box::new(operand)));
+        table
+    }
+}
+
+// Returns the symbol of an operator token
+fn symbol_of(token: &Token) -> Option<&str> {
+    match token {
+        Token::ADD => Some("+"),
+        Token::SUBTRACT => Some("-"),
+        Token::MULTIPLY => Some("*"),
+        Token::DIVIDE => Some("/"),
+        Token::CARET => Some("^"),
+        Token::PERCENT => Some("%"),
+        Token::SYMBOL(symbol) => Some(symbol),
+        _ => None,
+    }
+}
+
+/// The PrattParser struct parses an expression with the operators of a table, it checks the
+/// same limits than `Parser`
+pub struct PrattParser<'a> {
+    table: &'a OperatorTable,
+    tokenizer: Tokenizer<'a>,
+    current_token: Token,
+    limits: Limits,
+    // The nested calls of the parser and the nodes created, to check the limits
+    nesting: usize,
+    nodes: usize,
+}
+
+impl<'a> PrattParser<'a> {
+    /// Returns a new Pratt parser with the default limits
+    /// # Arguments
+    /// * `expr: &'a str` - the expression to parse
+    /// * `table: &'a OperatorTable` - the operators that the expression can use
+    /// # Returns
+    /// * `Result<PrattParser, ParseError>` - the parser or an error if the first token is invalid
+    pub fn new(expr: &'a str, table: &'a OperatorTable) -> Result<Self, ParseError> {
+        PrattParser::with_limits(expr, table, Limits::default())
+    }
+
+    /// Returns a new Pratt parser that checks the given limits
+    /// # Arguments
+    /// * `expr: &'a str` - the expression to parse
+    /// * `table: &'a OperatorTable` - the operators that the expression can use
+    /// * `limits: Limits` - the limits of the input
+    /// # Returns
+    /// * `Result<PrattParser, ParseError>` - the parser or an error if the input is too long or
+    ///   the first token is invalid
+    pub fn with_limits(expr: &'a str, table: &'a OperatorTable, limits: Limits) -> Result<Self, ParseError> {
+        if expr.len() > limits.max_input_length {
+            return Err(ParseError::LimitExceeded(LimitError::InputTooLong(limits.max_input_length)));
+        }
+        let mut tokenizer = Tokenizer::with_symbols(expr, &table.extra_symbols());
+        let current_token = match tokenizer.next() {
+            Some(token) => token,
+            None => return Err(tokenizer.error()),
+        };
+        Ok(PrattParser {
+            table,
+            tokenizer,
+            current_token,
+            limits,
+            nesting: 0,
+            nodes: 0,
+        })
+    }
+
+    /// Parses the expression
+    /// # Returns
+    /// * `Result<Node, ParseError>` - the root of the AST or an error
+    /// # Example
+    /// ```
+    /// let table = OperatorTable::default();
+    /// let ast = PrattParser::new("1+2*3", &table).unwrap().parse().unwrap();
+    /// // This will be ADD(NUMBER(1.0), MULTIPLY(NUMBER(2.0), NUMBER(3.0)))
+    /// ```
+    pub fn parse(&mut self) -> Result<Node, ParseError> {
+        let (ast, _) = self.whole_expression()?;
+        Ok(ast)
+    }
+
+    fn get_next_token(&mut self) -> Result<(), ParseError> {
+        self.current_token = match self.tokenizer.next() {
+            Some(token) => token,
+            None => return Err(self.tokenizer.error()),
+        };
+        Ok(())
+    }
+
+    fn check_paren(&mut self, expected: Token) -> Result<(), ParseError> {
+        if expected == self.current_token {
+            self.get_next_token()
+        } else {
+            Err(ParseError::InvalidOperator(format!(
+                "Expected {:?}, got {:?}",
+                expected, self.current_token
+            )))
+        }
+    }
+
+    // Counts a new node with the given depth, the parse stops as soon as a limit is exceeded
+    fn add_node(&mut self, depth: usize) -> Result<usize, ParseError> {
+        self.nodes += 1;
+        if self.nodes > self.limits.max_nodes {
+            return Err(ParseError::LimitExceeded(LimitError::TooManyNodes(self.limits.max_nodes)));
+        }
+        if depth > self.limits.max_tree_depth {
+            return Err(ParseError::LimitExceeded(LimitError::TooDeep(self.limits.max_tree_depth)));
+        }
+        Ok(depth)
+    }
+
+    // Parses an expression with its range and its conversions, they bind looser than any
+    // operator of the table like in `Parser`
+    fn whole_expression(&mut self) -> Result<(Node, usize), ParseError> {
+        let expr = self.expression(0)?;
+        self.range_and_conversions(expr)
+    }
+
+    // Parses the range and the conversions after an expression
+    fn range_and_conversions(&mut self, mut expr: (Node, usize)) -> Result<(Node, usize), ParseError> {
+        if self.current_token == Token::RANGE {
+            self.get_next_token()?;
+            let (to, to_depth) = self.expression(0)?;
+            if self.current_token == Token::RANGE {
+                return Err(ParseError::UnableToParse("The bound of a range can't be a range".to_string()));
+            }
+            let (from, from_depth) = expr;
+            let depth = self.add_node(from_depth.max(to_depth) + 1)?;
+            expr = (Node::RANGE(Box::new(from), Box::new(to)), depth);
+        }
+        while self.current_token == Token::IN {
+            self.get_next_token()?;
+            let unit = match &self.current_token {
+                Token::UNIT(unit) => unit.clone(),
+                _ => return Err(ParseError::UnableToParse("Expected a unit after in".to_string())),
+            };
+            self.get_next_token()?;
+            let (value, depth) = expr;
+            let depth = self.add_node(depth + 1)?;
+            expr = (Node::CONVERT(Box::new(value), unit), depth);
+        }
+        Ok(expr)
+    }
+
+    // Parses the operators that bind at least with the given power, the nodes are returned with
+    // the depth of their subtree
+    fn expression(&mut self, min_power: u16) -> Result<(Node, usize), ParseError> {
+        // Every nested call uses the stack, so we stop before it overflows
+        self.nesting += 1;
+        if self.nesting > self.limits.max_depth {
+            return Err(ParseError::LimitExceeded(LimitError::TooDeep(self.limits.max_depth)));
+        }
+        let table = self.table;
+        // A date takes no index or postfix operator, like in `Parser`
+        let mut postfix = !matches!(self.current_token, Token::DATE(_));
+        let prefix = symbol_of(&self.current_token).and_then(|symbol| table.prefix.get(symbol));
+        let mut expr = match prefix {
+            Some(prefix) => {
+                self.get_next_token()?;
+                let (operand, depth) = self.expression(u16::from(prefix.binding_power) * 2 + 1)?;
+                let depth = self.add_node(depth + 1)?;
+                ((prefix.constructor)(operand), depth)
+            }
+            // A group is parsed at every nesting level, so it doesn't use the frame of primary
+            None if self.current_token == Token::LEFTPAREN => self.group(min_power)?,
+            None => self.primary()?,
+        };
+        // We take the operators after the operand while they bind enough
+        loop {
+            if postfix {
+                expr = self.postfix(expr, min_power)?;
+            }
+            let infix = match symbol_of(&self.current_token).and_then(|symbol| table.infix.get(symbol)) {
+                Some(infix) if infix.left_power() >= min_power => infix,
+                _ => break,
+            };
+            self.get_next_token()?;
+            let (right, right_depth) = self.expression(infix.right_power())?;
+            let (left, depth) = expr;
+            let depth = self.add_node(depth.max(right_depth) + 1)?;
+            expr = ((infix.constructor)(left, right), depth);
+            postfix = true;
+        }
+        self.nesting -= 1;
+        Ok(expr)
+    }
+
+    // Parses the indexes and the postfix operators that bind at least with the given power after
+    // an operand, an index binds tighter than any operator
+    fn postfix(&mut self, mut expr: (Node, usize), min_power: u16) -> Result<(Node, usize), ParseError> {
+        let table = self.table;
+        loop {
+            if self.current_token == Token::LEFTBRACKET {
+                self.get_next_token()?;
+                let (index, index_depth) = self.whole_expression()?;
+                self.check_paren(Token::RIGHTBRACKET)?;
+                let (list, depth) = expr;
+                let depth = self.add_node(depth.max(index_depth) + 1)?;
+                expr = (Node::INDEX(Box::new(list), Box::new(index)), depth);
+                continue;
+            }
+            let symbol = match symbol_of(&self.current_token) {
+                Some(symbol) => symbol,
+                None => return Ok(expr),
+            };
+            let percent = symbol == "%";
+            let postfix = match table.postfix.get(symbol) {
+                Some(postfix) if u16::from(postfix.binding_power) * 2 >= min_power => postfix,
+                _ => return Ok(expr),
+            };
+            self.get_next_token()?;
+            let (operand, depth) = expr;
+            let depth = self.add_node(depth + 1)?;
+            expr = ((postfix.constructor)(operand), depth);
+            // A percentage followed by the keyword multiplies the value at its right, like 50% of 80
+            let keyword = matches!(&self.current_token, Token::IDENT(name) if name == PERCENT_KEYWORD);
+            match table.infix.get("*") {
+                Some(multiply) if percent && keyword => {
+                    self.get_next_token()?;
+                    let (value, value_depth) = self.expression(multiply.right_power())?;
+                    let (percentage, depth) = expr;
+                    let depth = self.add_node(depth.max(value_depth) + 1)?;
+                    return Ok(((multiply.constructor)(percentage, value), depth));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<(Node, usize), ParseError> {
+        match self.current_token.clone() {
+            Token::NUM(i) => {
+                self.get_next_token()?;
+                let depth = self.add_node(1)?;
+                // A unit after the number makes a quantity like 3 km
+                if let Token::UNIT(unit) = self.current_token.clone() {
+                    self.get_next_token()?;
+                    return Ok((Node::QUANTITY(i, unit), depth));
+                }
+                Ok((Node::NUMBER(i), depth))
+            }
+            Token::DATE(date) => {
+                self.get_next_token()?;
+                Ok((Node::DATE(date), self.add_node(1)?))
+            }
+            Token::IDENT(name) => {
+                // A name followed by parentesis is a call, if not is a variable
+                self.get_next_token()?;
+                // A name followed by an arrow is the parameter of a lambda
+                if self.current_token == Token::ARROW {
+                    return self.lambda(vec![name]);
+                }
+                if self.current_token != Token::LEFTPAREN {
+                    return Ok((Node::VARIABLE(name), self.add_node(1)?));
+                }
+                let (args, depth) = self.items(Token::RIGHTPAREN)?;
+                Ok((Node::FUNCTION(name, args), self.add_node(depth + 1)?))
+            }
+            Token::LEFTBRACKET => {
+                // A list is a sequence of expressions separated by commas inside brackets
+                let (items, depth) = self.items(Token::RIGHTBRACKET)?;
+                Ok((Node::LIST(items), self.add_node(depth + 1)?))
+            }
+            _ => Err(ParseError::UnableToParse("Unable to Parse".to_string())),
+        }
+    }
+
+    // Parses a group in parentheses, the current token
+    fn group(&mut self, min_power: u16) -> Result<(Node, usize), ParseError> {
+        self.get_next_token()?;
+        let expr = self.expression(0)?;
+        let (expr, depth) = self.range_and_conversions(expr)?;
+        // A comma inside the parentesis separates the parameters of a lambda
+        if self.current_token == Token::COMMA {
+            return self.lambda_parameters(expr);
+        }
+        self.check_paren(Token::RIGHTPAREN)?;
+        // A name in parentesis followed by an arrow is the parameter of a lambda
+        if self.current_token == Token::ARROW {
+            return self.lambda(parameter_names(&[expr])?);
+        }
+        let (expr, depth) = self.postfix((expr, depth), min_power)?;
+        // A group followed by another is a multiplication, if the table has one
+        let multiply = self.table.infix.get("*");
+        match multiply {
+            Some(multiply) if self.current_token == Token::LEFTPAREN => {
+                let (right, right_depth) = self.expression(multiply.right_power())?;
+                let depth = self.add_node(depth.max(right_depth) + 1)?;
+                Ok(((multiply.constructor)(expr, right), depth))
+            }
+            _ => Ok((expr, depth)),
+        }
+    }
+
+    // Parses the expressions separated by commas after the current token until the closing
+    // one, with the depth of the deepest one
+    fn items(&mut self, close: Token) -> Result<(Vec<Node>, usize), ParseError> {
+        self.get_next_token()?;
+        let mut items = Vec::new();
+        let mut depth = 0;
+        if self.current_token == close {
+            self.get_next_token()?;
+            return Ok((items, depth));
+        }
+        loop {
+            let (item, item_depth) = self.whole_expression()?;
+            items.push(item);
+            depth = depth.max(item_depth);
+            if self.current_token != Token::COMMA {
+                self.check_paren(close)?;
+                return Ok((items, depth));
+            }
+            self.get_next_token()?;
+        }
+    }
+
+    // Parses the rest of the parameters of a lambda like (x, y) -> x*y and its body, the current
+    // token is the comma after the first one
+    fn lambda_parameters(&mut self, first: Node) -> Result<(Node, usize), ParseError> {
+        let mut items = vec![first];
+        while self.current_token == Token::COMMA {
+            self.get_next_token()?;
+            let (item, _) = self.whole_expression()?;
+            items.push(item);
+        }
+        self.check_paren(Token::RIGHTPAREN)?;
+        if self.current_token != Token::ARROW {
+            return Err(ParseError::InvalidOperator(format!(
+                "Expected {:?}, got {:?}",
+                Token::ARROW,
+                self.current_token
+            )));
+        }
+        self.lambda(parameter_names(&items)?)
+    }
+
+    // Parses the body of a lambda, the current token is the arrow. The body takes everything at
+    // its right, like in `Parser`
+    fn lambda(&mut self, parameters: Vec<String>) -> Result<(Node, usize), ParseError> {
+        self.get_next_token()?;
+        let (body, depth) = self.whole_expression()?;
+        let depth = self.add_node(depth + 1)?;
+        Ok((Node::LAMBDA(parameters, Box::new(body)), depth))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsemath::{ast, parser::Parser, value::Value};
+    use std::collections::HashMap;
+
+    fn pratt(expr: &str, table: &OperatorTable) -> Node {
+        PrattParser::new(expr, table).unwrap().parse().unwrap()
+    }
+
+    #[test]
+    fn test_pratt_default_table() {
+        let table = OperatorTable::default();
+        let exprs = [
+            "1+2*3-4/5",
+            "2^3^2",
+            "-2^2",
+            "1--1",
+            "(1+2)(3+4)^2",
+            "-(2)(3)",
+            "max(x,-sqrt(y))*2",
+            "2**3",
+            "-5%^2+x%",
+        ];
+        for expr in exprs.iter() {
+            let expected = Parser::new(expr).unwrap().parse().unwrap();
+            assert_eq!(pratt(expr, &table), expected, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn test_pratt_custom_operators() {
+        let mut table = OperatorTable::default();
+        table
+            .add_infix("@", 2, Associativity::LEFT, |left, right| {
+                Node::FUNCTION("max".into(), vec![left, right])
+            })
+            .add_infix("±", 1, Associativity::LEFT, |left, right| {
+                Node::FUNCTION("plusminus".into(), vec![left, right])
+            })
+            .add_postfix("%", 5, |operand| {
+                Node::DIVIDE(Box::new(operand), Box::new(Node::NUMBER(100.0)))
+            })
+            .add_prefix("√", 4, |operand| Node::FUNCTION("sqrt".into(), vec![operand]));
+        let ast = pratt("1 + 2 @ 3*4", &table);
+        assert_eq!(ast, Parser::new("1+max(2,3)*4").unwrap().parse().unwrap());
+        assert_eq!(ast::eval(&pratt("-50% + √16", &table)).unwrap(), Value::NUMBER(3.5));
+        assert_eq!(
+            pratt("10 ± 2*x", &table),
+            Node::FUNCTION(
+                "plusminus".into(),
+                vec![Node::NUMBER(10.0), Parser::new("2*x").unwrap().parse().unwrap()]
+            )
+        );
+    }
+
+    #[test]
+    fn test_pratt_associativity_and_powers() {
+        let mut table = OperatorTable::default();
+        table
+            .add_infix("^", 3, Associativity::RIGHT, |left, right| {
+                Node::CARRET(Box::new(left), Box::new(right))
+            })
+            .add_prefix("-", 2, |operand| Node::NEGATIVE(Box::new(operand)));
+        let variables = HashMap::new();
+        assert_eq!(ast::eval_with(&pratt("2^3^2", &table), &variables).unwrap(), Value::NUMBER(512.0));
+        assert_eq!(ast::eval_with(&pratt("-2^2", &table), &variables).unwrap(), Value::NUMBER(-4.0));
+        assert_eq!(ast::eval_with(&pratt("-2*3+1", &table), &variables).unwrap(), Value::NUMBER(-5.0));
+    }
+
+    #[test]
+    fn test_pratt_errors() {
+        let table = OperatorTable::empty();
+        // Without operators the parser stops before the first symbol
+        assert_eq!(pratt("1+2", &table), Node::NUMBER(1.0));
+        assert!(PrattParser::new("-1", &table).unwrap().parse().is_err());
+        // A symbol that isn't in the table is an invalid character, like in `Parser`
+        let error = PrattParser::new("1 @ 2", &OperatorTable::default()).unwrap().parse().unwrap_err();
+        assert_eq!(error.to_string(), "Error in evaluating Invalid character");
+        let expr = "(".repeat(10_000) + "1" + &")".repeat(10_000);
+        let error = PrattParser::new(&expr, &table).unwrap().parse().unwrap_err();
+        assert_eq!(error.to_string(), "Error in evaluating The expression is nested deeper than 256 levels");
+    }
+}
@@ -0,0 +1,336 @@
+//! This module holds the **Printer**, the printer is responsible for convert an AST back to an
+//! infix arithmetic expression using the fewest parentheses that keep the same tree when the
+//! text is parsed again
+
+use std::fmt;
+use super::{ast::Node, token::OperPrec};
+
+/// The PowerSymbol enum holds the symbols that can be used to print the power operation
+/// - CARET: `2^3`
+/// - DOUBLESTAR: `2**3`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PowerSymbol {
+    CARET,
+    DOUBLESTAR,
+}
+
+/// The PrintStyle struct holds the options used to print an expression
+/// # Arguments
+/// * `spaces: bool` - if the binary operators are surrounded by spaces (`1 + 2` instead of `1+2`)
+/// * `power: PowerSymbol` - the symbol used for the power operation
+/// # Example
+/// ```
+/// // The default style is the compact one, `1+2*3^2`
+/// let compact = PrintStyle::default();
+/// // We can print with spaces and python like powers, `1 + 2 * 3**2`
+/// let spaced = PrintStyle::new(true, PowerSymbol::DOUBLESTAR);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PrintStyle {
+    pub spaces: bool,
+    pub power: PowerSymbol,
+}
+
+impl PrintStyle {
+    /// Returns a new print style
+    /// # Arguments
+    /// * `spaces: bool` - if the binary operators are surrounded by spaces
+    /// * `power: PowerSymbol` - the symbol used for the power operation
+    pub fn new(spaces: bool, power: PowerSymbol) -> Self {
+        PrintStyle { spaces, power }
+    }
+}
+
+impl Default for PrintStyle {
+    fn default() -> Self {
+        PrintStyle::new(false, PowerSymbol::CARET)
+    }
+}
+
+/// The Styled struct borrows a node and a print style and display the node with that style,
+/// is created with the `Node::display_with` method
+pub struct Styled<'a> {
+    node: &'a Node,
+    style: PrintStyle,
+}
+
+impl Node {
+    /// Returns a value that displays the node as an infix expression with the given style
+    /// # Arguments
+    /// * `style: PrintStyle` - the style used to print the expression
+    /// # Example
+    /// ```
+    /// let mut parser = Parser::new("(1+2)*3").unwrap();
+    /// let ast = parser.parse().unwrap();
+    /// let style = PrintStyle::new(true, PowerSymbol::CARET);
+    /// println!("{}", ast.display_with(style)); // (1 + 2) * 3
+    /// ```
+    pub fn display_with(&self, style: PrintStyle) -> Styled<'_> {
+        Styled { node: self, style }
+    }
+}
+
+// The trait for display with format! or println!, uses the default style
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_node(f, self, PrintStyle::default())
+    }
+}
+
+impl<'a> fmt::Display for Styled<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_node(f, self.node, self.style)
+    }
+}
+
+fn write_node(f: &mut fmt::Formatter, node: &Node, style: PrintStyle) -> fmt::Result {
+    use super::ast::Node::*;
+    match node {
+        NUMBER(value) => write!(f, "{}", value),
+        // The space separates the number from the unit, like the parser reads it
+        QUANTITY(value, unit) => write!(f, "{} {}", value, unit),
+        DATE(date) => write!(f, "{}", date),
+        CONVERT(expr, unit) => {
+            // A lambda would take the conversion as part of its body
+            write_operand(f, expr, style, matches!(**expr, LAMBDA(..)))?;
+            write!(f, " in {}", unit)
+        }
+        VARIABLE(name) => write!(f, "{}", name),
+        ERROR => write!(f, "?"),
+        LAMBDA(parameters, body) => {
+            // The body takes everything at its right, so it never needs parentheses
+            match parameters.as_slice() {
+                [parameter] => write!(f, "{}", parameter)?,
+                parameters => write!(f, "({})", parameters.join(if style.spaces { ", " } else { "," }))?,
+            }
+            write!(f, "{}", if style.spaces { " -> " } else { "->" })?;
+            write_node(f, body, style)
+        }
+        NEGATIVE(expr) => {
+            // The parser only applies the minus sign to a number or a parenthesized
+            // expression, so every operation below a negative needs parentheses
+            write!(f, "-")?;
+            write_operand(f, expr, style, expr.get_oper_prec() < OperPrec::NEGATIVE)
+        }
+        FUNCTION(name, args) => {
+            // The arguments are separated by commas and never need parentheses
+            write!(f, "{}(", name)?;
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 {
+                    write!(f, "{}", if style.spaces { ", " } else { "," })?;
+                }
+                write_node(f, arg, style)?;
+            }
+            write!(f, ")")
+        }
+        LIST(items) => {
+            // The items are separated by commas like the arguments of a call
+            write!(f, "[")?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    write!(f, "{}", if style.spaces { ", " } else { "," })?;
+                }
+                write_node(f, item, style)?;
+            }
+            write!(f, "]")
+        }
+        INDEX(list, index) => {
+            // The index applies to the operand at its left before the minus sign
+            let paren = list.get_oper_prec() < OperPrec::NEGATIVE || is_negative(list);
+            write_operand(f, list, style, paren)?;
+            write!(f, "[")?;
+            write_node(f, index, style)?;
+            write!(f, "]")
+        }
+        PERCENT(expr) => {
+            // The percent sign binds like an index, before the minus sign
+            let paren = expr.get_oper_prec() < OperPrec::NEGATIVE || is_negative(expr);
+            write_operand(f, expr, style, paren)?;
+            write!(f, "%")
+        }
+        RANGE(from, to) => {
+            // The bounds are whole expressions, only the lambdas and the ranges need parentheses
+            write_operand(f, from, style, from.get_oper_prec() == OperPrec::DEFAULTZERO)?;
+            write!(f, "{}", if style.spaces { " .. " } else { ".." })?;
+            write_operand(f, to, style, to.get_oper_prec() == OperPrec::DEFAULTZERO)
+        }
+        ADD(left, right) => write_binary(f, node, left, "+", right, style),
+        SUBTRACT(left, right) => write_binary(f, node, left, "-", right, style),
+        MULTIPLY(left, right) => write_binary(f, node, left, "*", right, style),
+        DIVIDE(left, right) => write_binary(f, node, left, "/", right, style),
+        CARRET(left, right) => {
+            let symbol = match style.power {
+                PowerSymbol::CARET => "^",
+                PowerSymbol::DOUBLESTAR => "**",
+            };
+            write_binary(f, node, left, symbol, right, style)
+        }
+    }
+}
+
+fn is_negative(node: &Node) -> bool {
+    match node {
+        Node::NEGATIVE(..) => true,
+        Node::NUMBER(value) => *value < 0.0,
+        _ => false,
+    }
+}
+
+fn write_binary(
+    f: &mut fmt::Formatter,
+    node: &Node,
+    left: &Node,
+    symbol: &str,
+    right: &Node,
+    style: PrintStyle,
+) -> fmt::Result {
+    let prec = node.get_oper_prec();
+    // All the operators are left associative in the parser, so the left side only needs
+    // parentheses when it binds looser, and the right side also when it binds the same. A
+    // quantity before a compact * or / would read the right side as part of its unit
+    let glued = !style.spaces && matches!(left, Node::QUANTITY(..)) && prec == OperPrec::MULDIV;
+    write_operand(f, left, style, left.get_oper_prec() < prec || glued)?;
+    // The numbers of 2026 - 10 - 16 written without spaces would read as a date
+    if style.spaces || (matches!(node, Node::SUBTRACT(..)) && reads_as_date(left, right)) {
+        write!(f, " {} ", symbol)?;
+    } else {
+        write!(f, "{}", symbol)?;
+    }
+    write_operand(f, right, style, right.get_oper_prec() <= prec)
+}
+
+// Checks if a compact subtraction of the left and the right operands is written like a date,
+// a number of four digits, a minus, a number of two digits, a minus and two digits
+fn reads_as_date(left: &Node, right: &Node) -> bool {
+    let digits = |value: Option<f64>, count: usize| {
+        value.is_some_and(|value| value.to_string().chars().filter(char::is_ascii_digit).count() == count)
+    };
+    match left {
+        Node::SUBTRACT(year, month) => {
+            let year = if year.get_oper_prec() < OperPrec::ADDSUB { None } else { last_number(year) };
+            digits(year, 4) && digits(last_number(month), 2) && first_digits(right) >= 2
+        }
+        _ => false,
+    }
+}
+
+// The number written at the end of a node, if it isn't in parentheses
+fn last_number(node: &Node) -> Option<f64> {
+    match node {
+        Node::NUMBER(value) if value.fract() == 0.0 => Some(*value),
+        Node::NEGATIVE(expr) if expr.get_oper_prec() == OperPrec::NEGATIVE => last_number(expr),
+        Node::ADD(_, right) | Node::SUBTRACT(_, right) | Node::MULTIPLY(_, right) | Node::DIVIDE(_, right) | Node::CARRET(_, right)
+            if right.get_oper_prec() > node.get_oper_prec() =>
+        {
+            last_number(right)
+        }
+        _ => None,
+    }
+}
+
+// The number of digits at the start of a node
+fn first_digits(node: &Node) -> usize {
+    match node {
+        Node::NUMBER(value) | Node::QUANTITY(value, _) if *value >= 0.0 => {
+            value.to_string().chars().take_while(char::is_ascii_digit).count()
+        }
+        Node::ADD(left, _) | Node::SUBTRACT(left, _) | Node::MULTIPLY(left, _) | Node::DIVIDE(left, _) | Node::CARRET(left, _)
+        | Node::INDEX(left, _) | Node::PERCENT(left) => first_digits(left),
+        _ => 0,
+    }
+}
+
+fn write_operand(f: &mut fmt::Formatter, node: &Node, style: PrintStyle, paren: bool) -> fmt::Result {
+    if paren {
+        write!(f, "(")?;
+        write_node(f, node, style)?;
+        write!(f, ")")
+    } else {
+        write_node(f, node, style)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsemath::parser::Parser;
+
+    fn parse(expr: &str) -> Node {
+        let mut parser = Parser::new(expr).unwrap();
+        parser.parse().unwrap()
+    }
+
+    #[test]
+    fn test_printer_precedence() {
+        assert_eq!(parse("1+2*3").to_string(), "1+2*3");
+        assert_eq!(parse("(1+2)*3").to_string(), "(1+2)*3");
+        assert_eq!(parse("((1))+(2*3)").to_string(), "1+2*3");
+    }
+
+    #[test]
+    fn test_printer_associativity() {
+        assert_eq!(parse("(1-2)-3").to_string(), "1-2-3");
+        assert_eq!(parse("1-(2-3)").to_string(), "1-(2-3)");
+        assert_eq!(parse("1+(2+3)").to_string(), "1+(2+3)");
+        assert_eq!(parse("2^(3^4)").to_string(), "2^(3^4)");
+        assert_eq!(parse("8/(4*2)").to_string(), "8/(4*2)");
+    }
+
+    #[test]
+    fn test_printer_negative() {
+        assert_eq!(parse("-2^2").to_string(), "-2^2");
+        assert_eq!(parse("-(2^2)").to_string(), "-(2^2)");
+        assert_eq!(parse("--1").to_string(), "--1");
+        assert_eq!(parse("2*-(1+1)").to_string(), "2*-(1+1)");
+    }
+
+    #[test]
+    fn test_printer_styles() {
+        let ast = parse("1+2*3^2");
+        let spaced = PrintStyle::new(true, PowerSymbol::DOUBLESTAR);
+        assert_eq!(ast.display_with(spaced).to_string(), "1 + 2 * 3 ** 2");
+        assert_eq!(ast.display_with(PrintStyle::default()).to_string(), "1+2*3^2");
+    }
+
+    #[test]
+    fn test_printer_round_trip() {
+        let exprs = [
+            "1+2*3-4/5",
+            "(1+2)*(3-4)/5",
+            "2^3^4",
+            "2^(3^4)",
+            "-(1+2)^-3",
+            "1-(2-(3-4))",
+            "1.5*-2.25/(0.5+-1)",
+            "((2))(3+4)",
+            "max(1+2,-sqrt(4))^2",
+            "(x->x^2)*-(y->2)",
+            "fold((a,i)->a+i->i,0,1,n)",
+            "[1,-x,[2][0]][1+1]*-v[0]",
+            "(-v)[0]+sum(1..n+1)-(1..2)[0]",
+            "(x->x)..(1..2)[0]",
+            "3 m/(2 s)+1 km in m",
+            "-(9.81 m/s^2)*2 kg*s^2 in N*s^2",
+            "(x->x) in m in km",
+            "(1 in m)..2",
+            "(-5)%+x[0]%^2-(1+y)%*(3 m)%",
+            "2026-10-16T14:30-2026-10-01+2 days",
+            "2026-10-(16)-x*2026-(10-16)-2026-10-16",
+            "2026 - 10 - 16*2 - 1 - 2 - 3",
+            "2*π*r^2-tau/phi+e",
+        ];
+        let styles = [
+            PrintStyle::default(),
+            PrintStyle::new(true, PowerSymbol::CARET),
+            PrintStyle::new(false, PowerSymbol::DOUBLESTAR),
+            PrintStyle::new(true, PowerSymbol::DOUBLESTAR),
+        ];
+        for expr in exprs.iter() {
+            let ast = parse(expr);
+            for style in styles.iter() {
+                let printed = ast.display_with(*style).to_string();
+                assert_eq!(parse(&printed), ast, "{} printed as {}", expr, printed);
+            }
+        }
+    }
+}
@@ -0,0 +1,369 @@
+//! This module holds the **programs**, a list of statements separated by `;` or new lines like
+//! `r = 0.05; n = 12; p * r / (1 - (1+r)^-n)`. An assignment stores the value of an expression
+//! in the environment and the next statements can read it as a variable, a definition like
+//! `f(x, y) = x^2 + y^2` stores a function that the next statements can call. A variable can
+//! also hold a lambda, `square = x -> x^2` is called like a function with `square(3)`
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::{
+    ast::{self, EvalError, Globals, Node, Scope},
+    constants::Constants,
+    limits::Limits,
+    parser::{ParseError, Parser},
+    value::Value,
+};
+
+/// The UserFunction struct holds a function defined in a program
+/// # Arguments
+/// * `parameters: Vec<String>` - the names of the parameters, in the order of the arguments
+/// * `body: Node` - the expression of the function, it can read its parameters, the variables
+///   of the program and call the functions, itself included
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserFunction {
+    pub parameters: Vec<String>,
+    pub body: Node,
+}
+
+/// The Statement enum holds a statement of a program
+/// - ASSIGN: `name = expression`, stores the value of the expression
+/// - DEFINE: `name(parameters) = expression`, stores the function
+/// - EXPRESSION: an expression alone, its value is the value of the program if is the last one
+#[derive(Clone, Debug, PartialEq)]
+pub enum Statement {
+    ASSIGN(String, Node),
+    DEFINE(String, UserFunction),
+    EXPRESSION(Node),
+}
+
+// The trait for display with format! or println!
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Statement::ASSIGN(name, expr) => write!(f, "{} = {}", name, expr),
+            Statement::DEFINE(name, function) => {
+                write!(f, "{}({}) = {}", name, function.parameters.join(","), function.body)
+            }
+            Statement::EXPRESSION(expr) => write!(f, "{}", expr),
+        }
+    }
+}
+
+/// The Environment struct holds the values of the variables and the functions of a program, it
+/// is kept between the evaluations so a program can use the values assigned by another one. The
+/// constants like `pi` and `e` are in every environment and can't be assigned
+/// # Example
+/// ```
+/// let mut environment = Environment::new();
+/// environment.set("p", Value::NUMBER(1000.0)).unwrap();
+/// let error = environment.set("pi", Value::NUMBER(3.0));
+/// // This will be Err(EvalError::ConstantAssignment("pi"))
+/// ```
+#[derive(Clone, Debug)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+    constants: HashSet<String>,
+    functions: HashMap<String, UserFunction>,
+}
+
+impl Environment {
+    /// Returns an environment with only the built-in constants
+    pub fn new() -> Self {
+        Environment::with_constants(&Constants::new())
+    }
+
+    /// Returns an environment with the built-in constants and the ones registered by the library
+    /// user
+    /// # Arguments
+    /// * `constants: &Constants` - the constants, they can't be assigned by the programs
+    /// # Example
+    /// ```
+    /// let mut constants = Constants::new();
+    /// constants.register("vat", 0.21).unwrap();
+    /// let mut environment = Environment::with_constants(&constants);
+    /// // environment.get("vat") will be Some(&Value::NUMBER(0.21))
+    /// ```
+    pub fn with_constants(constants: &Constants) -> Self {
+        let mut environment = Environment {
+            values: HashMap::new(),
+            constants: HashSet::new(),
+            functions: HashMap::new(),
+        };
+        for (name, value) in constants.iter() {
+            environment.values.insert(name.to_string(), Value::NUMBER(value));
+            environment.constants.insert(name.to_string());
+        }
+        environment
+    }
+
+    /// Returns the value of a variable or a constant
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+
+    /// Assigns a value to a variable
+    /// # Arguments
+    /// * `name: &str` - the name of the variable
+    /// * `value: Value` - the new value, a number or a lambda
+    /// # Returns
+    /// * `Result<(), EvalError>` - an error if the name is a constant
+    pub fn set(&mut self, name: &str, value: Value) -> Result<(), EvalError> {
+        if self.constants.contains(name) {
+            return Err(EvalError::ConstantAssignment(name.to_string()));
+        }
+        self.values.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Returns the values of all the variables and constants by name
+    pub fn values(&self) -> &HashMap<String, Value> {
+        &self.values
+    }
+
+    /// Returns a function defined by a program
+    pub fn function(&self, name: &str) -> Option<&UserFunction> {
+        self.functions.get(name)
+    }
+
+    /// Defines a function, replacing the function with the same name
+    pub fn define(&mut self, name: &str, function: UserFunction) {
+        self.functions.insert(name.to_string(), function);
+    }
+
+    // Evaluates an expression with the variables and functions of the environment
+    fn eval(&self, expr: &Node, limits: Limits) -> Result<Value, EvalError> {
+        let scope = Scope {
+            parameters: None,
+            globals: Globals::VALUES(&self.values),
+            // The constants of the environment are already in its values
+            constants: &Constants::new(),
+            functions: &self.functions,
+        };
+        ast::eval_scoped(expr, &scope, limits)
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::new()
+    }
+}
+
+/// The Program struct holds the parsed statements of a program
+/// # Example
+/// ```
+/// let program = Program::parse("r = 0.05; n = 12\np * r / (1 - (1+r)^-n)").unwrap();
+/// let mut environment = Environment::new();
+/// environment.set("p", Value::NUMBER(1000.0)).unwrap();
+/// let value = program.evaluate(&mut environment);
+/// // This will be Ok(Some(Value::NUMBER(112.82...))) and the environment will have r and n
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Program {
+    statements: Vec<Statement>,
+    limits: Limits,
+}
+
+impl Program {
+    /// Parses a program with the default limits
+    /// # Arguments
+    /// * `source: &str` - the statements, separated by `;` or new lines. A new line inside
+    ///   parentheses doesn't end the statement
+    /// # Returns
+    /// * `Result<Program, ParseError>` - the program or the first error
+    pub fn parse(source: &str) -> Result<Program, ParseError> {
+        Program::parse_with_limits(source, Limits::default())
+    }
+
+    /// Parses a program with the given limits, they apply to the whole source when parsing and
+    /// to every statement when evaluating
+    /// # Arguments
+    /// * `source: &str` - the statements, separated by `;` or new lines
+    /// * `limits: Limits` - the limits of the program
+    /// # Returns
+    /// * `Result<Program, ParseError>` - the program or the first error
+    pub fn parse_with_limits(source: &str, limits: Limits) -> Result<Program, ParseError> {
+        let statements = Parser::for_program(source, limits)?.parse_statements()?;
+        Ok(Program { statements, limits })
+    }
+
+    /// Returns the statements in the order of the source
+    pub fn statements(&self) -> &[Statement] {
+        &self.statements
+    }
+
+    /// Evaluates the statements in order, the assignments and definitions update the environment.
+    /// A recursive function stops with `LimitError::TooDeepRecursion` when the calls nest deeper
+    /// than `max_call_depth`, or with `LimitError::TooDeep` if its body is deep enough to reach
+    /// the depth limit before
+    /// # Arguments
+    /// * `environment: &mut Environment` - the values of the variables, the assignments made
+    ///   before an error are kept
+    /// # Returns
+    /// * `Result<Option<Value>, EvalError>` - the value of the last expression statement, `None` if
+    ///   the program has only assignments, or the first error
+    pub fn evaluate(&self, environment: &mut Environment) -> Result<Option<Value>, EvalError> {
+        let mut last = None;
+        for statement in self.statements.iter() {
+            match statement {
+                Statement::ASSIGN(name, expr) => {
+                    let value = environment.eval(expr, self.limits)?;
+                    environment.set(name, value)?;
+                }
+                Statement::DEFINE(name, function) => environment.define(name, function.clone()),
+                Statement::EXPRESSION(expr) => last = Some(environment.eval(expr, self.limits)?),
+            }
+        }
+        Ok(last)
+    }
+}
+
+// The trait for display with format! or println!, the statements are separated by semicolons
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let statements: Vec<String> = self.statements.iter().map(|statement| statement.to_string()).collect();
+        write!(f, "{}", statements.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsemath::limits::LimitError;
+
+    #[test]
+    fn test_program_evaluate() {
+        let program = Program::parse("r = 0.05; n = 12\np * r / (1 - (1+r)^-n)").unwrap();
+        let mut environment = Environment::new();
+        environment.set("p", Value::NUMBER(1000.0)).unwrap();
+        let value = program.evaluate(&mut environment).unwrap().unwrap().as_number().unwrap();
+        let expected = 1000.0 * 0.05 / (1.0 - 1.05_f64.powf(-12.0));
+        assert!((value - expected).abs() < 1e-9);
+        assert_eq!(environment.get("n"), Some(&Value::NUMBER(12.0)));
+        // The environment is kept for the next program
+        let program = Program::parse("n = n + 1\n\n;n*2;").unwrap();
+        assert_eq!(program.evaluate(&mut environment), Ok(Some(Value::NUMBER(26.0))));
+        assert_eq!(Program::parse("x = 1").unwrap().evaluate(&mut environment), Ok(None));
+    }
+
+    #[test]
+    fn test_program_multiline_parens() {
+        let program = Program::parse("x = (1 +\n 2)\ny = max(x,\n 10)\nx * y").unwrap();
+        assert_eq!(program.to_string(), "x = 1+2; y = max(x,10); x*y");
+        assert_eq!(program.evaluate(&mut Environment::new()), Ok(Some(Value::NUMBER(30.0))));
+    }
+
+    #[test]
+    fn test_program_constants() {
+        let program = Program::parse("r = 2; pi * r^2").unwrap();
+        let value = program.evaluate(&mut Environment::new()).unwrap().unwrap();
+        assert_eq!(value, Value::NUMBER(std::f64::consts::PI * 4.0));
+        let program = Program::parse("x = 1; pi = 3; x").unwrap();
+        let mut environment = Environment::new();
+        let error = program.evaluate(&mut environment).unwrap_err();
+        assert_eq!(error, EvalError::ConstantAssignment("pi".into()));
+        assert_eq!(error.to_string(), "The constant pi can't be assigned");
+        assert_eq!(environment.get("x"), Some(&Value::NUMBER(1.0)));
+        let mut constants = Constants::new();
+        constants.register("vat", 0.21).unwrap();
+        let mut environment = Environment::with_constants(&constants);
+        let program = Program::parse("100 * (1 + vat) + tau - 2*π").unwrap();
+        assert_eq!(program.evaluate(&mut environment), Ok(Some(Value::NUMBER(121.0))));
+        let error = Program::parse("vat = 0").unwrap().evaluate(&mut environment).unwrap_err();
+        assert_eq!(error, EvalError::ConstantAssignment("vat".into()));
+    }
+
+    #[test]
+    fn test_program_functions() {
+        let program = Program::parse("f(x, y) = x^2 + y^2\nf(3, 4)").unwrap();
+        assert_eq!(program.to_string(), "f(x,y) = x^2+y^2; f(3,4)");
+        let mut environment = Environment::new();
+        assert_eq!(program.evaluate(&mut environment), Ok(Some(Value::NUMBER(25.0))));
+        assert_eq!(environment.function("f").unwrap().parameters, vec!["x", "y"]);
+        let error = Program::parse("f(1)").unwrap().evaluate(&mut environment).unwrap_err();
+        assert_eq!(error, EvalError::WrongArgumentCount("f".into(), 2, 1));
+    }
+
+    #[test]
+    fn test_program_function_scope() {
+        // A parameter hides the variable with its name, and the body of g doesn't see the
+        // parameter y of k
+        let program = Program::parse("x = 10; y = 1; g(x) = x + y; k(y) = g(0) * 2; g(2) + k(100)");
+        let value = program.unwrap().evaluate(&mut Environment::new());
+        assert_eq!(value, Ok(Some(Value::NUMBER(5.0))));
+        let program = Program::parse("h(a) = a + b; h(1)").unwrap();
+        let error = program.evaluate(&mut Environment::new()).unwrap_err();
+        assert_eq!(error, EvalError::UnknownVariable("b".into()));
+    }
+
+    #[test]
+    fn test_program_recursion() {
+        let program = Program::parse("fact(n) = if(n, n * fact(n - 1), 1); fact(10)").unwrap();
+        assert_eq!(program.evaluate(&mut Environment::new()), Ok(Some(Value::NUMBER(3628800.0))));
+        let program = Program::parse("forever(n) = forever(n + 1); forever(0)").unwrap();
+        let error = program.evaluate(&mut Environment::new()).unwrap_err();
+        assert_eq!(error, EvalError::LimitExceeded(LimitError::TooDeepRecursion(64)));
+    }
+
+    #[test]
+    fn test_program_recursion_limit() {
+        // down(n) nests n + 1 calls, the last one at the limit
+        let mut environment = Environment::new();
+        let program = Program::parse("down(n) = if(n, down(n - 1), 0); down(63)").unwrap();
+        assert_eq!(program.evaluate(&mut environment), Ok(Some(Value::NUMBER(0.0))));
+        let error = Program::parse("down(64)").unwrap().evaluate(&mut environment).unwrap_err();
+        assert_eq!(error, EvalError::LimitExceeded(LimitError::TooDeepRecursion(64)));
+        // The lambdas are counted like the user functions
+        let limits = Limits {
+            max_call_depth: 2,
+            ..Limits::default()
+        };
+        let program = Program::parse_with_limits("twice(f, x) = f(f(x)); twice(x -> x + 1, 0)", limits).unwrap();
+        assert_eq!(program.evaluate(&mut environment), Ok(Some(Value::NUMBER(2.0))));
+        let program = Program::parse_with_limits("twice(x -> twice(y -> y, x), 0)", limits).unwrap();
+        let error = program.evaluate(&mut environment).unwrap_err();
+        assert_eq!(error, EvalError::LimitExceeded(LimitError::TooDeepRecursion(2)));
+    }
+
+    #[test]
+    fn test_program_lambdas() {
+        // A variable holds a lambda and a user function can be passed like a value
+        let source = "adder = n -> x -> x + n; add3 = adder(3); twice(f, x) = f(f(x)); twice(add3, 1)";
+        let mut environment = Environment::new();
+        let value = Program::parse(source).unwrap().evaluate(&mut environment);
+        assert_eq!(value, Ok(Some(Value::NUMBER(7.0))));
+        assert_eq!(environment.get("add3").unwrap().to_string(), "x->x+n");
+        let program = Program::parse("square(x) = x^2; fold((a, i) -> a + square(i), 0, 1, 3)").unwrap();
+        assert_eq!(program.evaluate(&mut environment), Ok(Some(Value::NUMBER(14.0))));
+        let program = Program::parse("map(square, 4)").unwrap();
+        assert_eq!(program.evaluate(&mut environment), Ok(Some(Value::NUMBER(16.0))));
+    }
+
+    #[test]
+    fn test_program_lists() {
+        // The monthly series are lists, a new line inside the brackets doesn't end the statement
+        let source = "sales = [120, 80,\n 100]; costs = [100, 90, 60]\nmargin = sales - costs\nsum(margin) / len(margin)";
+        let mut environment = Environment::new();
+        let value = Program::parse(source).unwrap().evaluate(&mut environment);
+        assert_eq!(value, Ok(Some(Value::NUMBER(50.0 / 3.0))));
+        assert_eq!(environment.get("margin"), Some(&Value::LIST(vec![20.0, -10.0, 40.0])));
+        let program = Program::parse("growth(v) = v[len(v) - 1] / v[0]; growth(sales)").unwrap();
+        assert_eq!(program.evaluate(&mut environment), Ok(Some(Value::NUMBER(100.0 / 120.0))));
+    }
+
+    #[test]
+    fn test_program_errors() {
+        let error = Program::parse("x + 1 = 2").unwrap_err();
+        assert_eq!(error.to_string(), "Error in evaluating Cannot assign to x+1");
+        let error = Program::parse("x = 1 2").unwrap_err();
+        assert_eq!(error.to_string(), "Error in evaluating Expected SEMICOLON, got NUM(2.0)");
+        assert!(Program::parse("x = ").is_err());
+        let error = Program::parse("sqrt(x) = 1").unwrap_err();
+        assert_eq!(error.to_string(), "Error in evaluating Cannot redefine the built-in function sqrt");
+        let error = Program::parse("f(x, x) = 1").unwrap_err();
+        assert_eq!(error.to_string(), "Error in evaluating Invalid parameter x of the function f");
+        let error = Program::parse("y = x").unwrap().evaluate(&mut Environment::new()).unwrap_err();
+        assert_eq!(error, EvalError::UnknownVariable("x".into()));
+    }
+}
@@ -0,0 +1,600 @@
+//! This module holds the conversions between the AST and the **Reverse Polish Notation**, the
+//! postfix notation used by stack calculators where the operands are written before the
+//! operator, `1 2 3 * +`. The negation is written with the `neg` word, `5 neg`, and a function
+//! call is written with its name and its number of arguments after the arguments, `1 2 max(2)`,
+//! so the calls of user functions like `1 f(1)` are read back too. A built-in function without
+//! the number, `16 sqrt`, takes its usual number of arguments. Any other name is a variable. The
+//! `RpnCalculator` evaluates RPN directly over a stack of numbers. A lambda is written with its
+//! body, its parameters and an arrow, `x 2 ^ x ->`, but it can't be read back, neither a call
+//! that takes a lambda like `map(x -> x+1, v)`, a built-in function used as a value like the
+//! `sqrt` of `map(sqrt, v)`, that is read as a call, nor the `?` of a part of the input that
+//! couldn't be parsed. A list is written between brackets,
+//! `[ 1 2 3 ]`, an index with the `at` word, `v 0 at`, and a range with its dots, `1 10 ..`.
+//! A quantity is written like in infix, `3 km`, and a conversion with the keyword and the unit
+//! after the value, `x in m`. A percentage has its sign after the value, `15 %`. In the
+//! calculator a constant like `pi` pushes its value
+
+use std::fmt;
+
+use super::{
+    ast::{self, Node},
+    constants,
+    functions,
+    parser::ParseError,
+    token::Token,
+    tokenizer::{Tokenizer, CONVERSION_KEYWORD},
+};
+
+/// The word used for the negation in RPN
+pub const NEGATE_WORD: &str = "neg";
+
+/// The word used for the index of a list in RPN
+pub const INDEX_WORD: &str = "at";
+
+/// The word used for a part of the input that couldn't be parsed, it can't be read back
+pub const ERROR_WORD: &str = "?";
+
+/// Converts an AST to a sequence of tokens in Reverse Polish Notation
+/// # Arguments
+/// * `node: &Node` - the root of the AST
+/// # Returns
+/// * `Vec<Token>` - the tokens, the negations and the calls are `Token::IDENT`, a call is
+///   followed by its number of arguments between parentheses
+/// # Example
+/// ```
+/// let mut parser = Parser::new("1+2*3").unwrap();
+/// let tokens = to_rpn(&parser.parse().unwrap());
+/// // The tokens will be [NUM(1.0), NUM(2.0), NUM(3.0), MULTIPLY, ADD]
+/// ```
+pub fn to_rpn(node: &Node) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    write_rpn(&mut tokens, node);
+    tokens
+}
+
+fn write_rpn(tokens: &mut Vec<Token>, node: &Node) {
+    use super::ast::Node::*;
+    // The bracket before the items tells where the list starts
+    if let LIST(_) = node {
+        tokens.push(Token::LEFTBRACKET);
+    }
+    // The operands go first, from left to right
+    for child in node.children() {
+        write_rpn(tokens, child);
+    }
+    let token = match node {
+        NUMBER(value) => Token::NUM(*value),
+        QUANTITY(value, unit) => {
+            tokens.push(Token::NUM(*value));
+            Token::UNIT(unit.clone())
+        }
+        DATE(date) => Token::DATE(*date),
+        CONVERT(_, unit) => {
+            tokens.push(Token::IN);
+            Token::UNIT(unit.clone())
+        }
+        ADD(..) => Token::ADD,
+        SUBTRACT(..) => Token::SUBTRACT,
+        MULTIPLY(..) => Token::MULTIPLY,
+        DIVIDE(..) => Token::DIVIDE,
+        CARRET(..) => Token::CARET,
+        NEGATIVE(..) => Token::IDENT(NEGATE_WORD.to_string()),
+        PERCENT(..) => Token::PERCENT,
+        FUNCTION(name, args) => {
+            tokens.push(Token::IDENT(name.clone()));
+            tokens.push(Token::LEFTPAREN);
+            tokens.push(Token::NUM(args.len() as f64));
+            Token::RIGHTPAREN
+        }
+        VARIABLE(name) => Token::IDENT(name.clone()),
+        ERROR => Token::IDENT(ERROR_WORD.to_string()),
+        LIST(_) => Token::RIGHTBRACKET,
+        INDEX(..) => Token::IDENT(INDEX_WORD.to_string()),
+        RANGE(..) => Token::RANGE,
+        LAMBDA(parameters, _) => {
+            tokens.extend(parameters.iter().map(|parameter| Token::IDENT(parameter.clone())));
+            Token::ARROW
+        }
+    };
+    tokens.push(token);
+}
+
+/// Converts a sequence of RPN tokens to text separated by spaces
+/// # Arguments
+/// * `tokens: &[Token]` - the tokens in Reverse Polish Notation
+/// # Returns
+/// * `String` - the text, negative numbers are written as a negation `5 neg` and the number of
+///   arguments of a call is joined to its name, `max(2)`
+/// # Example
+/// ```
+/// let text = rpn_to_string(&[Token::NUM(1.0), Token::NUM(2.0), Token::ADD]);
+/// // This will be "1 2 +"
+/// ```
+pub fn rpn_to_string(tokens: &[Token]) -> String {
+    let words = tokens
+        .iter()
+        .map(|token| match token {
+            Token::NUM(value) if *value < 0.0 => format!("{} {}", -value, NEGATE_WORD),
+            Token::NUM(value) => value.to_string(),
+            Token::ADD => "+".to_string(),
+            Token::SUBTRACT => "-".to_string(),
+            Token::MULTIPLY => "*".to_string(),
+            Token::DIVIDE => "/".to_string(),
+            Token::CARET => "^".to_string(),
+            Token::LEFTPAREN => "(".to_string(),
+            Token::RIGHTPAREN => ")".to_string(),
+            Token::LEFTBRACKET => "[".to_string(),
+            Token::RIGHTBRACKET => "]".to_string(),
+            Token::RANGE => "..".to_string(),
+            Token::IN => CONVERSION_KEYWORD.to_string(),
+            Token::PERCENT => "%".to_string(),
+            Token::COMMA => ",".to_string(),
+            Token::ASSIGN => "=".to_string(),
+            Token::SEMICOLON => ";".to_string(),
+            Token::ARROW => "->".to_string(),
+            Token::IDENT(name) | Token::SYMBOL(name) | Token::UNIT(name) => name.clone(),
+            Token::DATE(date) => date.to_string(),
+            Token::EOF => String::new(),
+        });
+    let mut text = String::new();
+    let mut joined = true;
+    for word in words {
+        // The parentheses only hold the number of arguments of a call, a space after the name
+        // would make it a unit
+        let glued = word == "(" || word == ")";
+        if !joined && !glued {
+            text.push(' ');
+        }
+        joined = word == "(";
+        text.push_str(&word);
+    }
+    text
+}
+
+/// Builds an AST from a sequence of RPN tokens
+/// # Arguments
+/// * `tokens: &[Token]` - the tokens in Reverse Polish Notation
+/// # Returns
+/// * `Result<Node, ParseError>` - the root of the AST or an error if an operator doesn't have
+///   enough operands or if there are operands left
+/// # Example
+/// ```
+/// let ast = from_rpn(&[Token::NUM(1.0), Token::NUM(2.0), Token::ADD]).unwrap();
+/// // The ast will be ADD(NUMBER(1.0), NUMBER(2.0))
+/// ```
+pub fn from_rpn(tokens: &[Token]) -> Result<Node, ParseError> {
+    let mut stack: Vec<Node> = Vec::new();
+    // The size of the stack where every open list starts
+    let mut lists: Vec<usize> = Vec::new();
+    let mut tokens = tokens.iter();
+    while let Some(token) = tokens.next() {
+        let constructor: fn(Box<Node>, Box<Node>) -> Node = match token {
+            Token::NUM(value) => {
+                stack.push(Node::NUMBER(*value));
+                continue;
+            }
+            Token::DATE(date) => {
+                stack.push(Node::DATE(*date));
+                continue;
+            }
+            Token::UNIT(unit) => {
+                // The unit of a quantity follows its number, a call like min(2) has the number
+                // of its arguments so min after a number is always the minutes
+                match stack.pop() {
+                    Some(Node::NUMBER(value)) => stack.push(Node::QUANTITY(value, unit.clone())),
+                    _ => return Err(ParseError::UnableToParse(format!("The unit {} needs a number", unit))),
+                }
+                continue;
+            }
+            Token::IN => {
+                let expr = pop_operands(&mut stack, CONVERSION_KEYWORD, 1)?.remove(0);
+                match tokens.next() {
+                    Some(Token::UNIT(unit)) => stack.push(Node::CONVERT(Box::new(expr), unit.clone())),
+                    _ => return Err(ParseError::UnableToParse("Expected a unit after in".into())),
+                }
+                continue;
+            }
+            Token::PERCENT => {
+                let expr = pop_operands(&mut stack, "%", 1)?.remove(0);
+                stack.push(Node::PERCENT(Box::new(expr)));
+                continue;
+            }
+            Token::IDENT(name) if tokens.as_slice().first() == Some(&Token::LEFTPAREN) => {
+                let count = match tokens.as_slice() {
+                    [_, Token::NUM(count), Token::RIGHTPAREN, ..] if count.fract() == 0.0 && *count >= 0.0 => *count as usize,
+                    _ => return Err(ParseError::UnableToParse(format!("Expected the number of arguments of {}", name))),
+                };
+                tokens.nth(2);
+                let args = pop_operands(&mut stack, name, count)?;
+                stack.push(Node::FUNCTION(name.clone(), args));
+                continue;
+            }
+            Token::IDENT(name) => {
+                // Without the number of arguments a call takes the arity of the function
+                let node = if name == ERROR_WORD {
+                    return Err(ParseError::UnableToParse("The input had an error".into()));
+                } else if name == NEGATE_WORD {
+                    Node::NEGATIVE(Box::new(pop_operands(&mut stack, name, 1)?.remove(0)))
+                } else if name == INDEX_WORD {
+                    let mut operands = pop_operands(&mut stack, name, 2)?.into_iter();
+                    let list = operands.next().unwrap();
+                    Node::INDEX(Box::new(list), Box::new(operands.next().unwrap()))
+                } else if let Some(arity) = functions::get_arity(name) {
+                    Node::FUNCTION(name.clone(), pop_operands(&mut stack, name, arity)?)
+                } else {
+                    Node::VARIABLE(name.clone())
+                };
+                stack.push(node);
+                continue;
+            }
+            Token::ADD => Node::ADD,
+            Token::SUBTRACT => Node::SUBTRACT,
+            Token::MULTIPLY => Node::MULTIPLY,
+            Token::DIVIDE => Node::DIVIDE,
+            Token::CARET => Node::CARRET,
+            Token::RANGE => Node::RANGE,
+            Token::LEFTBRACKET => {
+                lists.push(stack.len());
+                continue;
+            }
+            Token::RIGHTBRACKET => {
+                // The operators inside the list can't take the values before it
+                match lists.pop() {
+                    Some(start) if start <= stack.len() => {
+                        let items = stack.split_off(start);
+                        stack.push(Node::LIST(items));
+                    }
+                    _ => return Err(ParseError::UnableToParse("Unexpected RIGHTBRACKET".into())),
+                }
+                continue;
+            }
+            Token::EOF => break,
+            _ => {
+                return Err(ParseError::InvalidOperator(format!(
+                    "Please enter valid operator {:?}",
+                    token
+                )))
+            }
+        };
+        let mut operands = pop_operands(&mut stack, &format!("{:?}", token), 2)?.into_iter();
+        let left = operands.next().unwrap();
+        let right = operands.next().unwrap();
+        stack.push(constructor(Box::new(left), Box::new(right)));
+    }
+    if !lists.is_empty() {
+        return Err(ParseError::InvalidOperator("Expected RIGHTBRACKET".into()));
+    }
+    // A complete expression leaves a single value in the stack
+    match stack.len() {
+        1 => Ok(stack.pop().unwrap()),
+        0 => Err(ParseError::UnableToParse("Empty expression".into())),
+        n => Err(ParseError::UnableToParse(format!(
+            "{} values left in the stack, missing operators",
+            n
+        ))),
+    }
+}
+
+// Takes the last operands of the stack keeping their order
+fn pop_operands(stack: &mut Vec<Node>, operator: &str, count: usize) -> Result<Vec<Node>, ParseError> {
+    if stack.len() < count {
+        return Err(ParseError::UnableToParse(format!(
+            "The operator {} needs {} operands",
+            operator, count
+        )));
+    }
+    Ok(stack.split_off(stack.len() - count))
+}
+
+/// Parses an expression written in Reverse Polish Notation
+/// # Arguments
+/// * `expr: &str` - the expression, the tokens are separated by spaces like `3 4 + 2 *`
+/// # Returns
+/// * `Result<Node, ParseError>` - the root of the AST or an error if the expression is malformed
+/// # Example
+/// ```
+/// let ast = parse_rpn("3 4 + 2 *").unwrap();
+/// // The ast will be MULTIPLY(ADD(NUMBER(3.0), NUMBER(4.0)), NUMBER(2.0))
+/// ```
+pub fn parse_rpn(expr: &str) -> Result<Node, ParseError> {
+    from_rpn(&Tokenizer::new(expr).into_tokens()?)
+}
+
+/// The RpnCalculator struct holds the stack of a stack based calculator, the numbers are pushed
+/// to the stack and the operators take their operands from the top of the stack. Besides the
+/// operators, `neg` and the built-in functions it understands the stack commands:
+/// - `swap` exchanges the two values at the top
+/// - `dup` pushes a copy of the value at the top
+/// - `drop` removes the value at the top
+/// - `clear` removes all the values
+/// # Example
+/// ```
+/// let mut calculator = RpnCalculator::new();
+/// calculator.execute("3 4 + 2 *").unwrap();
+/// // The top of the stack will be Some(14.0)
+/// let top = calculator.top();
+/// ```
+#[derive(Debug, Default)]
+pub struct RpnCalculator {
+    stack: Vec<f64>,
+}
+
+impl RpnCalculator {
+    /// Returns a new calculator with an empty stack
+    pub fn new() -> Self {
+        RpnCalculator { stack: Vec::new() }
+    }
+
+    /// Returns the values in the stack, the last one is the top
+    pub fn stack(&self) -> &[f64] {
+        &self.stack
+    }
+
+    /// Returns the value at the top of the stack
+    pub fn top(&self) -> Option<f64> {
+        self.stack.last().copied()
+    }
+
+    /// Executes a line of numbers, operators and commands separated by spaces
+    /// # Arguments
+    /// * `line: &str` - the words to execute from left to right, like `3 4 + 2 *`
+    /// # Returns
+    /// * `Result<(), ParseError>` - an error if a word is invalid, there are not enough values
+    ///   for an operator or the operation fails, in that case the stack is left as it was before
+    ///   the line
+    pub fn execute(&mut self, line: &str) -> Result<(), ParseError> {
+        let tokens = Tokenizer::new(line).into_tokens()?;
+        // We work on a copy so a failed line doesn't leave the stack half modified
+        let saved = self.stack.clone();
+        for token in tokens {
+            if let Err(e) = self.execute_token(token) {
+                self.stack = saved;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_token(&mut self, token: Token) -> Result<(), ParseError> {
+        let constructor: fn(Box<Node>, Box<Node>) -> Node = match token {
+            Token::NUM(value) => {
+                self.stack.push(value);
+                return Ok(());
+            }
+            // The tokenizer reads a name after a number as a unit, like the min of 1 2 min
+            Token::IDENT(name) | Token::UNIT(name) => return self.execute_word(&name),
+            Token::ADD => Node::ADD,
+            Token::SUBTRACT => Node::SUBTRACT,
+            Token::MULTIPLY => Node::MULTIPLY,
+            Token::DIVIDE => Node::DIVIDE,
+            Token::CARET => Node::CARRET,
+            Token::PERCENT => {
+                let value = self.pop_values("%", 1)?[0];
+                return self.push_eval(Node::PERCENT(Box::new(Node::NUMBER(value))));
+            }
+            _ => {
+                return Err(ParseError::InvalidOperator(format!(
+                    "Please enter valid operator {:?}",
+                    token
+                )))
+            }
+        };
+        let operands = self.pop_values(&format!("{:?}", token), 2)?;
+        let node = constructor(
+            Box::new(Node::NUMBER(operands[0])),
+            Box::new(Node::NUMBER(operands[1])),
+        );
+        self.push_eval(node)
+    }
+
+    fn execute_word(&mut self, word: &str) -> Result<(), ParseError> {
+        match word {
+            "swap" => {
+                let values = self.pop_values(word, 2)?;
+                self.stack.push(values[1]);
+                self.stack.push(values[0]);
+            }
+            "dup" => {
+                let value = self.pop_values(word, 1)?[0];
+                self.stack.push(value);
+                self.stack.push(value);
+            }
+            "drop" => {
+                self.pop_values(word, 1)?;
+            }
+            "clear" => self.stack.clear(),
+            NEGATE_WORD => {
+                let value = self.pop_values(word, 1)?[0];
+                self.push_eval(Node::NEGATIVE(Box::new(Node::NUMBER(value))))?;
+            }
+            // A constant pushes its value
+            _ if constants::builtin(word).is_some() => self.push_eval(Node::VARIABLE(word.to_string()))?,
+            _ => {
+                let arity = match functions::get_arity(word) {
+                    Some(arity) => arity,
+                    None => return Err(ParseError::UnableToParse(format!("Unknown word {}", word))),
+                };
+                let args = self.pop_values(word, arity)?;
+                let args = args.into_iter().map(Node::NUMBER).collect();
+                self.push_eval(Node::FUNCTION(word.to_string(), args))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Takes the last values of the stack keeping their order
+    fn pop_values(&mut self, operator: &str, count: usize) -> Result<Vec<f64>, ParseError> {
+        if self.stack.len() < count {
+            return Err(ParseError::UnableToParse(format!(
+                "The operator {} needs {} values in the stack",
+                operator, count
+            )));
+        }
+        Ok(self.stack.split_off(self.stack.len() - count))
+    }
+
+    // The operations are evaluated by the AST so both modes give the same results
+    fn push_eval(&mut self, node: Node) -> Result<(), ParseError> {
+        match ast::eval(&node).and_then(|value| Ok(value.as_number()?)) {
+            Ok(value) => {
+                self.stack.push(value);
+                Ok(())
+            }
+            Err(e) => Err(ParseError::UnableToParse(e.to_string())),
+        }
+    }
+}
+
+// Displays the stack like the stack calculators, one level per line with the top at the bottom
+impl fmt::Display for RpnCalculator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.stack.is_empty() {
+            return write!(f, "(empty stack)");
+        }
+        let depth = self.stack.len();
+        for (i, value) in self.stack.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}: {}", depth - i, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsemath::limits::Limits;
+    use crate::parsemath::parser::Parser;
+    use crate::parsemath::sexpr;
+
+    fn parse(expr: &str) -> Node {
+        let mut parser = Parser::new(expr).unwrap();
+        parser.parse().unwrap()
+    }
+
+    #[test]
+    fn test_rpn_print() {
+        assert_eq!(rpn_to_string(&to_rpn(&parse("1+2*3"))), "1 2 3 * +");
+        assert_eq!(rpn_to_string(&to_rpn(&parse("(1+2)*3"))), "1 2 + 3 *");
+        assert_eq!(rpn_to_string(&to_rpn(&parse("-max(1,2)^2"))), "1 2 max(2) neg 2 ^");
+        assert_eq!(rpn_to_string(&[Token::NUM(-5.0)]), "5 neg");
+        assert_eq!(rpn_to_string(&to_rpn(&parse("[1,x+1][0]+(1..3)"))), "[ 1 x 1 + ] 0 at 1 3 .. +");
+        assert_eq!(rpn_to_string(&to_rpn(&parse("3 m/(2 s) in km/h"))), "3 m 2 s / in km/h");
+    }
+
+    #[test]
+    fn test_rpn_parse() {
+        assert_eq!(parse_rpn("3 4 + 2 *").unwrap(), parse("(3+4)*2"));
+        assert_eq!(parse_rpn("1 2 3 - -").unwrap(), parse("1-(2-3)"));
+        assert_eq!(parse_rpn("16 sqrt neg").unwrap(), parse("-sqrt(16)"));
+        assert_eq!(parse_rpn("x 2 ^").unwrap(), parse("x^2"));
+        assert_eq!(parse_rpn("1 2 f(2) 3 max(2)").unwrap(), parse("max(f(1, 2), 3)"));
+        // A known unit after a number is a quantity even if it's also a function
+        assert_eq!(parse_rpn("1 5 min +").unwrap(), parse("1+5 min"));
+    }
+
+    #[test]
+    fn test_rpn_errors() {
+        assert!(parse_rpn("1 +").is_err());
+        assert!(parse_rpn("1 2").is_err());
+        assert!(parse_rpn("").is_err());
+        assert!(parse_rpn("1 sqrt sqrt max").is_err());
+        assert!(parse_rpn("1 ( 2 +").is_err());
+        assert!(parse_rpn("[ 1 2").is_err());
+        assert!(parse_rpn("1 [ + ]").is_err());
+        assert!(parse_rpn("1 in").is_err());
+        assert!(parse_rpn("1 f(").is_err());
+        assert!(parse_rpn("1 f(x)").is_err());
+        assert!(parse_rpn("1 f(2)").is_err());
+    }
+
+    #[test]
+    fn test_rpn_unreadable() {
+        // The lambdas, the calls that take a lambda, a built-in function used as a value and the
+        // parse errors are written but can't be read back, reading them is an error
+        let recovered = Parser::with_recovery("1+$", Limits::default()).unwrap().parse_recovering().0;
+        let unreadable = [
+            parse("x -> x+1"),
+            parse("map(x -> 2*x, [1, 2])"),
+            parse("fold((a, i) -> a+i, 0, 1, 3)"),
+            parse("map(sqrt, [4])"),
+            recovered,
+        ];
+        for ast in unreadable.iter() {
+            let tokens = to_rpn(ast);
+            assert!(from_rpn(&tokens).is_err(), "{:?}", ast);
+            assert!(parse_rpn(&rpn_to_string(&tokens)).is_err(), "{:?}", ast);
+        }
+    }
+
+    #[test]
+    fn test_rpn_round_trip() {
+        for expr in ["1+2*3-4", "(1+2)^-(3/4)", "2^3^4", "--1", "min(1,2)*abs(-3)", "[][0]*[1,[2]]+(1..x)[2]", "3 km+x in m", "50% of 80-(-x)%", "f(1)+g()*h(x, [2])", "sum(i, 1, 3, i^2)+map(square, [4])", "fold(add, 0, 1, 3)", "5 min", "1+5 min", "min(5 min, 1 h)"].iter() {
+            let ast = parse(expr);
+            assert_eq!(from_rpn(&to_rpn(&ast)).expect(expr), ast);
+            assert_eq!(parse_rpn(&rpn_to_string(&to_rpn(&ast))).unwrap(), ast);
+        }
+    }
+
+    #[test]
+    fn test_rpn_calculator_arithmetic() {
+        let mut calculator = RpnCalculator::new();
+        calculator.execute("3 4 + 2 *").unwrap();
+        assert_eq!(calculator.stack(), &[14.0]);
+        calculator.execute("2 ^ 4 sqrt neg 1 max").unwrap();
+        assert_eq!(calculator.stack(), &[196.0, 1.0]);
+        // The min after a number is still the function
+        calculator.execute("3 min").unwrap();
+        assert_eq!(calculator.stack(), &[196.0, 1.0]);
+        calculator.execute("15 % *").unwrap();
+        assert_eq!(calculator.stack(), &[196.0, 0.15]);
+        calculator.execute("clear 2 π *").unwrap();
+        assert_eq!(calculator.stack(), &[std::f64::consts::TAU]);
+    }
+
+    #[test]
+    fn test_rpn_calculator_commands() {
+        let mut calculator = RpnCalculator::new();
+        calculator.execute("1 2 swap").unwrap();
+        assert_eq!(calculator.stack(), &[2.0, 1.0]);
+        calculator.execute("dup").unwrap();
+        assert_eq!(calculator.stack(), &[2.0, 1.0, 1.0]);
+        calculator.execute("drop -").unwrap();
+        assert_eq!(calculator.stack(), &[1.0]);
+        calculator.execute("clear").unwrap();
+        assert_eq!(calculator.top(), None);
+    }
+
+    #[test]
+    fn test_rpn_calculator_error_keeps_stack() {
+        let mut calculator = RpnCalculator::new();
+        calculator.execute("1 2").unwrap();
+        assert!(calculator.execute("3 + + +").is_err());
+        assert_eq!(calculator.stack(), &[1.0, 2.0]);
+        assert!(calculator.execute("foo").is_err());
+        assert!(calculator.execute("(").is_err());
+        assert_eq!(calculator.stack(), &[1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_rpn_calculator_display() {
+        let mut calculator = RpnCalculator::new();
+        assert_eq!(calculator.to_string(), "(empty stack)");
+        calculator.execute("5 7").unwrap();
+        assert_eq!(calculator.to_string(), "2: 5\n1: 7");
+    }
+
+    #[test]
+    fn test_rpn_notations_round_trip() {
+        // infix -> S-expression -> RPN -> infix gives back the same tree
+        for expr in ["1-2-3", "1-(2-3)", "-(2^2)*sqrt(9)/4", "max(1+2,3)^0.5"].iter() {
+            let ast = parse(expr);
+            let from_sexpr = sexpr::parse_sexpr(&sexpr::to_sexpr(&ast)).unwrap();
+            let from_rpn = parse_rpn(&rpn_to_string(&to_rpn(&from_sexpr))).unwrap();
+            let infix = parse(&from_rpn.to_string());
+            assert_eq!(infix, ast, "{}", expr);
+        }
+    }
+}
@@ -0,0 +1,667 @@
+//! This module holds the **serialization** of the tokens, the AST and the errors, is compiled
+//! with the `serde` cargo feature. The shape of the data doesn't follow the enum layout, every
+//! value is an object tagged by its kind so the format stays the same if the enums change:
+//!
+//! * `Token` - `{"token": "ADD"}`, `{"token": "ASSIGN"}`, `{"token": "NUM", "value": 5.0}`, `{"token": "IDENT", "name": "sqrt"}`,
+//!   `{"token": "SYMBOL", "symbol": "@"}`, `{"token": "IN"}`, `{"token": "PERCENT"}`,
+//!   `{"token": "UNIT", "unit": "km/h"}`, `{"token": "DATE", "date": "2026-10-16"}`
+//! * `OperPrec` - the name of the variant, `"ADDSUB"`
+//! * `Node` - `{"node": "add", "left": {..}, "right": {..}}` for `add`, `subtract`, `multiply`,
+//!   `divide` and `power`, `{"node": "negative", "operand": {..}}`, `{"node": "percent", "operand": {..}}`,
+//!   `{"node": "function", "name": "max", "args": [..]}`, `{"node": "variable", "name": "x"}`,
+//!   `{"node": "number", "value": 5.0}`, `{"node": "lambda", "parameters": ["x"], "body": {..}}`,
+//!   `{"node": "list", "items": [..]}`, `{"node": "index", "list": {..}, "index": {..}}`,
+//!   `{"node": "range", "from": {..}, "to": {..}}`, `{"node": "quantity", "value": 3.0, "unit": "km"}`,
+//!   `{"node": "convert", "expr": {..}, "unit": "m"}`, `{"node": "date", "value": "2026-10-16T14:30"}`
+//!   and `{"node": "error"}`
+//! * `ParseError` - `{"error": "UnableToParse", "message": ".."}` and
+//!   `{"error": "LimitExceeded", "limit": "TooDeep", "max": 1024}`
+//! * `EvalError` - `{"error": "UnknownFunction", "name": ".."}` and
+//!   `{"error": "WrongArgumentCount", "name": "..", "expected": 1, "found": 2}`,
+//!   `{"error": "UnknownVariable", "name": ".."}`,
+//!   `{"error": "WrongVariableCount", "expected": 1, "found": 2}`,
+//!   `{"error": "WrongColumnLength", "name": "..", "expected": 1, "found": 2}`,
+//!   `{"error": "LimitExceeded", "limit": "TooManySteps", "max": 1000}`,
+//!   `{"error": "SyntaxError"}`, `{"error": "ConstantAssignment", "name": ".."}` and
+//!   `{"error": "TypeMismatch", "expected": "a number", "found": "a function"}`,
+//!   `{"error": "IndexOutOfRange", "index": 3.0, "length": 2}`,
+//!   `{"error": "LengthMismatch", "left": 3, "right": 2}`,
+//!   `{"error": "ShapeMismatch", "operation": "multiply", "left": [2, 3], "right": [2, 3]}`,
+//!   `{"error": "NotSquare", "operation": "determinant", "shape": [1, 2]}`,
+//!   `{"error": "SingularMatrix"}`, `{"error": "DimensionMismatch", "left": "m", "right": "s"}` and
+//!   `{"error": "UnknownUnit", "unit": ".."}`, `{"error": "InvalidConstant", "name": ".."}`,
+//!   `{"error": "UnsupportedFunction", "name": ".."}`
+//!
+//! To store a value the `Versioned` wrapper adds the format version, `{"version": 1, "data": ..}`,
+//! and refuses to read a version that it doesn't know. A new kind of token, node or error is only
+//! a new tag, so it keeps the version: the documents written before are still read, but a
+//! reader without the new kind rejects a document that uses it. The numbers must be finite
+//! because JSON has no representation for `NaN` or the infinities
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{
+    ast::{EvalError, Node},
+    datetime::DateTime,
+    limits::LimitError,
+    parser::ParseError,
+    token::{OperPrec, Token},
+};
+
+/// The version of the serialized format, it changes when the shape of an existing kind changes.
+/// Adding a kind is backward-compatible only, the older readers fail with an unknown variant
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The Versioned struct wraps a value with the version of the format used to serialize it
+/// # Arguments
+/// * `version: u32` - the format version, always `FORMAT_VERSION` for the values created here
+/// * `data: T` - the serialized value
+/// # Example
+/// ```
+/// let ast = Parser::new("1+2").unwrap().parse().unwrap();
+/// let json = serde_json::to_string(&Versioned::new(ast)).unwrap();
+/// // {"version":1,"data":{"node":"add","left":{"node":"number","value":1.0},...}}
+/// let stored: Versioned<Node> = serde_json::from_str(&json).unwrap();
+/// ```
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RawVersioned<T>", bound(deserialize = "T: Deserialize<'de>"))]
+pub struct Versioned<T> {
+    pub version: u32,
+    pub data: T,
+}
+
+impl<T> Versioned<T> {
+    /// Returns a value wrapped with the current format version
+    /// # Arguments
+    /// * `data: T` - the value to serialize
+    pub fn new(data: T) -> Self {
+        Versioned {
+            version: FORMAT_VERSION,
+            data,
+        }
+    }
+}
+
+// The wrapper as is read, before checking the version
+#[derive(Deserialize)]
+struct RawVersioned<T> {
+    version: u32,
+    data: T,
+}
+
+/// The error returned when a stored value has a format version that isn't supported
+#[derive(Debug, PartialEq)]
+pub struct UnsupportedVersion(pub u32);
+
+impl fmt::Display for UnsupportedVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Unsupported format version {}, expected {}",
+            self.0, FORMAT_VERSION
+        )
+    }
+}
+
+impl<T> TryFrom<RawVersioned<T>> for Versioned<T> {
+    type Error = UnsupportedVersion;
+
+    fn try_from(raw: RawVersioned<T>) -> Result<Self, Self::Error> {
+        if raw.version != FORMAT_VERSION {
+            return Err(UnsupportedVersion(raw.version));
+        }
+        Ok(Versioned {
+            version: raw.version,
+            data: raw.data,
+        })
+    }
+}
+
+// The shapes of the values, the ones used to write borrow the data and the ones used to read
+// own it
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "token")]
+enum TokenRepr {
+    ADD,
+    SUBTRACT,
+    MULTIPLY,
+    DIVIDE,
+    CARET,
+    LEFTPAREN,
+    RIGHTPAREN,
+    LEFTBRACKET,
+    RIGHTBRACKET,
+    COMMA,
+    ASSIGN,
+    SEMICOLON,
+    ARROW,
+    RANGE,
+    IN,
+    PERCENT,
+    NUM { value: f64 },
+    IDENT { name: String },
+    UNIT { unit: String },
+    SYMBOL { symbol: String },
+    DATE { date: String },
+    EOF,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "node", rename_all = "lowercase")]
+enum NodeOut<'a> {
+    Add { left: &'a Node, right: &'a Node },
+    Subtract { left: &'a Node, right: &'a Node },
+    Multiply { left: &'a Node, right: &'a Node },
+    Divide { left: &'a Node, right: &'a Node },
+    Power { left: &'a Node, right: &'a Node },
+    Negative { operand: &'a Node },
+    Percent { operand: &'a Node },
+    Function { name: &'a str, args: &'a [Node] },
+    Variable { name: &'a str },
+    Number { value: f64 },
+    Lambda { parameters: &'a [String], body: &'a Node },
+    List { items: &'a [Node] },
+    Index { list: &'a Node, index: &'a Node },
+    Range { from: &'a Node, to: &'a Node },
+    Quantity { value: f64, unit: &'a str },
+    Convert { expr: &'a Node, unit: &'a str },
+    Date { value: String },
+    Error,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "node", rename_all = "lowercase")]
+enum NodeIn {
+    Add { left: Box<Node>, right: Box<Node> },
+    Subtract { left: Box<Node>, right: Box<Node> },
+    Multiply { left: Box<Node>, right: Box<Node> },
+    Divide { left: Box<Node>, right: Box<Node> },
+    Power { left: Box<Node>, right: Box<Node> },
+    Negative { operand: Box<Node> },
+    Percent { operand: Box<Node> },
+    Function { name: String, args: Vec<Node> },
+    Variable { name: String },
+    Number { value: f64 },
+    Lambda { parameters: Vec<String>, body: Box<Node> },
+    List { items: Vec<Node> },
+    Index { list: Box<Node>, index: Box<Node> },
+    Range { from: Box<Node>, to: Box<Node> },
+    Quantity { value: f64, unit: String },
+    Convert { expr: Box<Node>, unit: String },
+    Date { value: String },
+    Error,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "error")]
+enum ParseErrorRepr {
+    UnableToParse { message: String },
+    InvalidOperator { message: String },
+    LimitExceeded { limit: String, max: usize },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "error")]
+enum EvalErrorRepr {
+    UnknownFunction { name: String },
+    WrongArgumentCount { name: String, expected: usize, found: usize },
+    UnknownVariable { name: String },
+    WrongVariableCount { expected: usize, found: usize },
+    WrongColumnLength { name: String, expected: usize, found: usize },
+    LimitExceeded { limit: String, max: usize },
+    SyntaxError,
+    ConstantAssignment { name: String },
+    TypeMismatch { expected: String, found: String },
+    IndexOutOfRange { index: f64, length: usize },
+    LengthMismatch { left: usize, right: usize },
+    ShapeMismatch { operation: String, left: (usize, usize), right: (usize, usize) },
+    NotSquare { operation: String, shape: (usize, usize) },
+    SingularMatrix,
+    DimensionMismatch { left: String, right: String },
+    UnknownUnit { unit: String },
+    InvalidConstant { name: String },
+    UnsupportedFunction { name: String },
+}
+
+impl Serialize for Token {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr = match self {
+            Token::ADD => TokenRepr::ADD,
+            Token::SUBTRACT => TokenRepr::SUBTRACT,
+            Token::MULTIPLY => TokenRepr::MULTIPLY,
+            Token::DIVIDE => TokenRepr::DIVIDE,
+            Token::CARET => TokenRepr::CARET,
+            Token::LEFTPAREN => TokenRepr::LEFTPAREN,
+            Token::RIGHTPAREN => TokenRepr::RIGHTPAREN,
+            Token::LEFTBRACKET => TokenRepr::LEFTBRACKET,
+            Token::RIGHTBRACKET => TokenRepr::RIGHTBRACKET,
+            Token::COMMA => TokenRepr::COMMA,
+            Token::ASSIGN => TokenRepr::ASSIGN,
+            Token::SEMICOLON => TokenRepr::SEMICOLON,
+            Token::ARROW => TokenRepr::ARROW,
+            Token::RANGE => TokenRepr::RANGE,
+            Token::IN => TokenRepr::IN,
+            Token::PERCENT => TokenRepr::PERCENT,
+            Token::NUM(value) => TokenRepr::NUM { value: *value },
+            Token::IDENT(name) => TokenRepr::IDENT { name: name.clone() },
+            Token::UNIT(unit) => TokenRepr::UNIT { unit: unit.clone() },
+            Token::SYMBOL(symbol) => TokenRepr::SYMBOL {
+                symbol: symbol.clone(),
+            },
+            Token::DATE(date) => TokenRepr::DATE { date: date.to_string() },
+            Token::EOF => TokenRepr::EOF,
+        };
+        repr.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Token {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let token = match TokenRepr::deserialize(deserializer)? {
+            TokenRepr::ADD => Token::ADD,
+            TokenRepr::SUBTRACT => Token::SUBTRACT,
+            TokenRepr::MULTIPLY => Token::MULTIPLY,
+            TokenRepr::DIVIDE => Token::DIVIDE,
+            TokenRepr::CARET => Token::CARET,
+            TokenRepr::LEFTPAREN => Token::LEFTPAREN,
+            TokenRepr::RIGHTPAREN => Token::RIGHTPAREN,
+            TokenRepr::LEFTBRACKET => Token::LEFTBRACKET,
+            TokenRepr::RIGHTBRACKET => Token::RIGHTBRACKET,
+            TokenRepr::COMMA => Token::COMMA,
+            TokenRepr::ASSIGN => Token::ASSIGN,
+            TokenRepr::SEMICOLON => Token::SEMICOLON,
+            TokenRepr::ARROW => Token::ARROW,
+            TokenRepr::RANGE => Token::RANGE,
+            TokenRepr::IN => Token::IN,
+            TokenRepr::PERCENT => Token::PERCENT,
+            TokenRepr::NUM { value } => Token::NUM(value),
+            TokenRepr::IDENT { name } => Token::IDENT(name),
+            TokenRepr::UNIT { unit } => Token::UNIT(unit),
+            TokenRepr::SYMBOL { symbol } => Token::SYMBOL(symbol),
+            TokenRepr::DATE { date } => Token::DATE(read_date(&date)?),
+            TokenRepr::EOF => Token::EOF,
+        };
+        Ok(token)
+    }
+}
+
+impl Serialize for OperPrec {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let name = match self {
+            OperPrec::DEFAULTZERO => "DEFAULTZERO",
+            OperPrec::ADDSUB => "ADDSUB",
+            OperPrec::MULDIV => "MULDIV",
+            OperPrec::POWER => "POWER",
+            OperPrec::NEGATIVE => "NEGATIVE",
+        };
+        serializer.serialize_str(name)
+    }
+}
+
+impl<'de> Deserialize<'de> for OperPrec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        match name.as_str() {
+            "DEFAULTZERO" => Ok(OperPrec::DEFAULTZERO),
+            "ADDSUB" => Ok(OperPrec::ADDSUB),
+            "MULDIV" => Ok(OperPrec::MULDIV),
+            "POWER" => Ok(OperPrec::POWER),
+            "NEGATIVE" => Ok(OperPrec::NEGATIVE),
+            _ => Err(serde::de::Error::unknown_variant(
+                &name,
+                &["DEFAULTZERO", "ADDSUB", "MULDIV", "POWER", "NEGATIVE"],
+            )),
+        }
+    }
+}
+
+impl Serialize for Node {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr = match self {
+            Node::ADD(left, right) => NodeOut::Add { left, right },
+            Node::SUBTRACT(left, right) => NodeOut::Subtract { left, right },
+            Node::MULTIPLY(left, right) => NodeOut::Multiply { left, right },
+            Node::DIVIDE(left, right) => NodeOut::Divide { left, right },
+            Node::CARRET(left, right) => NodeOut::Power { left, right },
+            Node::NEGATIVE(operand) => NodeOut::Negative { operand },
+            Node::PERCENT(operand) => NodeOut::Percent { operand },
+            Node::FUNCTION(name, args) => NodeOut::Function { name, args },
+            Node::VARIABLE(name) => NodeOut::Variable { name },
+            Node::NUMBER(value) => NodeOut::Number { value: *value },
+            Node::LAMBDA(parameters, body) => NodeOut::Lambda { parameters, body },
+            Node::LIST(items) => NodeOut::List { items },
+            Node::INDEX(list, index) => NodeOut::Index { list, index },
+            Node::RANGE(from, to) => NodeOut::Range { from, to },
+            Node::QUANTITY(value, unit) => NodeOut::Quantity { value: *value, unit },
+            Node::CONVERT(expr, unit) => NodeOut::Convert { expr, unit },
+            Node::DATE(date) => NodeOut::Date { value: date.to_string() },
+            Node::ERROR => NodeOut::Error,
+        };
+        repr.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let node = match NodeIn::deserialize(deserializer)? {
+            NodeIn::Add { left, right } => Node::ADD(left, right),
+            NodeIn::Subtract { left, right } => Node::SUBTRACT(left, right),
+            NodeIn::Multiply { left, right } => Node::MULTIPLY(left, right),
+            NodeIn::Divide { left, right } => Node::DIVIDE(left, right),
+            NodeIn::Power { left, right } => Node::CARRET(left, right),
+            NodeIn::Negative { operand } => Node::NEGATIVE(operand),
+            NodeIn::Percent { operand } => Node::PERCENT(operand),
+            NodeIn::Function { name, args } => Node::FUNCTION(name, args),
+            NodeIn::Variable { name } => Node::VARIABLE(name),
+            NodeIn::Number { value } => Node::NUMBER(value),
+            NodeIn::Lambda { parameters, body } => Node::LAMBDA(parameters, body),
+            NodeIn::List { items } => Node::LIST(items),
+            NodeIn::Index { list, index } => Node::INDEX(list, index),
+            NodeIn::Range { from, to } => Node::RANGE(from, to),
+            NodeIn::Quantity { value, unit } => Node::QUANTITY(value, unit),
+            NodeIn::Convert { expr, unit } => Node::CONVERT(expr, unit),
+            NodeIn::Date { value } => Node::DATE(read_date(&value)?),
+            NodeIn::Error => Node::ERROR,
+        };
+        Ok(node)
+    }
+}
+
+// The dates are written like the tokenizer reads them, a day that doesn't exist is an error
+fn read_date<E: serde::de::Error>(text: &str) -> Result<DateTime, E> {
+    DateTime::parse(text).ok_or_else(|| E::custom(format!("invalid date {}", text)))
+}
+
+impl Serialize for ParseError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr = match self {
+            ParseError::UnableToParse(message) => ParseErrorRepr::UnableToParse {
+                message: message.clone(),
+            },
+            ParseError::InvalidOperator(message) => ParseErrorRepr::InvalidOperator {
+                message: message.clone(),
+            },
+            ParseError::LimitExceeded(error) => {
+                let (limit, max) = limit_repr(error);
+                ParseErrorRepr::LimitExceeded { limit, max }
+            }
+        };
+        repr.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ParseError {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let error = match ParseErrorRepr::deserialize(deserializer)? {
+            ParseErrorRepr::UnableToParse { message } => ParseError::UnableToParse(message),
+            ParseErrorRepr::InvalidOperator { message } => ParseError::InvalidOperator(message),
+            ParseErrorRepr::LimitExceeded { limit, max } => {
+                ParseError::LimitExceeded(limit_from_repr(&limit, max)?)
+            }
+        };
+        Ok(error)
+    }
+}
+
+impl Serialize for EvalError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr = match self {
+            EvalError::UnknownFunction(name) => EvalErrorRepr::UnknownFunction { name: name.clone() },
+            EvalError::WrongArgumentCount(name, expected, found) => {
+                EvalErrorRepr::WrongArgumentCount {
+                    name: name.clone(),
+                    expected: *expected,
+                    found: *found,
+                }
+            }
+            EvalError::UnknownVariable(name) => EvalErrorRepr::UnknownVariable { name: name.clone() },
+            EvalError::WrongVariableCount(expected, found) => EvalErrorRepr::WrongVariableCount {
+                expected: *expected,
+                found: *found,
+            },
+            EvalError::WrongColumnLength(name, expected, found) => {
+                EvalErrorRepr::WrongColumnLength {
+                    name: name.clone(),
+                    expected: *expected,
+                    found: *found,
+                }
+            }
+            EvalError::LimitExceeded(error) => {
+                let (limit, max) = limit_repr(error);
+                EvalErrorRepr::LimitExceeded { limit, max }
+            }
+            EvalError::SyntaxError => EvalErrorRepr::SyntaxError,
+            EvalError::ConstantAssignment(name) => EvalErrorRepr::ConstantAssignment { name: name.clone() },
+            EvalError::TypeMismatch(expected, found) => EvalErrorRepr::TypeMismatch {
+                expected: expected.clone(),
+                found: found.clone(),
+            },
+            EvalError::IndexOutOfRange(index, length) => EvalErrorRepr::IndexOutOfRange {
+                index: *index,
+                length: *length,
+            },
+            EvalError::LengthMismatch(left, right) => EvalErrorRepr::LengthMismatch {
+                left: *left,
+                right: *right,
+            },
+            EvalError::ShapeMismatch(operation, left, right) => EvalErrorRepr::ShapeMismatch {
+                operation: operation.clone(),
+                left: *left,
+                right: *right,
+            },
+            EvalError::NotSquare(operation, shape) => EvalErrorRepr::NotSquare {
+                operation: operation.clone(),
+                shape: *shape,
+            },
+            EvalError::SingularMatrix => EvalErrorRepr::SingularMatrix,
+            EvalError::DimensionMismatch(left, right) => EvalErrorRepr::DimensionMismatch {
+                left: left.clone(),
+                right: right.clone(),
+            },
+            EvalError::UnknownUnit(unit) => EvalErrorRepr::UnknownUnit { unit: unit.clone() },
+            EvalError::InvalidConstant(name) => EvalErrorRepr::InvalidConstant { name: name.clone() },
+            EvalError::UnsupportedFunction(name) => EvalErrorRepr::UnsupportedFunction { name: name.clone() },
+        };
+        repr.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for EvalError {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let error = match EvalErrorRepr::deserialize(deserializer)? {
+            EvalErrorRepr::UnknownFunction { name } => EvalError::UnknownFunction(name),
+            EvalErrorRepr::WrongArgumentCount {
+                name,
+                expected,
+                found,
+            } => EvalError::WrongArgumentCount(name, expected, found),
+            EvalErrorRepr::UnknownVariable { name } => EvalError::UnknownVariable(name),
+            EvalErrorRepr::WrongVariableCount { expected, found } => {
+                EvalError::WrongVariableCount(expected, found)
+            }
+            EvalErrorRepr::WrongColumnLength {
+                name,
+                expected,
+                found,
+            } => EvalError::WrongColumnLength(name, expected, found),
+            EvalErrorRepr::LimitExceeded { limit, max } => {
+                EvalError::LimitExceeded(limit_from_repr(&limit, max)?)
+            }
+            EvalErrorRepr::SyntaxError => EvalError::SyntaxError,
+            EvalErrorRepr::ConstantAssignment { name } => EvalError::ConstantAssignment(name),
+            EvalErrorRepr::TypeMismatch { expected, found } => EvalError::TypeMismatch(expected, found),
+            EvalErrorRepr::IndexOutOfRange { index, length } => EvalError::IndexOutOfRange(index, length),
+            EvalErrorRepr::LengthMismatch { left, right } => EvalError::LengthMismatch(left, right),
+            EvalErrorRepr::ShapeMismatch { operation, left, right } => {
+                EvalError::ShapeMismatch(operation, left, right)
+            }
+            EvalErrorRepr::NotSquare { operation, shape } => EvalError::NotSquare(operation, shape),
+            EvalErrorRepr::SingularMatrix => EvalError::SingularMatrix,
+            EvalErrorRepr::DimensionMismatch { left, right } => EvalError::DimensionMismatch(left, right),
+            EvalErrorRepr::UnknownUnit { unit } => EvalError::UnknownUnit(unit),
+            EvalErrorRepr::InvalidConstant { name } => EvalError::InvalidConstant(name),
+            EvalErrorRepr::UnsupportedFunction { name } => EvalError::UnsupportedFunction(name),
+        };
+        Ok(error)
+    }
+}
+
+// The limit errors are written inside the parse and eval errors as the name of the limit and
+// its maximum value
+fn limit_repr(error: &LimitError) -> (String, usize) {
+    let (limit, max) = match *error {
+        LimitError::InputTooLong(max) => ("InputTooLong", max),
+        LimitError::TooDeep(max) => ("TooDeep", max),
+        LimitError::TooManyNodes(max) => ("TooManyNodes", max),
+        LimitError::TooManySteps(max) => ("TooManySteps", max),
+        LimitError::TooDeepRecursion(max) => ("TooDeepRecursion", max),
+    };
+    (limit.to_string(), max)
+}
+
+fn limit_from_repr<E: serde::de::Error>(limit: &str, max: usize) -> Result<LimitError, E> {
+    match limit {
+        "InputTooLong" => Ok(LimitError::InputTooLong(max)),
+        "TooDeep" => Ok(LimitError::TooDeep(max)),
+        "TooManyNodes" => Ok(LimitError::TooManyNodes(max)),
+        "TooManySteps" => Ok(LimitError::TooManySteps(max)),
+        "TooDeepRecursion" => Ok(LimitError::TooDeepRecursion(max)),
+        _ => Err(E::unknown_variant(
+            limit,
+            &["InputTooLong", "TooDeep", "TooManyNodes", "TooManySteps", "TooDeepRecursion"],
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsemath::parser::Parser;
+    use crate::parsemath::tokenizer::Tokenizer;
+
+    fn parse(expr: &str) -> Node {
+        let mut parser = Parser::new(expr).unwrap();
+        parser.parse().unwrap()
+    }
+
+    #[test]
+    fn test_serialization_node_shape() {
+        let json = serde_json::to_value(parse("-max(1,2)^3")).unwrap();
+        let expected = serde_json::json!({
+            "node": "power",
+            "left": {
+                "node": "negative",
+                "operand": {
+                    "node": "function",
+                    "name": "max",
+                    "args": [{"node": "number", "value": 1.0}, {"node": "number", "value": 2.0}]
+                }
+            },
+            "right": {"node": "number", "value": 3.0}
+        });
+        assert_eq!(json, expected);
+    }
+
+    #[test]
+    fn test_serialization_node_round_trip() {
+        let ast = parse("(1+x)*3-4/5^-sqrt(rate)+map((a, b) -> a*b, 2)+sum([1, x][0]*(1..12))+3 km/h in m/s-50% of x%+(2026-10-16T14:30-2026-10-01)");
+        let json = serde_json::to_string(&Versioned::new(ast.clone())).unwrap();
+        let stored: Versioned<Node> = serde_json::from_str(&json).unwrap();
+        assert_eq!(stored, Versioned::new(ast));
+        assert!(serde_json::from_str::<Node>(r#"{"node":"date","value":"2026-02-30"}"#).is_err());
+    }
+
+    #[test]
+    fn test_serialization_token_round_trip() {
+        let mut tokenizer = Tokenizer::new("max(1.5,2)^3-x+2026-10-16");
+        let mut tokens = Vec::new();
+        while let Some(token) = tokenizer.next() {
+            tokens.push(token.clone());
+            if token == Token::EOF {
+                break;
+            }
+        }
+        let json = serde_json::to_string(&tokens).unwrap();
+        assert!(json.starts_with(r#"[{"token":"IDENT","name":"max"},{"token":"LEFTPAREN"}"#));
+        let read: Vec<Token> = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, tokens);
+    }
+
+    #[test]
+    fn test_serialization_oper_prec_round_trip() {
+        let json = serde_json::to_string(&OperPrec::MULDIV).unwrap();
+        assert_eq!(json, r#""MULDIV""#);
+        let read: OperPrec = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, OperPrec::MULDIV);
+    }
+
+    #[test]
+    fn test_serialization_errors_round_trip() {
+        let error = EvalError::WrongArgumentCount("sqrt".into(), 1, 2);
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(
+            json,
+            r#"{"error":"WrongArgumentCount","name":"sqrt","expected":1,"found":2}"#
+        );
+        assert_eq!(serde_json::from_str::<EvalError>(&json).unwrap(), error);
+
+        let error = ParseError::InvalidOperator("Invalid character".into());
+        let json = serde_json::to_string(&error).unwrap();
+        let read: ParseError = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.to_string(), error.to_string());
+
+        let error = EvalError::LimitExceeded(LimitError::TooManySteps(1000));
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(json, r#"{"error":"LimitExceeded","limit":"TooManySteps","max":1000}"#);
+        assert_eq!(serde_json::from_str::<EvalError>(&json).unwrap(), error);
+
+        let error = EvalError::ShapeMismatch("multiply".into(), (2, 3), (2, 3));
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(
+            json,
+            r#"{"error":"ShapeMismatch","operation":"multiply","left":[2,3],"right":[2,3]}"#
+        );
+        assert_eq!(serde_json::from_str::<EvalError>(&json).unwrap(), error);
+
+        let error = EvalError::DimensionMismatch("m".into(), "s".into());
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(json, r#"{"error":"DimensionMismatch","left":"m","right":"s"}"#);
+        assert_eq!(serde_json::from_str::<EvalError>(&json).unwrap(), error);
+
+        let error = EvalError::InvalidConstant("sqrt".into());
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(json, r#"{"error":"InvalidConstant","name":"sqrt"}"#);
+        assert_eq!(serde_json::from_str::<EvalError>(&json).unwrap(), error);
+
+        let error = EvalError::UnsupportedFunction("sum".into());
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(json, r#"{"error":"UnsupportedFunction","name":"sum"}"#);
+        assert_eq!(serde_json::from_str::<EvalError>(&json).unwrap(), error);
+    }
+
+    #[test]
+    fn test_serialization_unsupported_version() {
+        let json = r#"{"version":2,"data":{"node":"number","value":1.0}}"#;
+        let error = serde_json::from_str::<Versioned<Node>>(json).unwrap_err();
+        assert!(error.to_string().contains("Unsupported format version 2"));
+    }
+
+    #[test]
+    fn test_serialization_new_kinds_keep_version() {
+        // A document written before the lambdas is still read and a lambda uses the same version
+        let json = r#"{"version":1,"data":{"node":"add","left":{"node":"variable","name":"x"},"right":{"node":"number","value":1.0}}}"#;
+        let stored = serde_json::from_str::<Versioned<Node>>(json).unwrap();
+        assert_eq!(stored.data, Parser::new("x+1").unwrap().parse().unwrap());
+        let lambda = Parser::new("x -> x+1").unwrap().parse().unwrap();
+        let json = serde_json::to_string(&Versioned::new(lambda)).unwrap();
+        assert!(json.starts_with(r#"{"version":1,"data":{"node":"lambda""#));
+        // A kind that this reader doesn't know is an unknown variant
+        let json = r#"{"version":1,"data":{"node":"matrix","rows":[]}}"#;
+        let error = serde_json::from_str::<Versioned<Node>>(json).unwrap_err();
+        assert!(error.to_string().contains("unknown variant `matrix`"));
+    }
+}
@@ -0,0 +1,322 @@
+//! This module holds the conversions between the AST and **S-expressions**, the prefix notation
+//! used by Lisp where every operation is a list with the operator first, `(+ 1 (* 2 3))`.
+//! A list with a single operand for `-` is a negation, `(- 1)`, and a list with a name first is
+//! a function call, `(max 1 2)`. A name alone is a variable and a lambda is a list with an arrow,
+//! the list of its parameters and its body, `(-> (x y) (+ x y))`. A list of values is written
+//! between brackets, `[1 2 3]`, an index has empty brackets first, `([] v 0)`, and a range has
+//! its dots first, `(.. 1 10)`. A quantity is written like in infix, `3 km`, and a conversion
+//! has the keyword and the unit first, `(in km (+ x 1 m))`. A percentage is a list with the
+//! percent sign and a single operand, `(% 15)`
+
+use super::{
+    ast::Node,
+    parser::ParseError,
+    token::Token,
+    tokenizer::Tokenizer,
+};
+
+/// Converts an AST to an S-expression
+/// # Arguments
+/// * `node: &Node` - the root of the AST
+/// # Returns
+/// * `String` - the S-expression, negative numbers are written as negations `(- 5)`
+/// # Example
+/// ```
+/// let mut parser = Parser::new("1+2*3").unwrap();
+/// let sexpr = to_sexpr(&parser.parse().unwrap());
+/// // This will be (+ 1 (* 2 3))
+/// ```
+pub fn to_sexpr(node: &Node) -> String {
+    let mut out = String::new();
+    write_sexpr(&mut out, node);
+    out
+}
+
+fn write_sexpr(out: &mut String, node: &Node) {
+    use super::ast::Node::*;
+    let head = match node {
+        NUMBER(value) if *value < 0.0 => {
+            out.push_str(&format!("(- {})", -value));
+            return;
+        }
+        NUMBER(value) => {
+            out.push_str(&value.to_string());
+            return;
+        }
+        QUANTITY(value, unit) => {
+            out.push_str(&format!("{} {}", value, unit));
+            return;
+        }
+        DATE(date) => {
+            out.push_str(&date.to_string());
+            return;
+        }
+        CONVERT(expr, unit) => {
+            out.push_str(&format!("(in {} ", unit));
+            write_sexpr(out, expr);
+            out.push(')');
+            return;
+        }
+        ADD(..) => "+",
+        SUBTRACT(..) | NEGATIVE(..) => "-",
+        MULTIPLY(..) => "*",
+        DIVIDE(..) => "/",
+        CARRET(..) => "^",
+        VARIABLE(name) => {
+            out.push_str(name);
+            return;
+        }
+        ERROR => {
+            out.push('?');
+            return;
+        }
+        LAMBDA(parameters, body) => {
+            out.push_str(&format!("(-> ({}) ", parameters.join(" ")));
+            write_sexpr(out, body);
+            out.push(')');
+            return;
+        }
+        LIST(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(' ');
+                }
+                write_sexpr(out, item);
+            }
+            out.push(']');
+            return;
+        }
+        INDEX(..) => "[]",
+        PERCENT(..) => "%",
+        RANGE(..) => "..",
+        FUNCTION(name, _) => name,
+    };
+    out.push('(');
+    out.push_str(head);
+    for child in node.children() {
+        out.push(' ');
+        write_sexpr(out, child);
+    }
+    out.push(')');
+}
+
+/// Parses an S-expression into an AST
+/// # Arguments
+/// * `expr: &str` - the S-expression, the operators can take more than two operands and are
+///   grouped from the left like the infix parser does, `(- 1 2 3)` is `1-2-3`
+/// # Returns
+/// * `Result<Node, ParseError>` - the root of the AST or an error if the expression is malformed
+/// # Example
+/// ```
+/// let ast = parse_sexpr("(+ 1 (* 2 3))").unwrap();
+/// // The ast will be ADD(NUMBER(1.0), MULTIPLY(NUMBER(2.0), NUMBER(3.0)))
+/// ```
+pub fn parse_sexpr(expr: &str) -> Result<Node, ParseError> {
+    let tokens = Tokenizer::new(expr).into_tokens()?;
+    let mut position = 0;
+    let node = parse_item(&tokens, &mut position)?;
+    // All the input must belong to a single expression
+    if position != tokens.len() {
+        return Err(ParseError::UnableToParse(format!(
+            "Unexpected {:?} after the expression",
+            tokens[position]
+        )));
+    }
+    Ok(node)
+}
+
+fn parse_item(tokens: &[Token], position: &mut usize) -> Result<Node, ParseError> {
+    match tokens.get(*position) {
+        Some(Token::NUM(value)) => {
+            *position += 1;
+            // The tokenizer reads a unit after a number, they are a quantity
+            if let Some(Token::UNIT(unit)) = tokens.get(*position) {
+                *position += 1;
+                return Ok(Node::QUANTITY(*value, unit.clone()));
+            }
+            Ok(Node::NUMBER(*value))
+        }
+        Some(Token::DATE(date)) => {
+            *position += 1;
+            Ok(Node::DATE(*date))
+        }
+        Some(Token::IDENT(name)) => {
+            *position += 1;
+            Ok(Node::VARIABLE(name.clone()))
+        }
+        Some(Token::LEFTBRACKET) => {
+            *position += 1;
+            let items = parse_until(tokens, position, Token::RIGHTBRACKET)?;
+            Ok(Node::LIST(items))
+        }
+        Some(Token::LEFTPAREN) => {
+            *position += 1;
+            let head = match tokens.get(*position) {
+                Some(token) => token.clone(),
+                None => return Err(ParseError::UnableToParse("Unexpected end of input".into())),
+            };
+            *position += 1;
+            // The head of an index are the two brackets
+            if head == Token::LEFTBRACKET {
+                if tokens.get(*position) != Some(&Token::RIGHTBRACKET) {
+                    return Err(ParseError::InvalidOperator("Expected RIGHTBRACKET".into()));
+                }
+                *position += 1;
+                let operands = parse_until(tokens, position, Token::RIGHTPAREN)?;
+                return build_pair(&head, operands, Node::INDEX);
+            }
+            // The unit of a conversion goes before the value
+            if head == Token::IN {
+                let unit = match tokens.get(*position) {
+                    Some(Token::UNIT(unit)) => unit.clone(),
+                    _ => return Err(ParseError::UnableToParse("Expected a unit after in".into())),
+                };
+                *position += 1;
+                let mut operands = parse_until(tokens, position, Token::RIGHTPAREN)?;
+                if operands.len() != 1 {
+                    return Err(ParseError::UnableToParse("A conversion needs a single value".into()));
+                }
+                return Ok(Node::CONVERT(Box::new(operands.remove(0)), unit));
+            }
+            let operands = parse_until(tokens, position, Token::RIGHTPAREN)?;
+            build_list(head, operands)
+        }
+        Some(token) => Err(ParseError::UnableToParse(format!("Unexpected {:?}", token))),
+        None => Err(ParseError::UnableToParse("Unexpected end of input".into())),
+    }
+}
+
+// We read the items until the list is closed
+fn parse_until(tokens: &[Token], position: &mut usize, close: Token) -> Result<Vec<Node>, ParseError> {
+    let mut items = Vec::new();
+    while tokens.get(*position) != Some(&close) {
+        if *position >= tokens.len() {
+            return Err(ParseError::InvalidOperator(format!("Expected {:?}", close)));
+        }
+        items.push(parse_item(tokens, position)?);
+    }
+    *position += 1;
+    Ok(items)
+}
+
+fn build_list(head: Token, operands: Vec<Node>) -> Result<Node, ParseError> {
+    let constructor: fn(Box<Node>, Box<Node>) -> Node = match head {
+        Token::IDENT(name) => return Ok(Node::FUNCTION(name, operands)),
+        Token::ARROW => return build_lambda(operands),
+        Token::RANGE => return build_pair(&head, operands, Node::RANGE),
+        Token::SUBTRACT if operands.len() == 1 => {
+            return Ok(Node::NEGATIVE(Box::new(operands.into_iter().next().unwrap())))
+        }
+        Token::PERCENT if operands.len() == 1 => {
+            return Ok(Node::PERCENT(Box::new(operands.into_iter().next().unwrap())))
+        }
+        Token::ADD => Node::ADD,
+        Token::SUBTRACT => Node::SUBTRACT,
+        Token::MULTIPLY => Node::MULTIPLY,
+        Token::DIVIDE => Node::DIVIDE,
+        Token::CARET => Node::CARRET,
+        _ => {
+            return Err(ParseError::InvalidOperator(format!(
+                "Please enter valid operator {:?}",
+                head
+            )))
+        }
+    };
+    if operands.len() < 2 {
+        return Err(ParseError::UnableToParse(format!(
+            "The operator {:?} needs at least two operands",
+            head
+        )));
+    }
+    // The operands are grouped from the left, (+ 1 2 3) is (+ (+ 1 2) 3)
+    let mut operands = operands.into_iter();
+    let first = operands.next().unwrap();
+    Ok(operands.fold(first, |left, right| constructor(Box::new(left), Box::new(right))))
+}
+
+// The index and the range take exactly two operands
+fn build_pair(head: &Token, operands: Vec<Node>, constructor: fn(Box<Node>, Box<Node>) -> Node) -> Result<Node, ParseError> {
+    if operands.len() != 2 {
+        return Err(ParseError::UnableToParse(format!(
+            "The operator {:?} needs two operands",
+            head
+        )));
+    }
+    let mut operands = operands.into_iter();
+    let first = operands.next().unwrap();
+    Ok(constructor(Box::new(first), Box::new(operands.next().unwrap())))
+}
+
+// The list of parameters is read like a call, (x y) is the name x with the argument y
+fn build_lambda(operands: Vec<Node>) -> Result<Node, ParseError> {
+    let error = || ParseError::UnableToParse("A lambda needs a list of names and a body".into());
+    if operands.len() != 2 {
+        return Err(error());
+    }
+    let mut operands = operands.into_iter();
+    let (names, body) = (operands.next().unwrap(), operands.next().unwrap());
+    let (first, rest) = match &names {
+        Node::FUNCTION(first, rest) => (first, rest.as_slice()),
+        Node::VARIABLE(first) => (first, &[][..]),
+        _ => return Err(error()),
+    };
+    let mut parameters = vec![first.clone()];
+    for name in rest {
+        match name {
+            Node::VARIABLE(name) if !parameters.contains(name) => parameters.push(name.clone()),
+            _ => return Err(error()),
+        }
+    }
+    Ok(Node::LAMBDA(parameters, Box::new(body)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsemath::parser::Parser;
+
+    fn parse(expr: &str) -> Node {
+        let mut parser = Parser::new(expr).unwrap();
+        parser.parse().unwrap()
+    }
+
+    #[test]
+    fn test_sexpr_print() {
+        assert_eq!(to_sexpr(&parse("1+2*3")), "(+ 1 (* 2 3))");
+        assert_eq!(to_sexpr(&parse("[1,x][0]+(1..3)")), "(+ ([] [1 x] 0) (.. 1 3))");
+        assert_eq!(to_sexpr(&parse("-(1-2)^0.5")), "(^ (- (- 1 2)) 0.5)");
+        assert_eq!(to_sexpr(&parse("max(1,sqrt(4))")), "(max 1 (sqrt 4))");
+        assert_eq!(to_sexpr(&parse("3 m/(2 s) in km/h")), "(in km/h (/ 3 m 2 s))");
+    }
+
+    #[test]
+    fn test_sexpr_parse() {
+        assert_eq!(parse_sexpr("(+ 1 (* 2 3))").unwrap(), parse("1+2*3"));
+        assert_eq!(parse_sexpr("(- 1 2 3)").unwrap(), parse("1-2-3"));
+        assert_eq!(parse_sexpr("(- 4)").unwrap(), parse("-4"));
+        assert_eq!(parse_sexpr(" ( max 1\n(sqrt 4) ) ").unwrap(), parse("max(1,sqrt(4))"));
+    }
+
+    #[test]
+    fn test_sexpr_errors() {
+        assert!(parse_sexpr("(+ 1 2").is_err());
+        assert!(parse_sexpr("(* 1)").is_err());
+        assert!(parse_sexpr("(1 2)").is_err());
+        assert!(parse_sexpr("(+ 1 2) 3").is_err());
+        assert!(parse_sexpr("").is_err());
+        assert!(parse_sexpr("[1 2").is_err());
+        assert!(parse_sexpr("(.. 1 2 3)").is_err());
+        assert!(parse_sexpr("([ v 0)").is_err());
+        assert!(parse_sexpr("(in km 1 2)").is_err());
+    }
+
+    #[test]
+    fn test_sexpr_round_trip() {
+        let exprs = ["1+2*3-4", "(1+2)^-(3/4)", "2^3^4", "--1", "min(x,2)*abs(-y)", "x -> (y, z) -> x*y^z", "[[], 1..n][0]", "1 km+x in m", "200-15%*x%"];
+        for expr in exprs.iter() {
+            let ast = parse(expr);
+            assert_eq!(parse_sexpr(&to_sexpr(&ast)).unwrap(), ast);
+        }
+    }
+}
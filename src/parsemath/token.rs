@@ -1,5 +1,7 @@
 use std::cmp::PartialEq;
 
+use super::datetime::DateTime;
+
 /// The Token struct holds the token type for a specific symbol or number
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
@@ -10,7 +12,20 @@ pub enum Token {
     CARET,
     LEFTPAREN,
     RIGHTPAREN,
+    LEFTBRACKET, // The [ that opens a list or an index
+    RIGHTBRACKET,
+    COMMA,
+    ASSIGN, // The = of an assignment, only read in programs
+    SEMICOLON, // The end of a statement, a ; or a new line in programs
+    ARROW, // The -> between the parameters and the body of a lambda
+    RANGE, // The .. between the bounds of a range like 1..10
+    IN, // The keyword of a conversion like 3 km in m
+    PERCENT, // The % after a percentage like 15%
     NUM(f64), // If the value is numeric we store the number in an Enum Variant
+    IDENT(String), // A name like sqrt, we store the name in an Enum Variant
+    UNIT(String), // The unit after a number or a conversion like km or m/s^2
+    DATE(DateTime), // A date like 2026-10-16 or a date and a time like 2026-10-16T14:30
+    SYMBOL(String), // An extra operator symbol like @, only read when the tokenizer is told so
     EOF,
 }
 
@@ -21,7 +36,7 @@ pub enum Token {
 /// - MULTDIV: 2 (Multiplication Division)
 /// - POWER: 3 (Pow operation)
 /// - NEGATIVE: 4 (-5 or -(Token::NUM))
-#[derive(Debug, PartialEq, PartialOrd)]
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
 pub enum OperPrec {
     DEFAULTZERO,
     ADDSUB,
@@ -1,16 +1,37 @@
-/// This module holds the **Tokenizer** Structure, the tokenizer is responsible for convert the
-/// characters to tokens and give it to the Parser to construct the AST (Abstract Sintax Tree)
-
+//! This module holds the **Tokenizer** Structure, the tokenizer is responsible for convert the
+//! characters to tokens and give it to the Parser to construct the AST (Abstract Sintax Tree)
 
 use std::{
     str::Chars, 
     iter::Peekable};
+use super::datetime::DateTime;
+use super::parser::ParseError;
 use super::token::Token;
+use super::units;
+
+/// The keyword of a conversion like `3 km in m`, it can't be the name of a variable
+pub const CONVERSION_KEYWORD: &str = "in";
+
+/// The keyword after a percentage like `50% of 80`, it's a name anywhere else
+pub const PERCENT_KEYWORD: &str = "of";
 
 /// The tokenizer struct holds all the methods to take the text and convert him to tokens
 pub struct Tokenizer<'a> {
     // The pekeeable is an iterator with the method peek that pop the first element in the stack
-    expr: Peekable<Chars<'a>>
+    expr: Peekable<Chars<'a>>,
+    // The extra operator symbols, the longest first
+    symbols: Vec<String>,
+    // The byte offset of the next character and of the last token read
+    offset: usize,
+    start: usize,
+    // In a program the statements are separated, a new line ends a statement when it isn't
+    // inside parentheses or brackets
+    program: bool,
+    open: usize,
+    // A unit can follow a number or the keyword of a conversion
+    unit_allowed: bool,
+    // Why the last token couldn't be read, when it isn't just an invalid character
+    invalid: Option<String>,
 }
 
 impl<'a> Tokenizer<'a> {
@@ -28,7 +49,7 @@ impl<'a> Tokenizer<'a> {
     /// // We create a new Tokenizer holding the expression 42
     /// let tokenizer = Tokenizer::new("42")
     /// ```
-    
+    //
     // We take a lifetime rule for prevent borrowing
     // When the variable goes out of scope
     pub fn new(new_expr: &'a str) -> Self {
@@ -36,9 +57,105 @@ impl<'a> Tokenizer<'a> {
         Tokenizer {
             // We convert the input expr to a peekeable
             expr: new_expr.chars().peekable(),
+            symbols: Vec::new(),
+            offset: 0,
+            start: 0,
+            program: false,
+            open: 0,
+            unit_allowed: false,
+            invalid: None,
         }
     }
 
+    /// Returns a new tokenizer for a program, that also reads the `=` of the assignments as
+    /// `Token::ASSIGN` and the `;` or the new lines between the statements as `Token::SEMICOLON`
+    /// # Arguments
+    /// * `new_expr` - A string containing the statements
+    /// # Examples
+    /// ```
+    /// let tokenizer = Tokenizer::for_program("x = 2\nx*(1\n+x)");
+    /// // The tokens would be IDENT("x"), ASSIGN, NUM(2.0), SEMICOLON, IDENT("x"), MULTIPLY,
+    /// // LEFTPAREN, NUM(1.0), ADD, IDENT("x") and RIGHTPAREN
+    /// ```
+    pub fn for_program(new_expr: &'a str) -> Self {
+        Tokenizer {
+            program: true,
+            ..Tokenizer::new(new_expr)
+        }
+    }
+
+    /// Returns a new tokenizer that also reads the given operator symbols as `Token::SYMBOL`
+    /// # Arguments
+    /// * `new_expr` - A string containing an Arithmetical Expression
+    /// * `symbols` - the extra symbols, they are read before the usual tokens and the longest
+    ///   symbol that matches wins
+    /// # Examples
+    /// ```
+    /// let tokenizer = Tokenizer::with_symbols("1 @ 2", &["@"]);
+    /// // The tokens would be NUM(1.0), SYMBOL("@") and NUM(2.0)
+    /// ```
+    pub fn with_symbols(new_expr: &'a str, symbols: &[&str]) -> Self {
+        let mut symbols: Vec<String> = symbols.iter().map(|symbol| symbol.to_string()).collect();
+        symbols.sort_by_key(|symbol| std::cmp::Reverse(symbol.chars().count()));
+        Tokenizer {
+            expr: new_expr.chars().peekable(),
+            symbols,
+            offset: 0,
+            start: 0,
+            program: false,
+            open: 0,
+            unit_allowed: false,
+            invalid: None,
+        }
+    }
+
+    /// Returns the byte offset where the last token read starts, for an invalid character is the
+    /// offset of the character
+    /// # Examples
+    /// ```
+    /// let mut tokenizer = Tokenizer::new("1 + 2");
+    /// tokenizer.next();
+    /// tokenizer.next();
+    /// // tokenizer.position() will be 2, the offset of the +
+    /// ```
+    pub fn position(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the error of the last token that couldn't be read, a malformed number has its own
+    /// message and anything else is an invalid character
+    /// # Examples
+    /// ```
+    /// let mut tokenizer = Tokenizer::new("1.2.3");
+    /// tokenizer.next();
+    /// // tokenizer.error() will be ParseError::UnableToParse("Invalid number 1.2.3")
+    /// ```
+    pub fn error(&self) -> ParseError {
+        match &self.invalid {
+            Some(message) => ParseError::UnableToParse(message.clone()),
+            None => ParseError::InvalidOperator("Invalid character".into()),
+        }
+    }
+
+    // Takes the next character and counts its bytes
+    fn advance(&mut self) -> Option<char> {
+        let next_char = self.expr.next()?;
+        self.offset += next_char.len_utf8();
+        Some(next_char)
+    }
+
+    // Reads the longest extra symbol at the current position
+    fn next_symbol(&mut self) -> Option<Token> {
+        let symbol = self.symbols.iter().find(|symbol| {
+            let mut rest = self.expr.clone();
+            symbol.chars().all(|c| rest.next() == Some(c))
+        })?.clone();
+        for _ in symbol.chars() {
+            self.advance();
+        }
+        Some(Token::SYMBOL(symbol))
+    }
+
     /// Peeks a single character or a number and return a Token Variant
     /// # Examples
     /// ```
@@ -46,9 +163,37 @@ impl<'a> Tokenizer<'a> {
     /// let tokenizer = Tokenizer::new("42");
     /// let token = tokenizer.next()?;
     /// // The token would be Token::NUM(42.0)
+    /// ```
+    /// A name of the table of units after a number or after `in` is a `Token::UNIT`, the units
+    /// joined by `*`, `/` and integer powers without spaces are a single unit, so `9.81 m/s^2`
+    /// gives `Token::NUM(9.81)` and `Token::UNIT("m/s^2")`
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<Token> {
+        let token = self.read_token();
+        self.unit_allowed = matches!(token, Some(Token::NUM(_)) | Some(Token::IN));
+        token
+    }
+
+    fn read_token(&mut self) -> Option<Token> {
+        // The spaces between the tokens are ignored
+        while let Some(space) = self.expr.peek() {
+            if !space.is_whitespace() {
+                break;
+            }
+            if self.program && *space == '\n' && self.open == 0 {
+                self.start = self.offset;
+                self.advance();
+                return Some(Token::SEMICOLON);
+            }
+            self.advance();
+        }
+        self.start = self.offset;
+        self.invalid = None;
+        if let Some(symbol) = self.next_symbol() {
+            return Some(symbol);
+        }
         // We take the next character in the stack and we store it into a variable
-        let next_char = self.expr.next();
+        let next_char = self.advance();
         match next_char {
             // If the next char is a number
             Some('0'..='9') => {
@@ -58,34 +203,209 @@ impl<'a> Tokenizer<'a> {
                 let mut number = next_char?.to_string();
                 // if the next value is a number we parse until this the next character be a 
                 // Symbol
-                while let Some(next_char) = self.expr.peek() {
-                    if next_char.is_numeric() || next_char == &'.' {
-                        // If is a number or a decimal point we push it to the number String
-                        number.push(self.expr.next()?);
-                    } else if next_char == &'(' {
+                while let Some(&next_char) = self.expr.peek() {
+                    if next_char == '.' && self.starts_range() {
+                        // The dots of a range like 1..10 end the number
+                        break;
+                    } else if next_char.is_ascii_digit() || next_char == '.' {
+                        // If is a digit or a decimal point we push it to the number String
+                        number.push(self.advance()?);
+                    } else if next_char == '(' {
                         return None;
                     } else {
                         break;
                     }
                 }
-                // We return a option type with the token
-                Some(Token::NUM(number.parse::<f64>().unwrap()))
+                // Four digits followed by a month and a day are the year of a date
+                if number.len() == 4 && !number.contains('.') && self.starts_date() {
+                    return self.read_date(number);
+                }
+                // We return a option type with the token, a number with two dots like 1.2.3
+                // is an error instead
+                match number.parse::<f64>() {
+                    Ok(value) => Some(Token::NUM(value)),
+                    Err(_) => {
+                        self.invalid = Some(format!("Invalid number {}", number));
+                        None
+                    }
+                }
             }
             // if the token are not numeric
             // We tokenize the mathematical symbol
             Some('+') => Some(Token::ADD),
-            Some('-') => Some(Token::SUBTRACT),
-            Some('*') => Some(Token::MULTIPLY),
+            Some('-') => {
+                // A minus followed by > is the arrow of a lambda
+                if self.expr.peek() == Some(&'>') {
+                    self.advance();
+                    return Some(Token::ARROW);
+                }
+                Some(Token::SUBTRACT)
+            }
+            Some('*') => {
+                // A double star is the alternative spelling of the power operator
+                if self.expr.peek() == Some(&'*') {
+                    self.advance();
+                    return Some(Token::CARET);
+                }
+                Some(Token::MULTIPLY)
+            }
             Some('/') => Some(Token::DIVIDE),
             Some('^') => Some(Token::CARET),
-            Some('(') => Some(Token::LEFTPAREN),
-            Some(')') => Some(Token::RIGHTPAREN),
+            Some('(') => {
+                self.open += 1;
+                Some(Token::LEFTPAREN)
+            }
+            Some(')') => {
+                self.open = self.open.saturating_sub(1);
+                Some(Token::RIGHTPAREN)
+            }
+            Some('[') => {
+                self.open += 1;
+                Some(Token::LEFTBRACKET)
+            }
+            Some(']') => {
+                self.open = self.open.saturating_sub(1);
+                Some(Token::RIGHTBRACKET)
+            }
+            Some('.') if self.expr.peek() == Some(&'.') => {
+                self.advance();
+                Some(Token::RANGE)
+            }
+            Some(',') => Some(Token::COMMA),
+            Some('%') => Some(Token::PERCENT),
+            Some('=') if self.program => Some(Token::ASSIGN),
+            Some(';') if self.program => Some(Token::SEMICOLON),
+            // If the next char is a letter we read a name until the next symbol
+            Some(letter) if letter.is_alphabetic() => {
+                let name = self.read_name(letter);
+                // A unit isn't a call, so min(1, 2) after a number is still the function
+                if self.unit_allowed && units::is_unit(&name) && self.expr.peek() != Some(&'(') {
+                    return Some(Token::UNIT(self.read_unit(name)));
+                }
+                if name == CONVERSION_KEYWORD {
+                    return Some(Token::IN);
+                }
+                Some(Token::IDENT(name))
+            }
             // If there is no more symbols we send a End-Of-File Indication to the parser
             None => Some(Token::EOF),
             // Whatever other symbol is and this isn't a token we return None
             Some(_) => None,
         }
-    } 
+    }
+
+    // Reads the rest of a name, the letters, digits and underscores after its first letter
+    fn read_name(&mut self, first: char) -> String {
+        let mut name = first.to_string();
+        while let Some(&next_char) = self.expr.peek() {
+            if next_char.is_alphanumeric() || next_char == '_' {
+                name.push(next_char);
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        name
+    }
+
+    // Reads the powers and the other units joined to the first unit, like the /s^2 of m/s^2.
+    // We only take an operator when a power or a unit follows it, so 2 m*x is a product
+    fn read_unit(&mut self, mut unit: String) -> String {
+        loop {
+            let mut rest = self.expr.clone();
+            let mut part = String::new();
+            match rest.next() {
+                Some('^') => {
+                    part.push('^');
+                    if rest.peek() == Some(&'-') {
+                        part.push('-');
+                        rest.next();
+                    }
+                    while let Some(digit) = rest.next_if(char::is_ascii_digit) {
+                        part.push(digit);
+                    }
+                    if !part.ends_with(|c: char| c.is_ascii_digit()) {
+                        return unit;
+                    }
+                }
+                Some(operator @ ('*' | '/')) => {
+                    part.push(operator);
+                    let mut name = String::new();
+                    while let Some(letter) = rest.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                        name.push(letter);
+                    }
+                    if !units::is_unit(&name) || rest.peek() == Some(&'(') {
+                        return unit;
+                    }
+                    part.push_str(&name);
+                }
+                _ => return unit,
+            }
+            for _ in part.chars() {
+                self.advance();
+            }
+            unit.push_str(&part);
+        }
+    }
+
+    // Checks if the next two characters are the dots of a range
+    fn starts_range(&self) -> bool {
+        let mut rest = self.expr.clone();
+        rest.next() == Some('.') && rest.next() == Some('.')
+    }
+
+    // Checks if the next characters are the month and the day of a date like -10-16
+    fn starts_date(&self) -> bool {
+        let mut rest = self.expr.clone();
+        "-00-00".chars().all(|pattern| match rest.next() {
+            Some(next) if pattern == '0' => next.is_ascii_digit(),
+            next => next == Some(pattern),
+        })
+    }
+
+    // Reads the month, the day and the time of a date after its year, the time starts with a T
+    // followed by a digit. A day or a time that doesn't exist like 2026-02-30 is an error
+    fn read_date(&mut self, mut date: String) -> Option<Token> {
+        for _ in 0..6 {
+            date.push(self.advance()?);
+        }
+        let mut rest = self.expr.clone();
+        if rest.next() == Some('T') && rest.next().is_some_and(|next| next.is_ascii_digit()) {
+            date.push(self.advance()?);
+            while let Some(&next_char) = self.expr.peek() {
+                if !next_char.is_ascii_digit() && next_char != ':' && next_char != '.' {
+                    break;
+                }
+                date.push(self.advance()?);
+            }
+        }
+        let parsed = DateTime::parse(&date).map(Token::DATE);
+        if parsed.is_none() {
+            self.invalid = Some(format!("{} is not a valid date", date));
+        }
+        parsed
+    }
+
+    /// Reads all the remaining tokens of the expression, without the EOF token
+    /// # Returns
+    /// * `Result<Vec<Token>, ParseError>` - the tokens or the error of the first token that
+    ///   couldn't be read
+    /// # Examples
+    /// ```
+    /// let tokenizer = Tokenizer::new("1 2 +");
+    /// let tokens = tokenizer.into_tokens();
+    /// // The tokens would be Ok(vec![Token::NUM(1.0), Token::NUM(2.0), Token::ADD])
+    /// ```
+    pub fn into_tokens(mut self) -> Result<Vec<Token>, ParseError> {
+        let mut tokens = Vec::new();
+        loop {
+            match self.next() {
+                Some(Token::EOF) => return Ok(tokens),
+                Some(token) => tokens.push(token),
+                None => return Err(self.error()),
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -157,6 +477,101 @@ mod test {
         assert_eq!(Token::SUBTRACT, token);
     }
 
+    #[test]
+    fn test_token_arrow() {
+        let tokens = Tokenizer::new("x -> x-1").into_tokens().unwrap();
+        let name = || Token::IDENT("x".into());
+        assert_eq!(tokens, vec![name(), Token::ARROW, name(), Token::SUBTRACT, Token::NUM(1.0)]);
+    }
+
+    #[test]
+    fn test_token_list() {
+        let tokens = Tokenizer::new("[1.5, 2][0] + 1..10").into_tokens().unwrap();
+        let expected = vec![
+            Token::LEFTBRACKET,
+            Token::NUM(1.5),
+            Token::COMMA,
+            Token::NUM(2.0),
+            Token::RIGHTBRACKET,
+            Token::LEFTBRACKET,
+            Token::NUM(0.0),
+            Token::RIGHTBRACKET,
+            Token::ADD,
+            Token::NUM(1.0),
+            Token::RANGE,
+            Token::NUM(10.0),
+        ];
+        assert_eq!(tokens, expected);
+        // A single dot isn't a token
+        assert!(Tokenizer::new("1 . 2").into_tokens().is_err());
+        // A new line inside a list doesn't end the statement
+        let tokens = Tokenizer::for_program("[1,\n2]").into_tokens().unwrap();
+        assert!(!tokens.contains(&Token::SEMICOLON));
+    }
+
+    #[test]
+    fn test_token_unit() {
+        let tokens = Tokenizer::new("9.81 m/s^2 * 2 s*x in km/h").into_tokens().unwrap();
+        let expected = vec![
+            Token::NUM(9.81),
+            Token::UNIT("m/s^2".into()),
+            Token::MULTIPLY,
+            Token::NUM(2.0),
+            Token::UNIT("s".into()),
+            Token::MULTIPLY,
+            Token::IDENT("x".into()),
+            Token::IN,
+            Token::UNIT("km/h".into()),
+        ];
+        assert_eq!(tokens, expected);
+        // A unit only follows a number, the other names are variables or calls
+        let tokens = Tokenizer::new("m + 2 min(1, 2)").into_tokens().unwrap();
+        assert_eq!(tokens[0], Token::IDENT("m".into()));
+        assert_eq!(tokens[3], Token::IDENT("min".into()));
+        let tokens = Tokenizer::new("3 m^x").into_tokens().unwrap();
+        assert_eq!(tokens[1..3], [Token::UNIT("m".into()), Token::CARET]);
+    }
+
+    #[test]
+    fn test_token_percent() {
+        let tokens = Tokenizer::new("200 + 15% - 50%of x").into_tokens().unwrap();
+        let expected = vec![
+            Token::NUM(200.0),
+            Token::ADD,
+            Token::NUM(15.0),
+            Token::PERCENT,
+            Token::SUBTRACT,
+            Token::NUM(50.0),
+            Token::PERCENT,
+            Token::IDENT("of".into()),
+            Token::IDENT("x".into()),
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn test_token_date() {
+        let tokens = Tokenizer::new("2026-10-16T14:30 - 2026-10-16 + 30 days").into_tokens().unwrap();
+        let date = |text: &str| Token::DATE(DateTime::parse(text).unwrap());
+        let expected = vec![
+            date("2026-10-16T14:30"),
+            Token::SUBTRACT,
+            date("2026-10-16"),
+            Token::ADD,
+            Token::NUM(30.0),
+            Token::UNIT("days".into()),
+        ];
+        assert_eq!(tokens, expected);
+        // The other numbers are still subtracted
+        let tokens = Tokenizer::new("2026-10 - 16").into_tokens().unwrap();
+        assert_eq!(tokens[..3], [Token::NUM(2026.0), Token::SUBTRACT, Token::NUM(10.0)]);
+        // A day that doesn't exist is an error about the date
+        let error = Tokenizer::new("2026-02-30").into_tokens().unwrap_err();
+        assert_eq!(error.to_string(), "Error in evaluating 2026-02-30 is not a valid date");
+        let error = Tokenizer::new("2026-13-45").into_tokens().unwrap_err();
+        assert_eq!(error.to_string(), "Error in evaluating 2026-13-45 is not a valid date");
+    }
+
     #[test]
     fn test_token_caret() {
         let mut tokenizer = Tokenizer::new("^");
@@ -167,6 +582,49 @@ mod test {
         assert_eq!(Token::CARET, token);
     }
 
+    #[test]
+    fn test_token_double_star() {
+        let mut tokenizer = Tokenizer::new("**");
+        let token = match tokenizer.next() {
+            Some(token) => token,
+            None => Token::EOF
+        };
+        assert_eq!(Token::CARET, token);
+    }
+
+    #[test]
+    fn test_token_skip_whitespace() {
+        let tokens = Tokenizer::new(" 1 +\t2 ").into_tokens().unwrap();
+        assert_eq!(tokens, vec![Token::NUM(1.0), Token::ADD, Token::NUM(2.0)]);
+    }
+
+    #[test]
+    fn test_token_invalid_character() {
+        assert!(Tokenizer::new("1 # 2").into_tokens().is_err());
+    }
+
+    #[test]
+    fn test_token_invalid_number() {
+        let mut tokenizer = Tokenizer::new("1.2.3");
+        assert_eq!(tokenizer.next(), None);
+        assert_eq!(tokenizer.error().to_string(), "Error in evaluating Invalid number 1.2.3");
+        // Only the ASCII digits belong to a number, a superscript is an invalid character
+        let mut tokenizer = Tokenizer::new("2²");
+        assert_eq!(tokenizer.next(), Some(Token::NUM(2.0)));
+        assert_eq!(tokenizer.next(), None);
+        assert_eq!(tokenizer.error().to_string(), "Error in evaluating Invalid character");
+    }
+
+    #[test]
+    fn test_token_ident() {
+        let mut tokenizer = Tokenizer::new("sqrt(");
+        let token = match tokenizer.next() {
+            Some(token) => token,
+            None => Token::EOF
+        };
+        assert_eq!(Token::IDENT("sqrt".into()), token);
+    }
+
     #[test]
     fn test_token_rparent() {
         let mut tokenizer = Tokenizer::new(")");
@@ -196,4 +654,59 @@ mod test {
         assert_eq!(Token::EOF, token);
     }
 
+    #[test]
+    fn test_token_symbols() {
+        let tokenizer = Tokenizer::with_symbols("1 ± 2 +- x**2 @", &["±", "+-", "@"]);
+        let expected = vec![
+            Token::NUM(1.0),
+            Token::SYMBOL("±".into()),
+            Token::NUM(2.0),
+            Token::SYMBOL("+-".into()),
+            Token::IDENT("x".into()),
+            Token::CARET,
+            Token::NUM(2.0),
+            Token::SYMBOL("@".into()),
+        ];
+        assert_eq!(tokenizer.into_tokens().unwrap(), expected);
+        assert!(Tokenizer::new("1 @ 2").into_tokens().is_err());
+    }
+
+    #[test]
+    fn test_token_program() {
+        let tokenizer = Tokenizer::for_program("x = 2; y = x*(1\n+x)\n\ny");
+        let expected = vec![
+            Token::IDENT("x".into()),
+            Token::ASSIGN,
+            Token::NUM(2.0),
+            Token::SEMICOLON,
+            Token::IDENT("y".into()),
+            Token::ASSIGN,
+            Token::IDENT("x".into()),
+            Token::MULTIPLY,
+            Token::LEFTPAREN,
+            Token::NUM(1.0),
+            Token::ADD,
+            Token::IDENT("x".into()),
+            Token::RIGHTPAREN,
+            Token::SEMICOLON,
+            Token::SEMICOLON,
+            Token::IDENT("y".into()),
+        ];
+        assert_eq!(tokenizer.into_tokens().unwrap(), expected);
+        // Outside of a program they are invalid characters
+        assert!(Tokenizer::new("x = 2").into_tokens().is_err());
+    }
+
+    #[test]
+    fn test_token_position() {
+        let mut tokenizer = Tokenizer::new("é + 12 # 3");
+        let mut positions = Vec::new();
+        while tokenizer.next().is_some() {
+            positions.push(tokenizer.position());
+        }
+        // The positions are in bytes, the é takes two
+        assert_eq!(positions, vec![0, 3, 5]);
+        assert_eq!(tokenizer.position(), 8);
+    }
+
 }
\ No newline at end of file
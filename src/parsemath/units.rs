@@ -0,0 +1,432 @@
+//! This module holds the **physical units** of the quantities like `3 km` or `9.81 m/s^2`. A
+//! quantity stores its value in the SI base units with the exponents of its dimension, so the
+//! compatible units convert automatically: `1 km + 500 m` is `1.5 km` and
+//! `5 kg * 9.81 m/s^2 in N` is `49.05 N`. Adding or converting quantities of different
+//! dimensions, like `1 m + 1 s`, fails with `EvalError::DimensionMismatch`.
+//!
+//! The units are read from a built-in table of SI and imperial units, there is nothing to
+//! download. All the units are multiplicative, the temperatures with an offset like the degrees
+//! Celsius aren't in the table
+
+use std::fmt;
+use std::sync::Arc;
+
+use super::ast::{EvalError, Node};
+use super::value::{Value, INTEGER_TYPE, NUMBER_TYPE, QUANTITY_TYPE};
+
+// The symbols of the SI base units in the order of the exponents of a dimension
+const BASE_UNITS: [&str; 7] = ["kg", "m", "s", "A", "K", "mol", "cd"];
+
+/// The Dimension struct holds the exponents of the SI base units of a quantity, in the order
+/// kilogram, metre, second, ampere, kelvin, mole and candela. A speed is `m/s`, so its
+/// exponents are `[0, 1, -1, 0, 0, 0, 0]`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Dimension(pub [i8; 7]);
+
+impl Dimension {
+    /// The dimension of the plain numbers
+    pub const NONE: Dimension = Dimension([0; 7]);
+
+    /// Returns true if all the exponents are zero, like the dimension of a ratio
+    pub fn is_none(self) -> bool {
+        self == Dimension::NONE
+    }
+
+    // The exponents of a product with the other dimension raised to the given power, -1 for
+    // a quotient
+    fn combine(self, other: Dimension, power: i8) -> Dimension {
+        let mut exponents = self.0;
+        for (exponent, other) in exponents.iter_mut().zip(other.0) {
+            *exponent = exponent.saturating_add(other.saturating_mul(power));
+        }
+        Dimension(exponents)
+    }
+}
+
+// The trait for display with format! or println!, a dimension is written with the base units
+// like kg*m/s^2 and a plain number is 1
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let part = |symbol: &str, exponent: i8| match exponent {
+            1 => symbol.to_string(),
+            _ => format!("{}^{}", symbol, exponent),
+        };
+        let numerator: Vec<String> = BASE_UNITS.iter().zip(self.0)
+            .filter(|(_, exponent)| *exponent > 0)
+            .map(|(symbol, exponent)| part(symbol, exponent))
+            .collect();
+        let denominator: Vec<String> = BASE_UNITS.iter().zip(self.0)
+            .filter(|(_, exponent)| *exponent < 0)
+            .map(|(symbol, exponent)| part(symbol, -exponent))
+            .collect();
+        match (numerator.is_empty(), denominator.is_empty()) {
+            (true, true) => write!(f, "1"),
+            (false, true) => write!(f, "{}", numerator.join("*")),
+            (true, false) => write!(f, "1/{}", denominator.join("/")),
+            (false, false) => write!(f, "{}/{}", numerator.join("*"), denominator.join("/")),
+        }
+    }
+}
+
+const MASS: Dimension = Dimension([1, 0, 0, 0, 0, 0, 0]);
+const LENGTH: Dimension = Dimension([0, 1, 0, 0, 0, 0, 0]);
+/// The dimension of the durations, the seconds
+pub const TIME: Dimension = Dimension([0, 0, 1, 0, 0, 0, 0]);
+const CURRENT: Dimension = Dimension([0, 0, 0, 1, 0, 0, 0]);
+const TEMPERATURE: Dimension = Dimension([0, 0, 0, 0, 1, 0, 0]);
+const AMOUNT: Dimension = Dimension([0, 0, 0, 0, 0, 1, 0]);
+const LUMINOSITY: Dimension = Dimension([0, 0, 0, 0, 0, 0, 1]);
+const AREA: Dimension = Dimension([0, 2, 0, 0, 0, 0, 0]);
+const VOLUME: Dimension = Dimension([0, 3, 0, 0, 0, 0, 0]);
+const SPEED: Dimension = Dimension([0, 1, -1, 0, 0, 0, 0]);
+const FREQUENCY: Dimension = Dimension([0, 0, -1, 0, 0, 0, 0]);
+const FORCE: Dimension = Dimension([1, 1, -2, 0, 0, 0, 0]);
+const PRESSURE: Dimension = Dimension([1, -1, -2, 0, 0, 0, 0]);
+const ENERGY: Dimension = Dimension([1, 2, -2, 0, 0, 0, 0]);
+const POWER: Dimension = Dimension([1, 2, -3, 0, 0, 0, 0]);
+const CHARGE: Dimension = Dimension([0, 0, 1, 1, 0, 0, 0]);
+const VOLTAGE: Dimension = Dimension([1, 2, -3, -1, 0, 0, 0]);
+
+/// The built-in units with the value of one unit in the SI base units and the dimension. The
+/// inch is written `inch` because `in` is the conversion keyword
+pub const UNITS: [(&str, f64, Dimension); 45] = [
+    ("kg", 1.0, MASS),
+    ("g", 1e-3, MASS),
+    ("mg", 1e-6, MASS),
+    ("t", 1e3, MASS),
+    ("lb", 0.453_592_37, MASS),
+    ("oz", 0.028_349_523_125, MASS),
+    ("m", 1.0, LENGTH),
+    ("km", 1e3, LENGTH),
+    ("cm", 1e-2, LENGTH),
+    ("mm", 1e-3, LENGTH),
+    ("inch", 0.0254, LENGTH),
+    ("ft", 0.3048, LENGTH),
+    ("yd", 0.9144, LENGTH),
+    ("mi", 1_609.344, LENGTH),
+    ("s", 1.0, TIME),
+    ("ms", 1e-3, TIME),
+    ("min", 60.0, TIME),
+    ("h", 3_600.0, TIME),
+    ("hour", 3_600.0, TIME),
+    ("hours", 3_600.0, TIME),
+    ("day", 86_400.0, TIME),
+    ("days", 86_400.0, TIME),
+    ("week", 604_800.0, TIME),
+    ("weeks", 604_800.0, TIME),
+    ("A", 1.0, CURRENT),
+    ("K", 1.0, TEMPERATURE),
+    ("mol", 1.0, AMOUNT),
+    ("cd", 1.0, LUMINOSITY),
+    ("ha", 1e4, AREA),
+    ("acre", 4_046.856_422_4, AREA),
+    ("L", 1e-3, VOLUME),
+    ("mL", 1e-6, VOLUME),
+    ("gal", 3.785_411_784e-3, VOLUME),
+    ("mph", 0.447_04, SPEED),
+    ("Hz", 1.0, FREQUENCY),
+    ("N", 1.0, FORCE),
+    ("Pa", 1.0, PRESSURE),
+    ("bar", 1e5, PRESSURE),
+    ("psi", 6_894.757_293_168, PRESSURE),
+    ("J", 1.0, ENERGY),
+    ("kWh", 3.6e6, ENERGY),
+    ("cal", 4.184, ENERGY),
+    ("W", 1.0, POWER),
+    ("C", 1.0, CHARGE),
+    ("V", 1.0, VOLTAGE),
+];
+
+/// The Unit struct holds a unit of the table or a compound unit like `m/s^2`
+/// # Arguments
+/// * `name: String` - the unit as it's written
+/// * `factor: f64` - the value of one unit in the SI base units
+/// * `dimension: Dimension` - the exponents of the SI base units
+#[derive(Clone, Debug, PartialEq)]
+pub struct Unit {
+    pub name: String,
+    pub factor: f64,
+    pub dimension: Dimension,
+}
+
+/// Returns true if the name is a unit of the table
+pub fn is_unit(name: &str) -> bool {
+    UNITS.iter().any(|(unit, _, _)| *unit == name)
+}
+
+/// Returns the unit written with the units of the table joined by `*` and `/`, every unit can
+/// have an integer power like `s^2`. The operators apply from left to right
+/// # Arguments
+/// * `text: &str` - the unit, like `km/h` or `kg*m^2/s^2`
+/// # Returns
+/// * `Option<Unit>` - the unit or `None` if a part isn't a unit of the table
+/// # Example
+/// ```
+/// let unit = parse_unit("km/h").unwrap();
+/// // The factor will be 1000/3600 and the dimension the one of a speed
+/// ```
+pub fn parse_unit(text: &str) -> Option<Unit> {
+    let mut factor = 1.0;
+    let mut dimension = Dimension::NONE;
+    // The separator before every part, a quotient divides only the next part
+    let mut separator = '*';
+    let mut rest = text;
+    loop {
+        let end = rest.find(['*', '/']).unwrap_or(rest.len());
+        let (name, power) = match rest[..end].split_once('^') {
+            Some((name, power)) => (name, power.parse::<i8>().ok()?),
+            None => (&rest[..end], 1),
+        };
+        let (_, unit_factor, unit_dimension) = UNITS.iter().find(|(unit, _, _)| *unit == name)?;
+        let power = if separator == '/' { power.checked_neg()? } else { power };
+        factor *= unit_factor.powi(power as i32);
+        dimension = dimension.combine(*unit_dimension, power);
+        match rest[end..].chars().next() {
+            Some(next) => {
+                separator = next;
+                rest = &rest[end + 1..];
+            }
+            None => break,
+        }
+    }
+    Some(Unit {
+        name: text.to_string(),
+        factor,
+        dimension,
+    })
+}
+
+/// The Quantity struct holds a number with a physical dimension
+/// # Arguments
+/// * `value: f64` - the value in the SI base units
+/// * `dimension: Dimension` - the exponents of the SI base units
+/// * `unit: Option<Arc<Unit>>` - the unit in which the quantity is written, without a unit it's
+///   written with the SI base units
+/// # Example
+/// ```
+/// let quantity = Quantity::new(3.0, parse_unit("km").unwrap());
+/// // The value will be 3000 and the quantity is displayed as 3 km
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quantity {
+    value: f64,
+    dimension: Dimension,
+    unit: Option<Arc<Unit>>,
+}
+
+impl Quantity {
+    /// Returns the quantity of the given number of units
+    pub fn new(value: f64, unit: Unit) -> Quantity {
+        Quantity {
+            value: value * unit.factor,
+            dimension: unit.dimension,
+            unit: Some(Arc::new(unit)),
+        }
+    }
+
+    /// Returns the value in the SI base units
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Returns the exponents of the SI base units
+    pub fn dimension(&self) -> Dimension {
+        self.dimension
+    }
+
+    /// Returns the same quantity written in another unit
+    /// # Arguments
+    /// * `unit: Unit` - the new unit, it must have the same dimension
+    /// # Returns
+    /// * `Result<Quantity, EvalError>` - the quantity or `EvalError::DimensionMismatch`
+    /// # Example
+    /// ```
+    /// let distance = Quantity::new(1.5, parse_unit("km").unwrap());
+    /// let meters = distance.convert(parse_unit("m").unwrap());
+    /// // This will be Ok with the quantity displayed as 1500 m
+    /// ```
+    pub fn convert(&self, unit: Unit) -> Result<Quantity, EvalError> {
+        if unit.dimension != self.dimension {
+            return Err(mismatch(self.dimension, unit.dimension));
+        }
+        Ok(Quantity {
+            value: self.value,
+            dimension: self.dimension,
+            unit: Some(Arc::new(unit)),
+        })
+    }
+
+    /// Returns the quantity with the opposite value
+    pub fn negate(self) -> Quantity {
+        Quantity {
+            value: -self.value,
+            ..self
+        }
+    }
+
+    // A quantity without dimension is a plain number
+    fn into_value(self) -> Value {
+        if self.dimension.is_none() {
+            return Value::NUMBER(self.value);
+        }
+        Value::QUANTITY(self)
+    }
+}
+
+// The trait for display with format! or println!, the value is written in its unit
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.unit {
+            Some(unit) => write!(f, "{} {}", self.value / unit.factor, unit.name),
+            None => write!(f, "{} {}", self.value, self.dimension),
+        }
+    }
+}
+
+/// Applies an arithmetic operation where one of the operands is a quantity, the other one must
+/// be a quantity or a number. The sums need the same dimension, the products combine the
+/// dimensions and the power must be an integer
+/// # Arguments
+/// * `expr: &Node` - the operation node, it gives the operator
+/// * `left: Value, right: Value` - the values of the operands
+/// # Returns
+/// * `Result<Value, EvalError>` - the result, a number if the dimensions cancel out,
+///   `EvalError::DimensionMismatch` for a sum of different dimensions or
+///   `EvalError::TypeMismatch` for an operand that isn't a number or a quantity
+/// # Example
+/// ```
+/// let speed = apply(&divide_node, distance, time);
+/// // For 3 m and 2 s this will be Ok with the quantity displayed as 1.5 m/s
+/// ```
+pub fn apply(expr: &Node, left: Value, right: Value) -> Result<Value, EvalError> {
+    let (left, right) = (to_quantity(left)?, to_quantity(right)?);
+    match expr {
+        Node::ADD(..) | Node::SUBTRACT(..) => {
+            if left.dimension != right.dimension {
+                return Err(mismatch(left.dimension, right.dimension));
+            }
+            let value = match expr {
+                Node::ADD(..) => left.value + right.value,
+                _ => left.value - right.value,
+            };
+            // The sum is written in the unit of the left operand
+            Ok(Quantity {
+                value,
+                dimension: left.dimension,
+                unit: left.unit.or(right.unit),
+            }
+            .into_value())
+        }
+        Node::MULTIPLY(..) | Node::DIVIDE(..) => {
+            let divide = matches!(expr, Node::DIVIDE(..));
+            let (value, power) = if divide {
+                (left.value / right.value, -1)
+            } else {
+                (left.value * right.value, 1)
+            };
+            // A quantity scaled by a number keeps its unit, so 2 * 3 km is 6 km
+            let unit = match (right.unit.is_none() && right.dimension.is_none(), left.dimension.is_none()) {
+                (true, _) => left.unit,
+                (false, true) if !divide && left.unit.is_none() => right.unit,
+                _ => None,
+            };
+            Ok(Quantity {
+                value,
+                dimension: left.dimension.combine(right.dimension, power),
+                unit,
+            }
+            .into_value())
+        }
+        _ => {
+            if !right.dimension.is_none() {
+                return Err(EvalError::TypeMismatch(NUMBER_TYPE.to_string(), QUANTITY_TYPE.to_string()));
+            }
+            let exponent = right.value;
+            if exponent.fract() != 0.0 || exponent.abs() > i8::MAX as f64 {
+                return Err(EvalError::TypeMismatch(INTEGER_TYPE.to_string(), exponent.to_string()));
+            }
+            Ok(Quantity {
+                value: left.value.powf(exponent),
+                dimension: Dimension::NONE.combine(left.dimension, exponent as i8),
+                unit: None,
+            }
+            .into_value())
+        }
+    }
+}
+
+/// Writes a value in a unit, the value of a node like `x in km`
+/// # Arguments
+/// * `value: Value` - a quantity
+/// * `unit: &str` - the unit, like `km/h`
+/// # Returns
+/// * `Result<Value, EvalError>` - the quantity in the unit, `EvalError::UnknownUnit`,
+///   `EvalError::DimensionMismatch` or `EvalError::TypeMismatch` if the value isn't a quantity
+pub fn convert(value: Value, unit: &str) -> Result<Value, EvalError> {
+    let unit = parse_unit(unit).ok_or_else(|| EvalError::UnknownUnit(unit.to_string()))?;
+    to_quantity(value)?.convert(unit).map(Value::QUANTITY)
+}
+
+/// Returns the value of a number with a unit, the value of a node like `3 km`
+/// # Arguments
+/// * `value: f64` - the number of units
+/// * `unit: &str` - the unit
+/// # Returns
+/// * `Result<Value, EvalError>` - the quantity or `EvalError::UnknownUnit`
+pub fn quantity(value: f64, unit: &str) -> Result<Value, EvalError> {
+    let unit = parse_unit(unit).ok_or_else(|| EvalError::UnknownUnit(unit.to_string()))?;
+    Ok(Value::QUANTITY(Quantity::new(value, unit)))
+}
+
+// A number is a quantity without dimension
+fn to_quantity(value: Value) -> Result<Quantity, EvalError> {
+    match value {
+        Value::QUANTITY(quantity) => Ok(quantity),
+        Value::NUMBER(value) => Ok(Quantity {
+            value,
+            dimension: Dimension::NONE,
+            unit: None,
+        }),
+        other => Err(EvalError::TypeMismatch(NUMBER_TYPE.to_string(), other.type_name().to_string())),
+    }
+}
+
+fn mismatch(left: Dimension, right: Dimension) -> EvalError {
+    EvalError::DimensionMismatch(left.to_string(), right.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_units_parse_unit() {
+        let unit = parse_unit("km/h").unwrap();
+        assert_eq!(unit.factor, 1000.0 / 3600.0);
+        assert_eq!(unit.dimension, SPEED);
+        assert_eq!(parse_unit("kg*m/s^2").unwrap().dimension, FORCE);
+        assert_eq!(parse_unit("s^-1").unwrap().dimension, FREQUENCY);
+        assert_eq!(parse_unit("m/foo"), None);
+        for (name, _, _) in UNITS.iter() {
+            assert!(is_unit(name));
+        }
+    }
+
+    #[test]
+    fn test_units_dimension_display() {
+        assert_eq!(FORCE.to_string(), "kg*m/s^2");
+        assert_eq!(FREQUENCY.to_string(), "1/s");
+        assert_eq!(AREA.to_string(), "m^2");
+        assert_eq!(Dimension::NONE.to_string(), "1");
+    }
+
+    #[test]
+    fn test_units_convert() {
+        let distance = Quantity::new(1.5, parse_unit("km").unwrap());
+        assert_eq!(distance.to_string(), "1.5 km");
+        assert_eq!(distance.convert(parse_unit("m").unwrap()).unwrap().to_string(), "1500 m");
+        let error = distance.convert(parse_unit("s").unwrap()).unwrap_err();
+        assert_eq!(error.to_string(), "The dimensions m and s are incompatible");
+        assert_eq!(convert(Value::NUMBER(1.0), "foo"), Err(EvalError::UnknownUnit("foo".into())));
+    }
+}
@@ -0,0 +1,318 @@
+//! This module holds the **values** produced by the evaluation of an AST. Most expressions
+//! evaluate to a number, a lambda like `x -> x^2` evaluates to a function that can be stored,
+//! passed to the higher-order functions like `map` or `fold` and called later. A list like
+//! `[1, 2, 3]` or `1..12` holds numbers, the operators apply to every element. A list of lists
+//! like `[[1, 2], [3, 4]]` is a matrix, `*` is the matrix product and `^` an integer power
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use super::ast::{EvalError, Node};
+use super::datetime::DateTime;
+use super::matrix::Matrix;
+use super::units::Quantity;
+
+/// The name of the number type in the error messages
+pub const NUMBER_TYPE: &str = "a number";
+
+/// The name of the function type in the error messages
+pub const FUNCTION_TYPE: &str = "a function";
+
+/// The name of the list type in the error messages
+pub const LIST_TYPE: &str = "a list";
+
+/// The name of the matrix type in the error messages
+pub const MATRIX_TYPE: &str = "a matrix";
+
+/// The name of the quantity type in the error messages
+pub const QUANTITY_TYPE: &str = "a quantity";
+
+/// The name of the date type in the error messages
+pub const DATE_TYPE: &str = "a date";
+
+/// The name of the durations in the error messages, the quantities of time added to a date
+pub const DURATION_TYPE: &str = "a duration";
+
+/// The name of the integer type in the error messages, the powers of a matrix or a quantity
+/// must be integers
+pub const INTEGER_TYPE: &str = "an integer";
+
+/// The Value enum holds the result of an evaluation
+/// - NUMBER: a number, like the result of `1+2`
+/// - LAMBDA: an anonymous function, like the result of `x -> x^2`. The lambda is shared, so the
+///   value is small and copying it doesn't copy the body
+/// - LIST: a list of numbers, like the result of `[1, 2, 3]` or `1..3`
+/// - MATRIX: a matrix of numbers, like the result of `[[1, 2], [3, 4]]`
+/// - QUANTITY: a number with a physical unit, like the result of `3 km / 2 h`
+/// - DATE: a date and a time of the day, like the result of `2026-10-16 + 30 days`
+/// # Example
+/// ```
+/// let ast = Parser::new("x -> x^2").unwrap().parse().unwrap();
+/// let value = eval(&ast).unwrap();
+/// // This will be a Value::LAMBDA displayed as x->x^2
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    NUMBER(f64),
+    LAMBDA(Arc<Lambda>),
+    LIST(Vec<f64>),
+    MATRIX(Matrix),
+    QUANTITY(Quantity),
+    DATE(DateTime),
+}
+
+/// The Lambda struct holds an anonymous function created by the evaluation of a lambda node
+/// # Arguments
+/// * `parameters: Vec<String>` - the names of the parameters, in the order of the arguments
+/// * `body: Node` - the expression of the function
+/// * `captured: HashMap<String, Value>` - the parameters of the enclosing functions when the
+///   lambda was created, the body can read them after those functions returned
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lambda {
+    pub parameters: Vec<String>,
+    pub body: Node,
+    pub captured: HashMap<String, Value>,
+}
+
+impl Value {
+    /// Returns the number held by the value
+    /// # Returns
+    /// * `Result<f64, EvalError>` - the number or `EvalError::TypeMismatch` if isn't a number
+    /// # Example
+    /// ```
+    /// let number = Value::NUMBER(2.0).as_number();
+    /// // This will be Ok(2.0)
+    /// ```
+    pub fn as_number(&self) -> Result<f64, EvalError> {
+        match self {
+            Value::NUMBER(value) => Ok(*value),
+            _ => Err(not_a_number(self.type_name())),
+        }
+    }
+
+    /// Returns the lambda held by the value
+    /// # Returns
+    /// * `Result<&Lambda, EvalError>` - the lambda or `EvalError::TypeMismatch` if isn't a function
+    pub fn as_lambda(&self) -> Result<&Lambda, EvalError> {
+        match self {
+            Value::LAMBDA(lambda) => Ok(lambda),
+            _ => Err(EvalError::TypeMismatch(FUNCTION_TYPE.to_string(), self.type_name().to_string())),
+        }
+    }
+
+    /// Returns the numbers of the list held by the value
+    /// # Returns
+    /// * `Result<&[f64], EvalError>` - the numbers or `EvalError::TypeMismatch` if isn't a list
+    pub fn as_list(&self) -> Result<&[f64], EvalError> {
+        match self {
+            Value::LIST(numbers) => Ok(numbers),
+            _ => Err(EvalError::TypeMismatch(LIST_TYPE.to_string(), self.type_name().to_string())),
+        }
+    }
+
+    /// Returns the matrix held by the value
+    /// # Returns
+    /// * `Result<&Matrix, EvalError>` - the matrix or `EvalError::TypeMismatch` if isn't a matrix
+    pub fn as_matrix(&self) -> Result<&Matrix, EvalError> {
+        match self {
+            Value::MATRIX(matrix) => Ok(matrix),
+            _ => Err(EvalError::TypeMismatch(MATRIX_TYPE.to_string(), self.type_name().to_string())),
+        }
+    }
+
+    /// Returns the name of the type of the value, as it's written in the error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::NUMBER(_) => NUMBER_TYPE,
+            Value::LAMBDA(_) => FUNCTION_TYPE,
+            Value::LIST(_) => LIST_TYPE,
+            Value::MATRIX(_) => MATRIX_TYPE,
+            Value::QUANTITY(_) => QUANTITY_TYPE,
+            Value::DATE(_) => DATE_TYPE,
+        }
+    }
+}
+
+/// Applies an arithmetic operation to two values. Two numbers give a number, a list and a
+/// number apply the operation to every element and two lists to the elements in the same
+/// position, so they must have the same length. A matrix works like a list of its elements
+/// with a number or with a matrix of the same shape
+/// # Arguments
+/// * `left: Value, right: Value` - the operands
+/// * `operation: fn(f64, f64) -> f64` - the operation on two numbers
+/// # Returns
+/// * `Result<Value, EvalError>` - the result, `EvalError::LengthMismatch` for two lists of
+///   different lengths, `EvalError::ShapeMismatch` for two matrices of different shapes or a
+///   matrix and a list, or `EvalError::TypeMismatch` for a lambda
+/// # Example
+/// ```
+/// let doubled = broadcast(Value::LIST(vec![1.0, 2.0]), Value::NUMBER(2.0), |a, b| a * b);
+/// // This will be Ok(Value::LIST(vec![2.0, 4.0]))
+/// ```
+pub fn broadcast(left: Value, right: Value, operation: fn(f64, f64) -> f64) -> Result<Value, EvalError> {
+    match (left, right) {
+        (Value::NUMBER(left), Value::NUMBER(right)) => Ok(Value::NUMBER(operation(left, right))),
+        (Value::LIST(mut left), Value::NUMBER(right)) => {
+            left.iter_mut().for_each(|element| *element = operation(*element, right));
+            Ok(Value::LIST(left))
+        }
+        (Value::NUMBER(left), Value::LIST(mut right)) => {
+            right.iter_mut().for_each(|element| *element = operation(left, *element));
+            Ok(Value::LIST(right))
+        }
+        (Value::LIST(mut left), Value::LIST(right)) => {
+            if left.len() != right.len() {
+                return Err(EvalError::LengthMismatch(left.len(), right.len()));
+            }
+            left.iter_mut().zip(right).for_each(|(element, other)| *element = operation(*element, other));
+            Ok(Value::LIST(left))
+        }
+        (Value::MATRIX(left), Value::NUMBER(right)) => Ok(Value::MATRIX(left.map(|element| operation(element, right)))),
+        (Value::NUMBER(left), Value::MATRIX(right)) => Ok(Value::MATRIX(right.map(|element| operation(left, element)))),
+        (Value::MATRIX(left), Value::MATRIX(right)) => left.zip(&right, "combine", operation).map(Value::MATRIX),
+        (Value::LAMBDA(_), _) | (_, Value::LAMBDA(_)) => Err(not_a_number(FUNCTION_TYPE)),
+        (Value::QUANTITY(_), _) | (_, Value::QUANTITY(_)) => Err(not_a_number(QUANTITY_TYPE)),
+        (Value::DATE(_), _) | (_, Value::DATE(_)) => Err(not_a_number(DATE_TYPE)),
+        (left, right) => Err(EvalError::ShapeMismatch("combine".to_string(), shape(&left), shape(&right))),
+    }
+}
+
+/// Multiplies two values where one is a matrix. Two matrices give the matrix product, a matrix
+/// and a list are the product of the matrix and a column, or of a row and the matrix, and give a
+/// list. A matrix and a number multiply every element
+/// # Arguments
+/// * `left: Value, right: Value` - the factors
+/// # Returns
+/// * `Result<Value, EvalError>` - the product or `EvalError::ShapeMismatch` if the columns of
+///   the left factor aren't the rows of the right one
+/// # Example
+/// ```
+/// let matrix = Value::MATRIX(Matrix::from_rows(vec![vec![1.0, 2.0], vec![3.0, 4.0]]).unwrap());
+/// let product = multiply(matrix, Value::LIST(vec![1.0, 1.0]));
+/// // This will be Ok(Value::LIST(vec![3.0, 7.0]))
+/// ```
+pub fn multiply(left: Value, right: Value) -> Result<Value, EvalError> {
+    match (left, right) {
+        (Value::MATRIX(left), Value::MATRIX(right)) => left.multiply(&right).map(Value::MATRIX),
+        (Value::MATRIX(left), Value::LIST(right)) => {
+            let product = left.multiply(&Matrix::column(&right))?;
+            Ok(Value::LIST(product.data().to_vec()))
+        }
+        (Value::LIST(left), Value::MATRIX(right)) => {
+            let product = Matrix::column(&left).transpose().multiply(&right)?;
+            Ok(Value::LIST(product.data().to_vec()))
+        }
+        (left, right) => broadcast(left, right, |left, right| left * right),
+    }
+}
+
+/// Raises a matrix to an integer power, like `m^3` for `m*m*m`
+/// # Arguments
+/// * `base: &Matrix` - a square matrix
+/// * `exponent: Value` - a whole number, a negative power is a power of the inverse
+/// # Returns
+/// * `Result<Value, EvalError>` - the power or the error of `Matrix::power`
+pub fn power(base: &Matrix, exponent: Value) -> Result<Value, EvalError> {
+    base.power(exponent.as_number()?).map(Value::MATRIX)
+}
+
+// The rows and columns of a value in the errors of the matrices, a list is a column
+fn shape(value: &Value) -> (usize, usize) {
+    match value {
+        Value::MATRIX(matrix) => matrix.shape(),
+        Value::LIST(numbers) => (numbers.len(), 1),
+        _ => (1, 1),
+    }
+}
+
+// The error of an operation that needs a number, the numeric evaluators like the compiled
+// closures return it for the lambdas
+pub(crate) fn not_a_number(found: &str) -> EvalError {
+    EvalError::TypeMismatch(NUMBER_TYPE.to_string(), found.to_string())
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::NUMBER(value)
+    }
+}
+
+impl From<Vec<f64>> for Value {
+    fn from(numbers: Vec<f64>) -> Self {
+        Value::LIST(numbers)
+    }
+}
+
+// The trait for display with format! or println!, a lambda is written like the parser reads it
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::NUMBER(value) => write!(f, "{}", value),
+            Value::LAMBDA(lambda) => write!(f, "{}", lambda),
+            Value::LIST(numbers) => {
+                let numbers: Vec<String> = numbers.iter().map(|number| number.to_string()).collect();
+                write!(f, "[{}]", numbers.join(","))
+            }
+            Value::MATRIX(matrix) => write!(f, "{}", matrix),
+            Value::QUANTITY(quantity) => write!(f, "{}", quantity),
+            Value::DATE(date) => write!(f, "{}", date),
+        }
+    }
+}
+
+impl fmt::Display for Lambda {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.parameters.as_slice() {
+            [parameter] => write!(f, "{}->{}", parameter, self.body),
+            parameters => write!(f, "({})->{}", parameters.join(","), self.body),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsemath::parser::Parser;
+
+    #[test]
+    fn test_value_as_number() {
+        assert_eq!(Value::from(2.5).as_number(), Ok(2.5));
+        let body = Parser::new("x+y").unwrap().parse().unwrap();
+        let lambda = Value::LAMBDA(Arc::new(Lambda {
+            parameters: vec!["x".into(), "y".into()],
+            body,
+            captured: HashMap::new(),
+        }));
+        let error = lambda.as_number().unwrap_err();
+        assert_eq!(error.to_string(), "Expected a number but found a function");
+        assert!(lambda.as_lambda().is_ok());
+        assert!(Value::NUMBER(1.0).as_lambda().is_err());
+        let error = Value::from(vec![1.0]).as_number().unwrap_err();
+        assert_eq!(error.to_string(), "Expected a number but found a list");
+        assert_eq!(Value::from(vec![1.0]).as_list(), Ok(&[1.0][..]));
+    }
+
+    #[test]
+    fn test_value_broadcast() {
+        let list = || Value::from(vec![1.0, 2.0, 3.0]);
+        assert_eq!(broadcast(list(), Value::NUMBER(2.0), |a, b| a * b), Ok(Value::from(vec![2.0, 4.0, 6.0])));
+        assert_eq!(broadcast(Value::NUMBER(1.0), list(), |a, b| a - b), Ok(Value::from(vec![0.0, -1.0, -2.0])));
+        assert_eq!(broadcast(list(), list(), |a, b| a + b), Ok(Value::from(vec![2.0, 4.0, 6.0])));
+        let short = Value::from(vec![1.0]);
+        assert_eq!(broadcast(list(), short, |a, b| a + b), Err(EvalError::LengthMismatch(3, 1)));
+    }
+
+    #[test]
+    fn test_value_display() {
+        assert_eq!(Value::NUMBER(0.5).to_string(), "0.5");
+        let body = Parser::new("x^2").unwrap().parse().unwrap();
+        let lambda = Lambda {
+            parameters: vec!["x".into()],
+            body,
+            captured: HashMap::new(),
+        };
+        assert_eq!(lambda.to_string(), "x->x^2");
+        assert_eq!(Value::from(vec![1.0, 2.5]).to_string(), "[1,2.5]");
+    }
+}
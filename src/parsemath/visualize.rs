@@ -0,0 +1,273 @@
+//! This module holds the **visualizers** of the AST, they draw the tree as a Graphviz DOT graph
+//! or as an indented ASCII tree for the terminal, so we can see how the parser grouped the
+//! operations of an expression
+
+use super::ast::{self, Node};
+
+/// Returns the text that identifies a node in the drawings, the operation name and the number
+/// or the function name when the node has one
+/// # Arguments
+/// * `node: &Node` - the node to describe
+/// # Example
+/// ```
+/// let label = node_label(&Node::NUMBER(5.0));
+/// // This will be "NUMBER 5"
+/// ```
+pub fn node_label(node: &Node) -> String {
+    use super::ast::Node::*;
+    match node {
+        ADD(..) => "ADD".to_string(),
+        SUBTRACT(..) => "SUBTRACT".to_string(),
+        MULTIPLY(..) => "MULTIPLY".to_string(),
+        DIVIDE(..) => "DIVIDE".to_string(),
+        CARRET(..) => "CARRET".to_string(),
+        NEGATIVE(..) => "NEGATIVE".to_string(),
+        FUNCTION(name, _) => format!("FUNCTION {}", name),
+        VARIABLE(name) => format!("VARIABLE {}", name),
+        NUMBER(value) => format!("NUMBER {}", value),
+        ERROR => "ERROR".to_string(),
+        LAMBDA(parameters, _) => format!("LAMBDA {}", parameters.join(",")),
+        LIST(..) => "LIST".to_string(),
+        INDEX(..) => "INDEX".to_string(),
+        PERCENT(..) => "PERCENT".to_string(),
+        RANGE(..) => "RANGE".to_string(),
+        QUANTITY(value, unit) => format!("QUANTITY {} {}", value, unit),
+        CONVERT(_, unit) => format!("CONVERT {}", unit),
+        DATE(date) => format!("DATE {}", date),
+    }
+}
+
+// The labels of the nodes in preorder. When they are annotated the values of all the subtrees
+// are computed in one pass, numbers are already their value
+fn labels(node: &Node, annotate: bool) -> Vec<String> {
+    let nodes = preorder(node);
+    let values = if annotate { ast::eval_subtrees(node) } else { Vec::new() };
+    let mut values = values.into_iter();
+    let label = |node: &Node| {
+        let label = node_label(node);
+        match values.next() {
+            None => label,
+            Some(_) if matches!(node, Node::NUMBER(_)) => label,
+            Some(Ok(value)) => format!("{} = {}", label, value),
+            Some(Err(e)) => format!("{} = error: {}", label, e),
+        }
+    };
+    nodes.into_iter().map(label).collect()
+}
+
+fn preorder(node: &Node) -> Vec<&Node> {
+    let mut nodes = Vec::new();
+    let mut pending = vec![node];
+    while let Some(node) = pending.pop() {
+        nodes.push(node);
+        // The first child is pushed last, so it's the next node
+        pending.extend(node.children().into_iter().rev());
+    }
+    nodes
+}
+
+/// Draws an AST as a Graphviz DOT directed graph
+/// # Arguments
+/// * `node: &Node` - the root of the AST
+/// * `annotate: bool` - if each operation shows the value of its subtree
+/// # Returns
+/// * `String` - the DOT source, it can be rendered with `dot -Tpng`
+/// # Example
+/// ```
+/// let mut parser = Parser::new("1+2").unwrap();
+/// let dot = to_dot(&parser.parse().unwrap(), false);
+/// // digraph AST {
+/// //     node [shape=box];
+/// //     n0 [label="ADD"];
+/// //     n1 [label="NUMBER 1"];
+/// //     n0 -> n1;
+/// //     ...
+/// ```
+pub fn to_dot(node: &Node, annotate: bool) -> String {
+    let mut out = String::from("digraph AST {\n    node [shape=box];\n");
+    let mut labels = labels(node, annotate).into_iter();
+    let mut next_id = 0;
+    // We draw without recursion so the trees deeper than the stack can be drawn too, the edge to
+    // a node is written once its subtree is written
+    let mut tasks = vec![DotTask::Visit(node, None)];
+    while let Some(task) = tasks.pop() {
+        match task {
+            DotTask::Visit(node, parent) => {
+                let id = next_id;
+                next_id += 1;
+                let label = labels.next().unwrap().replace('\\', "\\\\").replace('"', "\\\"");
+                out.push_str(&format!("    n{} [label=\"{}\"];\n", id, label));
+                if let Some(parent) = parent {
+                    tasks.push(DotTask::Edge(parent, id));
+                }
+                tasks.extend(node.children().into_iter().rev().map(|child| DotTask::Visit(child, Some(id))));
+            }
+            DotTask::Edge(parent, id) => out.push_str(&format!("    n{} -> n{};\n", parent, id)),
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+// The pending work of to_dot, a node to write with the id of its parent or an edge to write
+enum DotTask<'a> {
+    Visit(&'a Node, Option<usize>),
+    Edge(usize, usize),
+}
+
+/// Draws an AST as an indented tree with box-drawing characters
+/// # Arguments
+/// * `node: &Node` - the root of the AST
+/// * `annotate: bool` - if each operation shows the value of its subtree
+/// # Returns
+/// * `String` - the tree, one node per line
+/// # Example
+/// ```
+/// let mut parser = Parser::new("1+2*3").unwrap();
+/// let tree = to_ascii_tree(&parser.parse().unwrap(), true);
+/// // ADD = 7
+/// // ├── NUMBER 1
+/// // └── MULTIPLY = 6
+/// //     ├── NUMBER 2
+/// //     └── NUMBER 3
+/// ```
+pub fn to_ascii_tree(node: &Node, annotate: bool) -> String {
+    let mut labels = labels(node, annotate).into_iter();
+    let mut out = labels.next().unwrap();
+    out.push('\n');
+    // We draw without recursion so the trees deeper than the stack can be drawn too, each
+    // pending node keeps the prefix of its line and if it's the last child
+    let mut pending = Vec::new();
+    push_ascii_children(&mut pending, node, "");
+    while let Some((node, prefix, last)) = pending.pop() {
+        // The last child closes the branch so its children don't continue the line
+        let (branch, indent) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+        out.push_str(&prefix);
+        out.push_str(branch);
+        out.push_str(&labels.next().unwrap());
+        out.push('\n');
+        push_ascii_children(&mut pending, node, &format!("{}{}", prefix, indent));
+    }
+    out
+}
+
+// The first child is pushed last, so it's the next node drawn
+fn push_ascii_children<'a>(pending: &mut Vec<(&'a Node, String, bool)>, node: &'a Node, prefix: &str) {
+    let children = node.children();
+    let count = children.len();
+    for (i, child) in children.into_iter().enumerate().rev() {
+        pending.push((child, prefix.to_string(), i + 1 == count));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsemath::parser::Parser;
+
+    fn parse(expr: &str) -> Node {
+        let mut parser = Parser::new(expr).unwrap();
+        parser.parse().unwrap()
+    }
+
+    #[test]
+    fn test_visualize_ascii_tree() {
+        let tree = to_ascii_tree(&parse("1+2*3"), false);
+        let expected = [
+            "ADD",
+            "├── NUMBER 1",
+            "└── MULTIPLY",
+            "    ├── NUMBER 2",
+            "    └── NUMBER 3",
+            "",
+        ];
+        assert_eq!(tree, expected.join("\n"));
+    }
+
+    #[test]
+    fn test_visualize_ascii_tree_annotated() {
+        let tree = to_ascii_tree(&parse("(1+2)*-sqrt(4)"), true);
+        let expected = [
+            "MULTIPLY = -6",
+            "├── ADD = 3",
+            "│   ├── NUMBER 1",
+            "│   └── NUMBER 2",
+            "└── NEGATIVE = -2",
+            "    └── FUNCTION sqrt = 2",
+            "        └── NUMBER 4",
+            "",
+        ];
+        assert_eq!(tree, expected.join("\n"));
+    }
+
+    #[test]
+    fn test_visualize_dot() {
+        let dot = to_dot(&parse("-2^3"), true);
+        let expected = [
+            "digraph AST {",
+            "    node [shape=box];",
+            "    n0 [label=\"CARRET = -8\"];",
+            "    n1 [label=\"NEGATIVE = -2\"];",
+            "    n2 [label=\"NUMBER 2\"];",
+            "    n1 -> n2;",
+            "    n0 -> n1;",
+            "    n3 [label=\"NUMBER 3\"];",
+            "    n0 -> n3;",
+            "}",
+            "",
+        ];
+        assert_eq!(dot, expected.join("\n"));
+    }
+
+    #[test]
+    fn test_visualize_annotations_match_eval() {
+        // The values of the single pass are the values of every subtree evaluated alone
+        // And the formulas that have a value must not only compare errors
+        let formulas = [
+            ("(1+2)*-sqrt(4)^2/5", true),
+            ("max([1, 2, 3][1], 10%) + 5 km in m", false),
+            ("if(x, 1/0, 2) + sum(i, 1, 3, i^2)", false),
+            ("fold((a, i) -> a + i, 0, 1, 4) * len(1..3)", true),
+            ("[[1, 2], [3, 4]] * [1, 2][0] + foo(2)", false),
+            ("2026-10-16 + 3 days", true),
+        ];
+        for (formula, valid) in formulas.iter() {
+            let ast = parse(formula);
+            assert_eq!(ast::eval(&ast).is_ok(), *valid, "{}", formula);
+            let expected = preorder(&ast)
+                .into_iter()
+                .map(|node| match (node, ast::eval(node)) {
+                    (Node::NUMBER(_), _) => node_label(node),
+                    (_, Ok(value)) => format!("{} = {}", node_label(node), value),
+                    (_, Err(e)) => format!("{} = error: {}", node_label(node), e),
+                })
+                .collect::<Vec<String>>();
+            assert_eq!(labels(&ast, true), expected, "{}", formula);
+        }
+    }
+
+    #[test]
+    fn test_visualize_deep_tree() {
+        // The default limits of the parser don't bound a tree built by hand
+        let mut node = Node::NUMBER(1.0);
+        for _ in 0..100_000 {
+            node = Node::NEGATIVE(Box::new(node));
+        }
+        let dot = to_dot(&node, false);
+        assert!(dot.contains("    n100000 [label=\"NUMBER 1\"];\n    n99999 -> n100000;\n"));
+        // Every line of the ASCII tree repeats the indentation, so it stays shallower
+        let mut node = Node::NUMBER(1.0);
+        for _ in 0..2_000 {
+            node = Node::NEGATIVE(Box::new(node));
+        }
+        let tree = to_ascii_tree(&node, false);
+        assert_eq!(tree.lines().count(), 2_001);
+        assert!(tree.ends_with(&format!("{}└── NUMBER 1\n", "    ".repeat(1_999))));
+    }
+
+    #[test]
+    fn test_visualize_error_annotation() {
+        let node = Node::FUNCTION("foo".into(), vec![Node::NUMBER(1.0)]);
+        assert_eq!(to_ascii_tree(&node, true), "FUNCTION foo = error: Unknown function foo\n└── NUMBER 1\n");
+    }
+}
@@ -0,0 +1,331 @@
+//! This module holds the **bytecode compiler** and the **stack virtual machine**. An AST is
+//! compiled once to a flat list of instructions where the variables are already resolved to
+//! slots, then the virtual machine runs the instructions as many times as needed with
+//! different values for the slots, without walking the tree or looking names up
+
+use super::{
+    ast::{EvalError, Node},
+    constants,
+    functions::{Builtin, HigherOrder, CONDITIONAL_NAME},
+    value::{not_a_number, DATE_TYPE, FUNCTION_TYPE, LIST_TYPE, QUANTITY_TYPE},
+};
+
+/// The Instruction enum holds the operations of the virtual machine, the operands are taken
+/// from the top of the stack and the result is pushed back
+/// - CONST: pushes a number
+/// - LOAD: pushes the value of the variable in the slot
+/// - ADD, SUBTRACT, MULTIPLY, DIVIDE, POWER: take two values and push the result
+/// - NEGATE: takes a value and pushes it with the sign changed
+/// - CALL: takes as many values as the arity of the function and pushes the result
+/// - BRANCH: takes a value and goes on at the instruction with the given index if it's zero
+/// - JUMP: goes on at the instruction with the given index
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
+    CONST(f64),
+    LOAD(usize),
+    ADD,
+    SUBTRACT,
+    MULTIPLY,
+    DIVIDE,
+    POWER,
+    NEGATE,
+    CALL(Builtin),
+    BRANCH(usize),
+    JUMP(usize),
+}
+
+/// The Bytecode struct holds a compiled expression, is created with `Bytecode::compile` that
+/// checks all the names, so the virtual machine doesn't need to check them again
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bytecode {
+    instructions: Vec<Instruction>,
+    variables: Vec<String>,
+    max_stack: usize,
+}
+
+impl Bytecode {
+    /// Compiles an AST to bytecode
+    /// # Arguments
+    /// * `node: &Node` - the root of the AST
+    /// * `variables: &[&str]` - the names of the variables, the position of a name is its slot
+    /// # Returns
+    /// * `Result<Bytecode, EvalError>` - the bytecode or an error if the AST uses a variable that
+    ///   isn't in the list, an unknown function or a function with the wrong number of arguments
+    /// # Example
+    /// ```
+    /// let ast = Parser::new("x*2+y").unwrap().parse().unwrap();
+    /// let code = Bytecode::compile(&ast, &["x", "y"]).unwrap();
+    /// // The instructions will be [LOAD(0), CONST(2.0), MULTIPLY, LOAD(1), ADD]
+    /// ```
+    pub fn compile(node: &Node, variables: &[&str]) -> Result<Bytecode, EvalError> {
+        let mut code = Bytecode {
+            instructions: Vec::new(),
+            variables: variables.iter().map(|name| name.to_string()).collect(),
+            max_stack: 0,
+        };
+        code.compile_node(node, 0)?;
+        Ok(code)
+    }
+
+    /// Returns the compiled instructions
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// Returns the names of the variables, in the order of their slots
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    // Emits the instructions of a node, depth is the number of values already in the stack
+    fn compile_node(&mut self, node: &Node, depth: usize) -> Result<(), EvalError> {
+        use super::ast::Node::*;
+        let instruction = match node {
+            NUMBER(value) => Instruction::CONST(*value),
+            VARIABLE(name) => match self.variables.iter().position(|variable| variable == name) {
+                Some(slot) => Instruction::LOAD(slot),
+                None => match constants::builtin(name) {
+                    Some(value) => Instruction::CONST(value),
+                    None => return Err(EvalError::UnknownVariable(name.clone())),
+                },
+            },
+            ERROR => return Err(EvalError::SyntaxError),
+            // The stack only holds numbers
+            LAMBDA(..) => return Err(not_a_number(FUNCTION_TYPE)),
+            LIST(..) | INDEX(..) | RANGE(..) => return Err(not_a_number(LIST_TYPE)),
+            QUANTITY(..) | CONVERT(..) => return Err(not_a_number(QUANTITY_TYPE)),
+            DATE(_) => return Err(not_a_number(DATE_TYPE)),
+            NEGATIVE(expr) => {
+                self.compile_node(expr, depth)?;
+                Instruction::NEGATE
+            }
+            // A percentage is its operand divided by 100
+            PERCENT(expr) => {
+                self.compile_node(expr, depth)?;
+                self.compile_node(&NUMBER(100.0), depth + 1)?;
+                Instruction::DIVIDE
+            }
+            ADD(left, right)
+            | SUBTRACT(left, right)
+            | MULTIPLY(left, right)
+            | DIVIDE(left, right)
+            | CARRET(left, right) => {
+                // The left value stays in the stack while the right one is computed
+                self.compile_node(left, depth)?;
+                self.compile_node(right, depth + 1)?;
+                match node {
+                    ADD(..) => Instruction::ADD,
+                    SUBTRACT(..) => Instruction::SUBTRACT,
+                    MULTIPLY(..) => Instruction::MULTIPLY,
+                    DIVIDE(..) => Instruction::DIVIDE,
+                    _ => Instruction::POWER,
+                }
+            }
+            FUNCTION(name, args) if name == CONDITIONAL_NAME => return self.compile_conditional(args, depth),
+            FUNCTION(name, args) => {
+                let builtin = match Builtin::from_name(name) {
+                    Some(builtin) => builtin,
+                    None if HigherOrder::from_name(name).is_some() => {
+                        return Err(EvalError::UnsupportedFunction(name.clone()))
+                    }
+                    None => return Err(EvalError::UnknownFunction(name.clone())),
+                };
+                if builtin.arity() != args.len() {
+                    return Err(EvalError::WrongArgumentCount(
+                        name.clone(),
+                        builtin.arity(),
+                        args.len(),
+                    ));
+                }
+                for (i, arg) in args.iter().enumerate() {
+                    self.compile_node(arg, depth + i)?;
+                }
+                Instruction::CALL(builtin)
+            }
+        };
+        // Every instruction leaves one more value over the ones below it
+        self.max_stack = self.max_stack.max(depth + 1);
+        self.instructions.push(instruction);
+        Ok(())
+    }
+
+    // Emits if(condition, then, else) with jumps, so only the chosen branch is run
+    fn compile_conditional(&mut self, args: &[Node], depth: usize) -> Result<(), EvalError> {
+        if args.len() != 3 {
+            return Err(EvalError::WrongArgumentCount(CONDITIONAL_NAME.to_string(), 3, args.len()));
+        }
+        self.compile_node(&args[0], depth)?;
+        let to_otherwise = self.instructions.len();
+        self.instructions.push(Instruction::BRANCH(0));
+        self.compile_node(&args[1], depth)?;
+        let to_end = self.instructions.len();
+        self.instructions.push(Instruction::JUMP(0));
+        // The targets are known once the branches are emitted
+        self.instructions[to_otherwise] = Instruction::BRANCH(self.instructions.len());
+        self.compile_node(&args[2], depth)?;
+        self.instructions[to_end] = Instruction::JUMP(self.instructions.len());
+        Ok(())
+    }
+}
+
+/// The Vm struct holds the stack of the virtual machine, the stack is kept between the runs so
+/// running the same bytecode many times doesn't allocate
+/// # Example
+/// ```
+/// let ast = Parser::new("x*2+y").unwrap().parse().unwrap();
+/// let code = Bytecode::compile(&ast, &["x", "y"]).unwrap();
+/// let mut vm = Vm::new();
+/// let value = vm.run(&code, &[3.0, 1.0]);
+/// // This will be Ok(7.0)
+/// ```
+#[derive(Debug, Default)]
+pub struct Vm {
+    stack: Vec<f64>,
+}
+
+impl Vm {
+    /// Returns a new virtual machine with an empty stack
+    pub fn new() -> Self {
+        Vm { stack: Vec::new() }
+    }
+
+    /// Runs the bytecode with the values of the variables
+    /// # Arguments
+    /// * `code: &Bytecode` - the compiled expression
+    /// * `values: &[f64]` - the values of the variables, in the order of their slots
+    /// # Returns
+    /// * `Result<f64, EvalError>` - the value of the expression or an error if the number of
+    ///   values isn't the number of variables
+    pub fn run(&mut self, code: &Bytecode, values: &[f64]) -> Result<f64, EvalError> {
+        if values.len() != code.variables.len() {
+            return Err(EvalError::WrongVariableCount(code.variables.len(), values.len()));
+        }
+        let stack = &mut self.stack;
+        stack.clear();
+        stack.reserve(code.max_stack);
+        // The compiler checked that every instruction has its operands in the stack
+        let mut next = 0;
+        while let Some(instruction) = code.instructions.get(next) {
+            next += 1;
+            match *instruction {
+                Instruction::CONST(value) => stack.push(value),
+                Instruction::LOAD(slot) => stack.push(values[slot]),
+                Instruction::NEGATE => {
+                    let top = stack.last_mut().unwrap();
+                    *top = -*top;
+                }
+                Instruction::CALL(builtin) => {
+                    let start = stack.len() - builtin.arity();
+                    let value = builtin.apply(&stack[start..]);
+                    stack.truncate(start);
+                    stack.push(value);
+                }
+                Instruction::BRANCH(target) => {
+                    if stack.pop().unwrap() == 0.0 {
+                        next = target;
+                    }
+                }
+                Instruction::JUMP(target) => next = target,
+                operator => {
+                    let right = stack.pop().unwrap();
+                    let left = stack.last_mut().unwrap();
+                    *left = match operator {
+                        Instruction::ADD => *left + right,
+                        Instruction::SUBTRACT => *left - right,
+                        Instruction::MULTIPLY => *left * right,
+                        Instruction::DIVIDE => *left / right,
+                        _ => left.powf(right),
+                    };
+                }
+            }
+        }
+        Ok(stack.pop().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsemath::{ast, parser::Parser};
+    use std::collections::HashMap;
+
+    fn parse(expr: &str) -> Node {
+        let mut parser = Parser::new(expr).unwrap();
+        parser.parse().unwrap()
+    }
+
+    #[test]
+    fn test_vm_compile() {
+        let code = Bytecode::compile(&parse("x*2+-y"), &["x", "y"]).unwrap();
+        let expected = [
+            Instruction::LOAD(0),
+            Instruction::CONST(2.0),
+            Instruction::MULTIPLY,
+            Instruction::LOAD(1),
+            Instruction::NEGATE,
+            Instruction::ADD,
+        ];
+        assert_eq!(code.instructions(), &expected);
+        assert_eq!(code.max_stack, 2);
+        // A constant is a number unless a variable has its name
+        let code = Bytecode::compile(&parse("e*pi"), &["e"]).unwrap();
+        let expected = [Instruction::LOAD(0), Instruction::CONST(std::f64::consts::PI), Instruction::MULTIPLY];
+        assert_eq!(code.instructions(), &expected);
+    }
+
+    #[test]
+    fn test_vm_compile_errors() {
+        assert_eq!(
+            Bytecode::compile(&parse("x+z"), &["x"]),
+            Err(EvalError::UnknownVariable("z".into()))
+        );
+        assert_eq!(
+            Bytecode::compile(&parse("foo(1)"), &[]),
+            Err(EvalError::UnknownFunction("foo".into()))
+        );
+        assert_eq!(
+            Bytecode::compile(&parse("max(1)"), &[]),
+            Err(EvalError::WrongArgumentCount("max".into(), 2, 1))
+        );
+        assert_eq!(
+            Bytecode::compile(&parse("prod(i, 1, 3, i)"), &[]),
+            Err(EvalError::UnsupportedFunction("prod".into()))
+        );
+    }
+
+    #[test]
+    fn test_vm_run_matches_eval() {
+        let exprs = [
+            "1+2*3-4/5",
+            "(1+x)^-(3/y)",
+            "2^3^4",
+            "--x*y",
+            "max(x,sqrt(y))*abs(-3)+min(1,x)",
+            "p*r/(1-(1+r)^-n)",
+            "if(x-1.5, 1/0, y)+if(n, p, sqrt(-1))",
+            "if(if(0, x, 0), x, -y)^2",
+        ];
+        let names = ["x", "y", "p", "r", "n"];
+        let values = [1.5, 4.0, 1000.0, 0.05, 12.0];
+        let variables: HashMap<String, f64> = names
+            .iter()
+            .map(|name| name.to_string())
+            .zip(values.iter().copied())
+            .collect();
+        let mut vm = Vm::new();
+        for expr in exprs.iter() {
+            let ast = parse(expr);
+            let code = Bytecode::compile(&ast, &names).unwrap();
+            let expected = ast::eval_with(&ast, &variables).unwrap().as_number().unwrap();
+            assert_eq!(vm.run(&code, &values).unwrap(), expected, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn test_vm_wrong_variable_count() {
+        let code = Bytecode::compile(&parse("x+y"), &["x", "y"]).unwrap();
+        let mut vm = Vm::new();
+        assert_eq!(vm.run(&code, &[1.0]), Err(EvalError::WrongVariableCount(2, 1)));
+        assert_eq!(vm.run(&code, &[1.0, 2.0]), Ok(3.0));
+    }
+}
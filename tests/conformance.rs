@@ -0,0 +1,281 @@
+//! The conformance suite checks that the recursive parser and evaluator and the iterative ones
+//! give the same results, the same AST and value for the valid inputs and the same error for
+//! the invalid ones. The Pratt parser with the default table must give the same ASTs too. The
+//! evaluators are compared on the formulas that only hold numbers, the closures, the bytecode, the
+//! shared formulas and the batches must give the value of the recursive evaluator when it has one
+
+use std::collections::HashMap;
+
+use arithmetic_paser::parsemath::{
+    ast::{self, EvalError, Node},
+    batch, closure,
+    cse::FormulaSet,
+    functions::HigherOrder,
+    iterative,
+    limits::Limits,
+    parser::Parser,
+    pratt::{OperatorTable, PrattParser},
+    vm::{Bytecode, Vm},
+};
+
+const CASES: [&str; 94] = [
+    // Numbers and operators
+    "1",
+    "2.5",
+    "1+2",
+    "1-2-3",
+    "2*3+4",
+    "2+3*4",
+    "8/4/2",
+    "2^3^2",
+    "1+2*3^2-4/5",
+    "1 + 2 ** 3",
+    "  7  ",
+    // Negatives
+    "-1",
+    "--1",
+    "-2^2",
+    "-(1+2)",
+    "3*-2",
+    "1--1",
+    "2^-1",
+    // Parentheses and implicit multiplication
+    "(1+2)*3",
+    "((((1))))",
+    "(1+2)(3+4)",
+    "(2)(3)(4)",
+    "(2)(3)^2",
+    "-(2)(3)",
+    "(1)(2)*3+4",
+    // Calls and variables
+    "sqrt(16)",
+    "max(1,2*3)",
+    "min(x,y)+abs(-x)",
+    "sqrt(x^2+y^2)",
+    "x*y-x/y",
+    "log(1000)+ln(1)",
+    "foo()",
+    "max(1)",
+    "z+1",
+    "1/0",
+    "sqrt(-1)",
+    // Conditionals, the branch that isn't taken is never evaluated
+    "if(1, 2, 3)",
+    "if(x-3, 1/0, y)",
+    "if(x-3, sqrt(-1), -x)",
+    "if(0, 1/0, 2) + if(y, 2, sqrt(-1))",
+    "if(1, 2)",
+    // Percentages
+    "200 + 15%",
+    "x% * 2",
+    "5%%",
+    "-5%^2",
+    "50% of 80",
+    "50% of 80 + 2",
+    "(10)%(2)",
+    "-v[x+1]%",
+    // Units and conversions
+    "3 km",
+    "3 km + 500 m",
+    "90 km/h in m/s",
+    "(1 km + 1 m) in m",
+    "x in km",
+    "3 km in",
+    "3 km in 2",
+    // Lists, indexes and ranges
+    "[1, 2]",
+    "[]",
+    "[1, [2, 3]][1][0]",
+    "v[0]",
+    "(1)[0](2)",
+    "1..3",
+    "sum(1..3)",
+    "[1..3, 4]",
+    "1..2..3",
+    "[1, 2",
+    "v[0",
+    // Lambdas
+    "x -> x",
+    "x -> x + 1",
+    "(x, y) -> x*y",
+    "(x) -> x^2",
+    "map([1, 2], x -> x^2)",
+    "sum(i, 1, 3, i)",
+    "x + prod(i, 1, 3, i^2)",
+    "fold((a, i) -> a + i, 0, 1, 3)",
+    "(x, x) -> x",
+    "(x, 1) -> x",
+    "(x, y)",
+    // Dates
+    "2026-10-16",
+    "2026-10-16 + 30 days",
+    "(2026-10-18 - 2026-10-16) in hours",
+    "2026-10-16%",
+    // Trailing tokens that the parser stops before
+    "1 2",
+    "(1+2))",
+    "sqrt(4)(2)",
+    // Errors
+    "",
+    "+",
+    "1+",
+    "(1+2",
+    "max(1,2",
+    "max(1 2)",
+    "1+$",
+    "2(3)",
+    "*2",
+];
+
+fn variables() -> HashMap<String, f64> {
+    let mut variables = HashMap::new();
+    variables.insert("x".to_string(), 3.0);
+    variables.insert("y".to_string(), -4.5);
+    variables
+}
+
+fn parse_recursive(expr: &str) -> Result<Node, String> {
+    Parser::with_limits(expr, Limits::unlimited())
+        .and_then(|mut parser| parser.parse())
+        .map_err(|e| e.to_string())
+}
+
+// The iterative evaluator only computes the ASTs without lists, lambdas, quantities or dates
+fn numeric(node: &Node) -> bool {
+    match node {
+        Node::LAMBDA(..) | Node::LIST(_) | Node::INDEX(..) | Node::RANGE(..) => false,
+        Node::QUANTITY(..) | Node::CONVERT(..) | Node::DATE(_) => false,
+        _ => node.children().into_iter().all(numeric),
+    }
+}
+
+// The first function that binds variables like sum, in the order of evaluation
+fn binding_function(node: &Node) -> Option<String> {
+    match node {
+        Node::FUNCTION(name, _) if HigherOrder::from_name(name).is_some() => Some(name.clone()),
+        _ => node.children().into_iter().find_map(binding_function),
+    }
+}
+
+fn same_value(left: f64, right: f64) -> bool {
+    left == right || (left.is_nan() && right.is_nan())
+}
+
+// Checks one input with both implementations
+fn check_conformance(expr: &str) {
+    let recursive = parse_recursive(expr);
+    let iterative = iterative::parse(expr).map_err(|e| e.to_string());
+    assert_eq!(recursive, iterative, "parsing {:?}", expr);
+    let table = OperatorTable::default();
+    let pratt = PrattParser::with_limits(expr, &table, Limits::unlimited())
+        .and_then(|mut parser| parser.parse())
+        .map_err(|e| e.to_string());
+    assert_eq!(recursive, pratt, "parsing {:?} with the Pratt parser", expr);
+    let ast = match recursive {
+        Ok(ast) => ast,
+        Err(_) => return,
+    };
+    if let Some(name) = binding_function(&ast) {
+        // The bound variables aren't reported as unknown, the function itself is rejected
+        let error = Err(EvalError::UnsupportedFunction(name));
+        assert_eq!(iterative::eval_with(&ast, &variables()), error, "evaluating {:?}", expr);
+        let set = FormulaSet::new(std::slice::from_ref(&ast));
+        assert_eq!(set.evaluate(&variables()).map(|values| values[0]), error, "evaluating {:?}", expr);
+        return;
+    }
+    if !numeric(&ast) {
+        return;
+    }
+    // The formula only has numbers, so the recursive evaluator always returns a number
+    let recursive = ast::eval_with(&ast, &variables()).and_then(|value| Ok(value.as_number()?));
+    let recursive = recursive.map_err(|e| e.to_string());
+    let iterative = iterative::eval_with(&ast, &variables()).map_err(|e| e.to_string());
+    match (&recursive, iterative) {
+        (Ok(left), Ok(right)) => {
+            assert!(same_value(*left, right), "evaluating {:?}: {} and {}", expr, left, right)
+        }
+        (left, right) => assert_eq!(left, &right, "evaluating {:?}", expr),
+    }
+    if let Ok(value) = recursive {
+        if value.is_finite() {
+            check_backends(expr, &ast, value);
+        }
+    }
+}
+
+// Checks that the compiled evaluators give the finite value of the recursive one
+fn check_backends(expr: &str, ast: &Node, expected: f64) {
+    let names = ["x", "y"];
+    let values = [variables()["x"], variables()["y"]];
+    let function = closure::compile(ast, &names).expect(expr);
+    assert_eq!(function(&values), Ok(expected), "evaluating {:?} with a closure", expr);
+    let code = Bytecode::compile(ast, &names).expect(expr);
+    let value = Vm::new().run(&code, &values).expect(expr);
+    assert_eq!(value, expected, "evaluating {:?} with the bytecode", expr);
+    let value = FormulaSet::new(std::slice::from_ref(ast)).evaluate(&variables()).expect(expr);
+    assert_eq!(value, vec![expected], "evaluating {:?} with the shared formulas", expr);
+    let output = batch::evaluate_columns(ast, &[("x", &values[..1]), ("y", &values[1..])]).expect(expr);
+    assert_eq!(output.values, vec![expected], "evaluating {:?} in a batch", expr);
+}
+
+#[test]
+fn test_conformance_cases() {
+    for expr in CASES.iter() {
+        check_conformance(expr);
+    }
+}
+
+#[test]
+fn test_conformance_random_expressions() {
+    // We build random expressions from a small xorshift generator, so every run is the same
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut next = move |limit: u64| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state % limit
+    };
+    let atoms = ["1", "2.5", "x", "y", "sqrt(4)", "max(x,1)"];
+    let operators = ["+", "-", "*", "/", "^"];
+    for _ in 0..500 {
+        let mut expr = String::new();
+        let mut open = 0;
+        for _ in 0..=next(8) {
+            match next(4) {
+                0 => expr.push('-'),
+                1 => {
+                    expr.push('(');
+                    open += 1;
+                }
+                _ => (),
+            }
+            expr.push_str(atoms[next(atoms.len() as u64) as usize]);
+            if open > 0 && next(3) == 0 {
+                expr.push(')');
+                open -= 1;
+            }
+            expr.push_str(operators[next(operators.len() as u64) as usize]);
+        }
+        expr.push_str(atoms[next(atoms.len() as u64) as usize]);
+        // Some of the inputs keep parentheses open, both parsers must fail in the same way
+        for _ in 0..next(open + 1) {
+            expr.push(')');
+        }
+        check_conformance(&expr);
+    }
+}
+
+#[test]
+fn test_conformance_deep_expressions() {
+    // The recursive versions handle these depths in the main test thread
+    for expr in [
+        "-".repeat(200) + "1",
+        "(".repeat(200) + "x" + &")".repeat(200),
+        vec!["1"; 200].join("^"),
+        vec!["x"; 300].join("-"),
+    ]
+    .iter()
+    {
+        check_conformance(expr);
+    }
+}